@@ -0,0 +1 @@
+ALTER TABLE accounts DROP COLUMN banned_until_us;
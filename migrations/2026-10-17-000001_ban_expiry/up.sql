@@ -0,0 +1,2 @@
+-- Bans may now expire. NULL means the ban lasts until someone lifts it.
+ALTER TABLE accounts ADD COLUMN banned_until_us BIGINT;
@@ -0,0 +1,2 @@
+-- Posts may be replies to other posts.
+ALTER TABLE posts ADD COLUMN parent_id INTEGER REFERENCES posts (id);
@@ -0,0 +1,3 @@
+ALTER TABLE boards DROP COLUMN sysop_post;
+ALTER TABLE boards DROP COLUMN read_only;
+ALTER TABLE boards DROP COLUMN hidden;
@@ -0,0 +1,6 @@
+-- Hidden boards are only visible to sysops.
+ALTER TABLE boards ADD COLUMN hidden BOOL NOT NULL DEFAULT FALSE;
+-- Nobody can post to read-only boards over the radio.
+ALTER TABLE boards ADD COLUMN read_only BOOL NOT NULL DEFAULT FALSE;
+-- Only sysops can post to sysop_post boards.
+ALTER TABLE boards ADD COLUMN sysop_post BOOL NOT NULL DEFAULT FALSE;
@@ -0,0 +1,11 @@
+-- Who did what to whom. Actors and targets are node IDs rather than foreign keys so that
+-- entries outlive merged and deleted accounts.
+CREATE TABLE audit_log (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    created_at_us BIGINT NOT NULL,
+    actor TEXT NOT NULL,
+    action TEXT NOT NULL,
+    target TEXT,
+    detail TEXT NOT NULL
+);
+CREATE INDEX audit_log_created_at_us ON audit_log (created_at_us);
@@ -0,0 +1,5 @@
+ALTER TABLE accounts DROP COLUMN search_post_ids;
+DROP TRIGGER posts_fts_update;
+DROP TRIGGER posts_fts_delete;
+DROP TRIGGER posts_fts_insert;
+DROP TABLE posts_fts;
@@ -0,0 +1,17 @@
+-- A full-text index of post bodies, kept in sync with the posts table by triggers.
+CREATE VIRTUAL TABLE posts_fts USING fts5(body, content='posts', content_rowid='id');
+INSERT INTO posts_fts(posts_fts) VALUES ('rebuild');
+
+CREATE TRIGGER posts_fts_insert AFTER INSERT ON posts BEGIN
+    INSERT INTO posts_fts(rowid, body) VALUES (new.id, new.body);
+END;
+CREATE TRIGGER posts_fts_delete AFTER DELETE ON posts BEGIN
+    INSERT INTO posts_fts(posts_fts, rowid, body) VALUES ('delete', old.id, old.body);
+END;
+CREATE TRIGGER posts_fts_update AFTER UPDATE OF body ON posts BEGIN
+    INSERT INTO posts_fts(posts_fts, rowid, body) VALUES ('delete', old.id, old.body);
+    INSERT INTO posts_fts(rowid, body) VALUES (new.id, new.body);
+END;
+
+-- The comma-separated post IDs from the account's latest search, so they can jump to a result.
+ALTER TABLE accounts ADD COLUMN search_post_ids TEXT;
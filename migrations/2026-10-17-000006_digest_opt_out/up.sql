@@ -0,0 +1,2 @@
+-- Whether the account gets a summary of unread posts and messages after being away a while.
+ALTER TABLE accounts ADD COLUMN digest_enabled BOOL NOT NULL DEFAULT TRUE;
@@ -0,0 +1,2 @@
+ALTER TABLE queued_messages DROP COLUMN receipt;
+ALTER TABLE accounts DROP COLUMN dm_receipts;
@@ -0,0 +1,4 @@
+-- Whether the account wants to be told when its direct messages are delivered.
+ALTER TABLE accounts ADD COLUMN dm_receipts BOOL NOT NULL DEFAULT FALSE;
+-- Delivery receipts are messages too, but they never get receipts of their own.
+ALTER TABLE queued_messages ADD COLUMN receipt BOOL NOT NULL DEFAULT FALSE;
@@ -0,0 +1,2 @@
+ALTER TABLE accounts DROP COLUMN skip_ignored_posts;
+DROP TABLE ignores;
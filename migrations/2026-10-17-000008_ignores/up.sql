@@ -0,0 +1,12 @@
+-- Accounts whose messages (and optionally posts) an account doesn't want to see.
+CREATE TABLE ignores (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    account_id INTEGER NOT NULL,
+    ignored_account_id INTEGER NOT NULL,
+    created_at_us BIGINT NOT NULL,
+    FOREIGN KEY (account_id) REFERENCES accounts (id),
+    FOREIGN KEY (ignored_account_id) REFERENCES accounts (id),
+    UNIQUE (account_id, ignored_account_id)
+);
+-- Whether to skip posts by ignored accounts when reading new posts.
+ALTER TABLE accounts ADD COLUMN skip_ignored_posts BOOL NOT NULL DEFAULT FALSE;
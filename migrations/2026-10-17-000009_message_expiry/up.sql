@@ -0,0 +1,2 @@
+-- When an undelivered message was given up on.
+ALTER TABLE queued_messages ADD COLUMN expired_at_us BIGINT;
@@ -0,0 +1,7 @@
+-- How long each packet the BBS sent spent on the air, for keeping under duty-cycle limits.
+CREATE TABLE airtime (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    sent_at_us BIGINT NOT NULL,
+    airtime_us BIGINT NOT NULL
+);
+CREATE INDEX airtime_sent_at_us ON airtime (sent_at_us);
@@ -0,0 +1,10 @@
+-- Each account's command rate limit: its token bucket, and whether it's been told to slow down.
+CREATE TABLE rate_limits (
+    account_id INTEGER NOT NULL PRIMARY KEY,
+    tokens DOUBLE NOT NULL,
+    updated_at_us BIGINT NOT NULL,
+    warned BOOL NOT NULL DEFAULT FALSE,
+    strikes INTEGER NOT NULL DEFAULT 0,
+    muted_until_us BIGINT,
+    FOREIGN KEY (account_id) REFERENCES accounts (id) ON DELETE CASCADE
+);
@@ -0,0 +1 @@
+DROP TABLE more_pages;
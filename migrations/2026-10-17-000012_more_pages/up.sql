@@ -0,0 +1,9 @@
+-- The rest of each account's last long reply, waiting for them to ask for more.
+CREATE TABLE more_pages (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    account_id INTEGER NOT NULL,
+    body TEXT NOT NULL,
+    created_at_us BIGINT NOT NULL,
+    FOREIGN KEY (account_id) REFERENCES accounts (id) ON DELETE CASCADE
+);
+CREATE INDEX more_pages_account_id ON more_pages (account_id);
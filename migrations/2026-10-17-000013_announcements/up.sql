@@ -0,0 +1,5 @@
+-- When each scheduled announcement was last broadcast, so restarting the BBS doesn't repeat them.
+CREATE TABLE announcements (
+    name TEXT NOT NULL PRIMARY KEY,
+    last_sent_at_us BIGINT NOT NULL
+);
@@ -0,0 +1 @@
+ALTER TABLE queued_messages DROP COLUMN notice;
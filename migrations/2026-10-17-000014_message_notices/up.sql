@@ -0,0 +1,3 @@
+-- Messages from the BBS itself, like telling a sender their message expired. They're addressed
+-- to the account they're about, but nobody wrote them.
+ALTER TABLE queued_messages ADD COLUMN notice BOOL NOT NULL DEFAULT FALSE;
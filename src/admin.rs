@@ -1,5 +1,5 @@
 use super::db::now_as_useconds;
-use crate::db::{boards, posts, users};
+use crate::db::{audit, boards, posts, users, Board};
 use crate::BBSConfig;
 use diesel::SqliteConnection;
 
@@ -22,11 +22,11 @@ pub fn user_list(conn: &mut SqliteConnection) {
             user.last_seen_at(),
             user.last_acted_at(),
             user.node_id(),
-            if user.jackass() { "*" } else { " " },
+            if user.is_banned() { "*" } else { " " },
             user.short_name(),
             user.display_name(),
         );
-        if user.jackass() {
+        if user.is_banned() {
             jackasses = true;
         }
     }
@@ -51,15 +51,20 @@ pub fn user_observe(
     );
 }
 
-pub fn user_ban(conn: &mut SqliteConnection, node_id: &str) {
+pub fn user_ban(conn: &mut SqliteConnection, node_id: &str, hours: Option<u32>) {
     let user = users::get(conn, node_id).unwrap();
-    let user = users::ban(conn, &user).unwrap();
-    println!("Banned user {user}");
+    let until = hours.map(|hours| now_as_useconds() + i64::from(hours) * 3600 * 1_000_000);
+    let user = users::ban(conn, audit::ADMIN, &user, until).unwrap();
+    if until.is_some() {
+        println!("Banned user {user} until {}", user.account.banned_until());
+    } else {
+        println!("Banned user {user}");
+    }
 }
 
 pub fn user_unban(conn: &mut SqliteConnection, node_id: &str) {
     let user = users::get(conn, node_id).unwrap();
-    let user = users::unban(conn, &user).unwrap();
+    let user = users::unban(conn, audit::ADMIN, &user).unwrap();
     println!("Unbanned user {user}");
 }
 
@@ -68,25 +73,112 @@ pub fn board_list(conn: &mut SqliteConnection) {
         "\
 # BBS boards
 
-| Created at          | Num | Name                           | Description |
-| ------------------- | --- | ------------------------------ | ----------- |"
+| Created at          | Num | Name                           | Flags | Description |
+| ------------------- | --- | ------------------------------ | ----- | ----------- |"
     );
     for board in boards::all(conn) {
         println!(
-            "| {} | {:3} | {:30} | {} |",
+            "| {} | {:3} | {:30} | {:5} | {} |",
             board.created_at(),
             board.id,
             board.name,
+            board_flags(&board),
             board.description,
         );
     }
+    println!();
+    println!("Flags: H = hidden, R = read-only, S = sysop posting only");
+}
+
+/// Summarize a board's permissions in a few characters.
+fn board_flags(board: &Board) -> String {
+    let mut flags = String::new();
+    if board.hidden {
+        flags.push('H');
+    }
+    if board.read_only {
+        flags.push('R');
+    }
+    if board.sysop_post {
+        flags.push('S');
+    }
+    flags
 }
 
-pub fn board_add(conn: &mut SqliteConnection, name: &str, description: &str) {
+pub fn board_add(
+    conn: &mut SqliteConnection,
+    name: &str,
+    description: &str,
+    hidden: bool,
+    read_only: bool,
+    sysop_post: bool,
+) {
     let board = boards::add(conn, name, description).unwrap();
+    let board = boards::set_permissions(
+        conn,
+        board.id,
+        Some(hidden),
+        Some(read_only),
+        Some(sysop_post),
+    )
+    .unwrap();
     println!("Created board #{}, '{}'", board.id, board.name);
 }
 
+pub fn board_permissions(
+    conn: &mut SqliteConnection,
+    board_id: i32,
+    hidden: Option<bool>,
+    read_only: Option<bool>,
+    sysop_post: Option<bool>,
+) {
+    let board = boards::get(conn, board_id).unwrap();
+    if hidden.is_none() && read_only.is_none() && sysop_post.is_none() {
+        println!("Nothing to change on board #{}, '{}'", board.id, board.name);
+        return;
+    }
+    let board = boards::set_permissions(conn, board_id, hidden, read_only, sysop_post).unwrap();
+    println!(
+        "Board #{}, '{}' now has flags '{}'",
+        board.id,
+        board.name,
+        board_flags(&board)
+    );
+}
+
+pub fn board_edit(
+    conn: &mut SqliteConnection,
+    board_id: i32,
+    name: Option<&str>,
+    description: Option<&str>,
+) {
+    let board = boards::get(conn, board_id).unwrap();
+    if name.is_none() && description.is_none() {
+        println!("Nothing to change on board #{}, '{}'", board.id, board.name);
+        return;
+    }
+    let board = match boards::edit(conn, board_id, name, description) {
+        Ok(board) => board,
+        Err(err) => {
+            println!(
+                "Unable to update board #{}, '{}': {err}",
+                board.id, board.name
+            );
+            return;
+        }
+    };
+    println!("Updated board #{}, '{}': {}", board.id, board.name, board.description);
+}
+
+pub fn board_delete(conn: &mut SqliteConnection, board_id: i32) {
+    let board = boards::get(conn, board_id).unwrap();
+    let deleted = boards::delete(conn, audit::ADMIN, board_id).unwrap();
+    println!(
+        "Deleted board #{}, '{}' and its {deleted} post(s)",
+        board.id, board.name
+    );
+}
+
 /// Print the path to the `SQLite` database file.
 pub fn db_path(cfg: &BBSConfig) {
     println!("{}", cfg.db_path);
@@ -104,14 +196,15 @@ pub fn post_read(conn: &mut SqliteConnection, board_id: i32) {
 
     println!(
         "\
-| Created at          | User      | Body |
-| ------------------- | --------- | ---- |"
+| Created at          | Num   | User      | Body |
+| ------------------- | ----- | --------- | ---- |"
     );
 
     for (post, user) in post_info {
         println!(
-            "| {} | {} | {} |",
+            "| {} | {:5} | {} | {} |",
             post.created_at(),
+            post.id,
             user.display_name(),
             post.body,
         );
@@ -123,3 +216,45 @@ pub fn post_add(conn: &mut SqliteConnection, board_id: i32, node_id: &str, conte
     let post = posts::add(conn, user.account_id(), board_id, content).unwrap();
     println!("Created post #{}", post.id);
 }
+
+pub fn post_edit(conn: &mut SqliteConnection, post_id: i32, content: &str) {
+    let post = posts::edit(conn, post_id, content).unwrap();
+    println!("Updated post #{}", post.id);
+}
+
+pub fn post_move(conn: &mut SqliteConnection, post_id: i32, board_id: i32) {
+    let board = boards::get(conn, board_id).unwrap();
+    let post = posts::move_to_board(conn, post_id, board_id).unwrap();
+    println!("Moved post #{} to board #{}, '{}'", post.id, board.id, board.name);
+}
+
+pub fn post_delete(conn: &mut SqliteConnection, post_id: i32) {
+    let post = posts::delete(conn, audit::ADMIN, post_id).unwrap();
+    println!("Deleted post #{}", post.id);
+}
+
+pub fn audit_list(
+    conn: &mut SqliteConnection,
+    actor: Option<&str>,
+    target: Option<&str>,
+    since: Option<i64>,
+    until: Option<i64>,
+) {
+    println!(
+        "\
+# Audit log
+
+| Created at          | Actor     | Action       | Target    | Detail |
+| ------------------- | --------- | ------------ | --------- | ------ |"
+    );
+    for entry in audit::list(conn, actor, target, since, until) {
+        println!(
+            "| {} | {:9} | {:12} | {:9} | {} |",
+            entry.created_at(),
+            entry.actor,
+            entry.action,
+            entry.target.unwrap_or_default(),
+            entry.detail,
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use crate::paginate::MAX_LENGTH;
+use serde::{Deserialize, Serialize};
+
+/// Meshtastic adds its own header, and the payload is wrapped in a protobuf, so every page takes
+/// more bytes on the air than its text. This is a slightly generous estimate of the difference.
+const PACKET_OVERHEAD_BYTES: u32 = 32;
+/// Meshtastic radios send 16 preamble symbols before every packet.
+const PREAMBLE_SYMBOLS: f64 = 16.0;
+
+/// The Meshtastic modem presets, which set how fast (and how far) the radio talks.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub enum ModemPreset {
+    ShortTurbo,
+    ShortFast,
+    ShortSlow,
+    MediumFast,
+    MediumSlow,
+    #[default]
+    LongFast,
+    LongModerate,
+    LongSlow,
+    VeryLongSlow,
+}
+
+impl ModemPreset {
+    /// The spreading factor, bandwidth in kHz, and coding rate denominator for this preset.
+    fn parameters(self) -> (u32, f64, u32) {
+        match self {
+            ModemPreset::ShortTurbo => (7, 500.0, 5),
+            ModemPreset::ShortFast => (7, 250.0, 5),
+            ModemPreset::ShortSlow => (8, 250.0, 5),
+            ModemPreset::MediumFast => (9, 250.0, 5),
+            ModemPreset::MediumSlow => (10, 250.0, 5),
+            ModemPreset::LongFast => (11, 250.0, 5),
+            ModemPreset::LongModerate => (11, 125.0, 8),
+            ModemPreset::LongSlow => (12, 125.0, 8),
+            ModemPreset::VeryLongSlow => (12, 62.5, 8),
+        }
+    }
+}
+
+/// How much of the time the BBS may spend transmitting.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AirtimeConfig {
+    /// The preset the radio is configured with, like "LongFast".
+    #[serde(default)]
+    pub modem_preset: ModemPreset,
+    /// The most of each window the BBS may spend transmitting, in percent.
+    pub duty_cycle_percent: f64,
+    /// The length of the rolling window the duty cycle is measured over.
+    #[serde(default = "default_window_minutes")]
+    pub window_minutes: u32,
+}
+
+fn default_window_minutes() -> u32 {
+    60
+}
+
+impl AirtimeConfig {
+    /// The length of the window, in microseconds.
+    pub fn window_us(&self) -> i64 {
+        i64::from(self.window_minutes) * 60_000_000
+    }
+
+    /// How much airtime the BBS may use in each window, in microseconds.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn allowed_us(&self) -> i64 {
+        (self.window_us() as f64 * self.duty_cycle_percent / 100.0) as i64
+    }
+
+    /// Make sure the budget leaves room to send at least one full page per window. Otherwise,
+    /// replies that are too big to ever fit would wait forever.
+    pub fn check(&self) -> Result<(), String> {
+        if !(self.duty_cycle_percent > 0.0 && self.duty_cycle_percent <= 100.0) {
+            return Err(format!(
+                "airtime duty_cycle_percent must be more than 0 and at most 100, not {}",
+                self.duty_cycle_percent
+            ));
+        }
+        let page_us = estimate_us(self.modem_preset, &"x".repeat(MAX_LENGTH));
+        if self.allowed_us() < page_us {
+            return Err(format!(
+                "the airtime budget of {:.1}s per {} minutes is too small to send one {:.1}s page",
+                self.allowed_us() as f64 / 1_000_000.0,
+                self.window_minutes,
+                page_us as f64 / 1_000_000.0
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Estimate how long it takes to transmit a page of text, in microseconds, using the standard
+/// LoRa time-on-air formula.
+#[allow(clippy::cast_possible_truncation)]
+pub fn estimate_us(preset: ModemPreset, text: &str) -> i64 {
+    let (spreading_factor, bandwidth_khz, coding_rate) = preset.parameters();
+    let payload_bytes = text.len() as f64 + f64::from(PACKET_OVERHEAD_BYTES);
+    let sf = f64::from(spreading_factor);
+    let symbol_ms = 2_f64.powf(sf) / bandwidth_khz;
+    // Slow symbols use low data rate optimization, which costs some capacity per symbol.
+    let low_data_rate = if symbol_ms > 16.0 { 1.0 } else { 0.0 };
+    let payload_symbols = 8.0
+        + ((8.0 * payload_bytes - 4.0 * sf + 28.0 + 16.0) / (4.0 * (sf - 2.0 * low_data_rate)))
+            .ceil()
+            .max(0.0)
+            * f64::from(coding_rate);
+    let total_ms = (PREAMBLE_SYMBOLS + 4.25 + payload_symbols) * symbol_ms;
+    (total_ms * 1000.0).round() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slower_presets_take_longer() {
+        let text = "x".repeat(200);
+        let fast = estimate_us(ModemPreset::ShortFast, &text);
+        let long = estimate_us(ModemPreset::LongFast, &text);
+        let slow = estimate_us(ModemPreset::LongSlow, &text);
+        assert!(fast < long && long < slow, "{fast} {long} {slow}");
+        // A full page on LongFast is about two seconds on the air.
+        assert!((1_500_000..2_500_000).contains(&long), "{long}");
+    }
+
+    #[test]
+    fn longer_pages_take_longer() {
+        assert!(
+            estimate_us(ModemPreset::LongFast, "hi")
+                < estimate_us(ModemPreset::LongFast, &"x".repeat(100))
+        );
+    }
+
+    #[test]
+    fn budget_is_a_share_of_the_window() {
+        let cfg = AirtimeConfig {
+            modem_preset: ModemPreset::LongFast,
+            duty_cycle_percent: 10.0,
+            window_minutes: 60,
+        };
+        assert_eq!(cfg.allowed_us(), 360_000_000);
+    }
+
+    #[test]
+    fn budget_must_fit_a_full_page() {
+        let mut cfg = AirtimeConfig {
+            modem_preset: ModemPreset::LongFast,
+            duty_cycle_percent: 10.0,
+            window_minutes: 60,
+        };
+        assert!(cfg.check().is_ok());
+        for percent in [0.0, -5.0, 150.0, f64::NAN] {
+            cfg.duty_cycle_percent = percent;
+            assert!(cfg.check().is_err(), "{percent}");
+        }
+        cfg.duty_cycle_percent = 10.0;
+        cfg.window_minutes = 0;
+        assert!(cfg.check().is_err());
+        // A tenth of a percent of a minute is 60ms, much less than one page on LongSlow.
+        cfg.modem_preset = ModemPreset::LongSlow;
+        cfg.duty_cycle_percent = 0.1;
+        cfg.window_minutes = 1;
+        assert!(cfg.check().is_err());
+    }
+}
@@ -1,10 +1,13 @@
 use crate::commands::{
-    available_state, command_structure, help_menu, help_toplevel, Menus, Replies, ReplyDestination,
+    available_state, command_structure, help_menu, help_public, help_toplevel, is_sysop,
+    unread_digest, CommandFunc, Menus, Replies, Reply, ReplyDestination,
 };
-use crate::db::users;
+use crate::db::{board_states, boards, now_as_useconds, users, User};
 use crate::paginate::{paginate, MAX_LENGTH};
+use crate::rate_limit::{self, Verdict};
 use crate::{linefeed, system_info, BBSConfig};
 use diesel::SqliteConnection;
+use regex::Captures;
 use std::io::{self, Write as _};
 
 const NO_SUCH_COMMAND: &str = "That's not an available command here.";
@@ -19,11 +22,22 @@ pub fn dispatch(
     cmdline: &str,
     local: bool,
 ) -> Replies {
+    // Find out when they were last here before recording that they're here now.
+    let last_acted_at_us = users::get(conn, node_id)
+        .ok()
+        .and_then(|user| user.account.last_acted_at_us);
     let (mut user, seen) = users::record(conn, node_id).unwrap();
+    // Don't let anyone over the radio run commands faster than the mesh can carry the replies.
+    if !local {
+        if let Some(replies) = rate_limited(conn, cfg, &user, cmdline) {
+            return replies;
+        }
+    }
     if seen {
         log::info!("Command from {user}: '{cmdline}'");
     } else {
         log::info!("Command from new {user}: '{cmdline}'");
+        start_caught_up(conn, cfg, &user);
         let mut out = vec![
             format!("Welcome to {}!", cfg.bbs_name),
             String::new(),
@@ -35,7 +49,143 @@ pub fn dispatch(
         return out.into();
     }
 
-    let state = available_state(cfg, &user, local);
+    // Catch them up on what they missed if they've been gone a while.
+    let digest = match (cfg.digest_idle_hours, last_acted_at_us) {
+        (Some(hours), Some(last_acted_at_us))
+            if user.account.digest_enabled
+                && last_acted_at_us + i64::from(hours) * 3_600_000_000 < now_as_useconds() =>
+        {
+            unread_digest(conn, cfg, &user)
+        }
+        _ => None,
+    };
+    let mut replies = respond(conn, cfg, &mut user, menus, cmdline, local);
+    if let Some(out) = digest {
+        replies.0.insert(
+            0,
+            Reply {
+                out,
+                destination: ReplyDestination::Sender,
+            },
+        );
+    }
+    replies
+}
+
+/// Handle a single command someone sent to a public channel. Only the commands the sysop allows
+/// in public are available, and banned accounts are quietly ignored so they can't make the BBS
+/// chatter on the channel.
+pub fn dispatch_public(
+    conn: &mut SqliteConnection,
+    cfg: &BBSConfig,
+    node_id: &str,
+    menus: &Menus,
+    cmdline: &str,
+) -> Replies {
+    let Some(public) = &cfg.public else {
+        return Replies(Vec::new());
+    };
+    let (mut user, seen) = users::record(conn, node_id).unwrap();
+    log::info!("Public command from {user}: '{cmdline}'");
+    if !seen {
+        start_caught_up(conn, cfg, &user);
+    }
+    if let Some(replies) = rate_limited(conn, cfg, &user, cmdline) {
+        return replies;
+    }
+    let state = available_state(cfg, &user, false);
+
+    for menu in menus {
+        for command in &menu.commands {
+            if !command.allowed_in_public || !(command.available)(&state) {
+                continue;
+            }
+            if let Some(captures) = command.pattern.captures(cmdline) {
+                if user.is_banned() && !command.allowed_when_banned {
+                    log::warn!("Ignored public command from banned {user}: '{cmdline}'");
+                    return Replies(Vec::new());
+                }
+                return run(
+                    conn,
+                    cfg,
+                    &mut user,
+                    command.public_func,
+                    &captures,
+                    cmdline,
+                );
+            }
+        }
+    }
+    // Don't answer a typo with the whole help list for everyone on the channel. Only the sender
+    // needs to see it.
+    Replies(vec![Reply {
+        out: help_public(&public.prefix, menus),
+        destination: ReplyDestination::Private,
+    }])
+}
+
+/// Let a new account skip all but the newest posts in each board, if the sysop wants.
+fn start_caught_up(conn: &mut SqliteConnection, cfg: &BBSConfig, user: &User) {
+    if let Some(leave_unread) = cfg.new_account_unread {
+        for board in boards::all(conn) {
+            board_states::catch_up(conn, user.account_id(), board.id, leave_unread);
+        }
+    }
+}
+
+/// If the user is sending commands too fast, get what to tell them instead of running this one.
+fn rate_limited(
+    conn: &mut SqliteConnection,
+    cfg: &BBSConfig,
+    user: &User,
+    cmdline: &str,
+) -> Option<Replies> {
+    let limits = cfg.rate_limit.as_ref()?;
+    let sysop = is_sysop(cfg, user);
+    match rate_limit::check(conn, limits, user.account_id(), sysop, now_as_useconds()) {
+        Verdict::Allow => None,
+        Verdict::Notify(notice) => {
+            log::warn!("Rate limited {user}: '{cmdline}'");
+            Some(notice.into())
+        }
+        Verdict::Ignore => {
+            log::info!("Ignored rate limited {user}: '{cmdline}'");
+            Some(Replies(Vec::new()))
+        }
+    }
+}
+
+/// Run the function of a command whose pattern matched the command line.
+fn run(
+    conn: &mut SqliteConnection,
+    cfg: &BBSConfig,
+    user: &mut User,
+    func: CommandFunc,
+    captures: &Captures,
+    cmdline: &str,
+) -> Replies {
+    // Collect all of the matched groups in the pattern into a vector of strs
+    let mut args: Vec<&str> = captures
+        .iter()
+        .skip(1) // The first item is the command
+        .flatten()
+        .map(|x| x.as_str().trim())
+        .collect();
+    // Prepend the full, trimmed command line as the first argument.
+    args.insert(0, cmdline.trim());
+    func(conn, cfg, user, args)
+}
+
+/// Run a command for a known user.
+fn respond(
+    conn: &mut SqliteConnection,
+    cfg: &BBSConfig,
+    user: &mut User,
+    menus: &Menus,
+    cmdline: &str,
+    local: bool,
+) -> Replies {
+    let state = available_state(cfg, user, local);
 
     // Special handling for help requests
     let help_cmdline = cmdline.to_lowercase();
@@ -69,16 +219,12 @@ pub fn dispatch(
                 continue;
             }
             if let Some(captures) = command.pattern.captures(cmdline) {
-                // Collect all of the matched groups in the pattern into a vector of strs
-                let mut args: Vec<&str> = captures
-                    .iter()
-                    .skip(1) // The first item is the command
-                    .flatten()
-                    .map(|x| x.as_str().trim())
-                    .collect();
-                // Prepend the full, trimmed command line as the first argument.
-                args.insert(0, cmdline.trim());
-                return (command.func)(conn, cfg, &mut user, args);
+                // Banned accounts only get to run the commands the sysop allows them.
+                if user.is_banned() && !command.allowed_when_banned {
+                    log::warn!("Rejected command from banned {user}: '{cmdline}'");
+                    return cfg.bans.message.as_str().into();
+                }
+                return run(conn, cfg, user, command.func, &captures, cmdline);
             }
         }
     }
@@ -137,10 +283,167 @@ fn print_replies(replies: Replies) {
             ReplyDestination::Broadcast => {
                 println!("Reply to the public channel:");
             }
-            ReplyDestination::Sender => {
+            ReplyDestination::Sender | ReplyDestination::Private => {
                 println!("Reply to you:");
             }
         };
         println!("{}", paginate(reply.out, MAX_LENGTH).join("\n"));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{self, boards};
+    use crate::{BanConfig, CommandConfig, MenuConfig};
+    use config::Map;
+    use diesel::RunQueryDsl as _;
+
+    fn test_config() -> BBSConfig {
+        let mut menus = Map::new();
+        menus.insert(
+            "Board".to_string(),
+            MenuConfig {
+                help_suffix: "B".to_string(),
+                commands: vec![
+                    CommandConfig::for_tests("B", "b", "always", "board::lister"),
+                    CommandConfig::for_tests("Bn", "b\\s*(\\d+)", "always", "board::enter"),
+                    CommandConfig::for_tests(
+                        "W msg",
+                        "(?s)w\\s*(.+?)\\s*",
+                        "in_board",
+                        "board::write",
+                    ),
+                ],
+            },
+        );
+        BBSConfig {
+            menus,
+            bans: BanConfig {
+                allowed_commands: vec!["board::lister".to_string(), "board::enter".to_string()],
+                message: "Nope.".to_string(),
+            },
+            ..BBSConfig::for_tests()
+        }
+    }
+
+    fn reply_text(replies: &Replies) -> String {
+        replies.0[0].out.join("\n")
+    }
+
+    #[test]
+    fn banned_account_is_limited_to_allowed_commands() {
+        let mut conn = db::test_connection();
+        let cfg = test_config();
+        let menus = command_structure(&cfg);
+        boards::add(&mut conn, "General", "General discussion").expect("should create board");
+        let (user, _) = users::record(&mut conn, "!10000001").expect("user");
+        users::ban(&mut conn, "admin", &user, None).expect("should ban");
+
+        let replies = dispatch(&mut conn, &cfg, "!10000001", &menus, "b1", false);
+        assert!(reply_text(&replies).starts_with("Entering board 1"));
+
+        let replies = dispatch(&mut conn, &cfg, "!10000001", &menus, "w spam", false);
+        assert_eq!(reply_text(&replies), "Nope.");
+        assert!(db::posts::in_board(&mut conn, 1).is_empty());
+    }
+
+    #[test]
+    fn expired_ban_no_longer_limits_commands() {
+        let mut conn = db::test_connection();
+        let cfg = test_config();
+        let menus = command_structure(&cfg);
+        boards::add(&mut conn, "General", "General discussion").expect("should create board");
+        let (user, _) = users::record(&mut conn, "!10000002").expect("user");
+        users::ban(&mut conn, "admin", &user, Some(db::now_as_useconds() - 1_000_000))
+            .expect("should ban");
+
+        dispatch(&mut conn, &cfg, "!10000002", &menus, "b1", false);
+        let replies = dispatch(&mut conn, &cfg, "!10000002", &menus, "w hello", false);
+        assert!(reply_text(&replies).starts_with("Published at"));
+    }
+
+    #[test]
+    fn new_accounts_can_start_mostly_caught_up() {
+        let mut conn = db::test_connection();
+        let mut cfg = test_config();
+        cfg.new_account_unread = Some(1);
+        let menus = command_structure(&cfg);
+        let board = boards::add(&mut conn, "General", "General discussion").expect("board");
+        let (author, _) = users::record(&mut conn, "!10000003").expect("author");
+        let mut posted = vec![];
+        for body in ["Old news", "Older news", "Today's news"] {
+            posted.push(
+                db::posts::add(&mut conn, author.account_id(), board.id, body).expect("post"),
+            );
+            std::thread::sleep(std::time::Duration::from_micros(10));
+        }
+
+        dispatch(&mut conn, &cfg, "!10000004", &menus, "?", false);
+        let user = users::get(&mut conn, "!10000004").expect("user");
+        assert_eq!(
+            board_states::get(&mut conn, user.account_id(), board.id),
+            posted[1].created_at_us
+        );
+    }
+
+    /// Pretend the user was last active this many hours ago.
+    fn backdate(conn: &mut SqliteConnection, node_id: &str, hours: i64) {
+        let user = users::get(conn, node_id).expect("user");
+        diesel::sql_query("UPDATE accounts SET last_acted_at_us = ? WHERE id = ?")
+            .bind::<diesel::sql_types::BigInt, _>(db::now_as_useconds() - hours * 3_600_000_000)
+            .bind::<diesel::sql_types::Integer, _>(user.account_id())
+            .execute(conn)
+            .expect("should backdate account");
+    }
+
+    #[test]
+    fn returning_users_get_a_digest_unless_they_opt_out() {
+        let mut conn = db::test_connection();
+        let mut cfg = test_config();
+        cfg.digest_idle_hours = Some(12);
+        let menus = command_structure(&cfg);
+        let board = boards::add(&mut conn, "General", "General discussion").expect("board");
+        let (author, _) = users::record(&mut conn, "!10000005").expect("author");
+        dispatch(&mut conn, &cfg, "!10000006", &menus, "?", false);
+        db::posts::add(&mut conn, author.account_id(), board.id, "News").expect("post");
+
+        // Recently active users don't get one.
+        let replies = dispatch(&mut conn, &cfg, "!10000006", &menus, "b", false);
+        assert_eq!(replies.0.len(), 1);
+
+        backdate(&mut conn, "!10000006", 13);
+        let replies = dispatch(&mut conn, &cfg, "!10000006", &menus, "b", false);
+        assert_eq!(replies.0.len(), 2);
+        assert!(reply_text(&replies).contains("B1 General: 1 new"));
+
+        let user = users::get(&mut conn, "!10000006").expect("user");
+        users::set_digest_enabled(&mut conn, &user, false).expect("opt out");
+        backdate(&mut conn, "!10000006", 13);
+        let replies = dispatch(&mut conn, &cfg, "!10000006", &menus, "b", false);
+        assert_eq!(replies.0.len(), 1);
+    }
+
+    #[test]
+    fn fast_senders_are_told_once_then_ignored() {
+        let mut conn = db::test_connection();
+        let mut cfg = test_config();
+        cfg.rate_limit = Some(crate::rate_limit::RateLimitConfig::default());
+        let menus = command_structure(&cfg);
+        boards::add(&mut conn, "General", "General discussion").expect("board");
+        users::record(&mut conn, "!10000007").expect("user");
+
+        for _ in 0..5 {
+            let replies = dispatch(&mut conn, &cfg, "!10000007", &menus, "b", false);
+            assert!(reply_text(&replies).contains("General"));
+        }
+        let replies = dispatch(&mut conn, &cfg, "!10000007", &menus, "b", false);
+        assert!(reply_text(&replies).starts_with("Slow down!"));
+        let replies = dispatch(&mut conn, &cfg, "!10000007", &menus, "b", false);
+        assert!(replies.0.is_empty());
+
+        // The sysop at the console isn't limited.
+        let replies = dispatch(&mut conn, &cfg, "!10000007", &menus, "b", true);
+        assert!(reply_text(&replies).contains("General"));
+    }
+}
@@ -1,5 +1,5 @@
-use crate::db::User;
-use crate::{linefeed, BBSConfig};
+use crate::db::{users, User};
+use crate::{canonical_node_id, linefeed, BBSConfig};
 use diesel::SqliteConnection;
 use regex::{Regex, RegexBuilder};
 mod board;
@@ -11,12 +11,18 @@ mod sysop;
 mod user;
 mod weather;
 
+pub use user::unread_digest;
+
 const ERROR_POSTING: &str = "Unable to insert this post.";
+const INVALID_NODEID: &str = "The given address is invalid.";
+const NO_SUCH_USER: &str = "That user does not exist.";
 
 /// To where shall I respond?
 #[derive(Debug)]
 pub enum ReplyDestination {
     Sender,
+    /// Only the sender, even if they asked in a public channel.
+    Private,
     Broadcast,
 }
 
@@ -89,6 +95,19 @@ pub fn help_menu(state: &AvailableState, menu: &Menu) -> Vec<String> {
     out
 }
 
+/// Show anyone in a public channel the commands they may run there.
+pub fn help_public(prefix: &str, menus: &Menus) -> Vec<String> {
+    let mut out = vec![format!("Send \"{prefix} command\":")];
+    for menu in menus {
+        for command in &menu.commands {
+            if command.allowed_in_public {
+                out.push(format!("{} : {}", command.arg, command.help));
+            }
+        }
+    }
+    out
+}
+
 // Contexts in which certain actions may be available
 
 /// Information about the user's state during a single command.
@@ -98,12 +117,29 @@ pub struct AvailableState {
     is_sysop: bool,
 }
 
+/// Is this user one of the BBS's sysops?
+pub fn is_sysop(cfg: &BBSConfig, user: &User) -> bool {
+    cfg.sysops.iter().any(|s| s == user.node_id())
+}
+
+/// Find a user by their node ID, like "!abcd1234", or by their short name if it's unique.
+fn find_user(conn: &mut SqliteConnection, who: &str) -> Result<User, &'static str> {
+    if who.len() > 5 || who.starts_with('!') {
+        let Some(node_id) = canonical_node_id(who) else {
+            return Err(INVALID_NODEID);
+        };
+        users::get(conn, &node_id).map_err(|_| NO_SUCH_USER)
+    } else {
+        users::get_by_short_name(conn, who).ok_or(NO_SUCH_USER)
+    }
+}
+
 /// Pre-compute values used by available_* functions so we're not repeatedly hitting the database.
 pub fn available_state(cfg: &BBSConfig, user: &User, local: bool) -> AvailableState {
     AvailableState {
         in_board: user.in_board().is_some(),
         is_local: local,
-        is_sysop: cfg.sysops.iter().any(|s| s == user.node_id()),
+        is_sysop: is_sysop(cfg, user),
     }
 }
 
@@ -145,6 +181,9 @@ impl Menu {
 
 pub type Menus = Vec<Menu>;
 
+/// What every command looks like.
+pub type CommandFunc = fn(&mut SqliteConnection, &BBSConfig, &mut User, Vec<&str>) -> Replies;
+
 /// Information about a command a user can execute.
 pub struct Command {
     /// Help text showing the user what to send.
@@ -155,8 +194,15 @@ pub struct Command {
     pub pattern: Regex,
     /// A function that determines whether the user in this state can run this command.
     pub available: fn(&AvailableState) -> bool,
+    /// Whether banned accounts may run this command.
+    pub allowed_when_banned: bool,
+    /// Whether anyone may run this command in a public channel.
+    pub allowed_in_public: bool,
     /// The function that implements this command.
-    pub func: fn(&mut SqliteConnection, &BBSConfig, &mut User, Vec<&str>) -> Replies,
+    pub func: CommandFunc,
+    /// The function to run instead in a public channel, where everyone sees the reply. It
+    /// mustn't show sysops anything the rest of the channel can't see.
+    pub public_func: CommandFunc,
 }
 
 /// Build a Regex in our common fashion.
@@ -186,37 +232,15 @@ pub fn command_structure(cfg: &BBSConfig) -> Menus {
                     "sysop" => available_to_sysops,
                     _ => panic!("Unknown command availability: {}", command.available),
                 },
-                func: match command.func.as_str() {
-                    "ping::ping" => ping::ping,
-                    "board::author" => board::author,
-                    "board::current" => board::current,
-                    "board::enter" => board::enter,
-                    "board::lister" => board::lister,
-                    "board::next" => board::next,
-                    "board::previous" => board::previous,
-                    "board::quick" => board::quick,
-                    "board::write" => board::write,
-                    "dm::send" => dm::send,
-                    "state::describe" => state::describe,
-                    "sysop::advertise" => sysop::advertise,
-                    "user::active" => user::active,
-                    "user::bio_read" => user::bio_read,
-                    "user::bio_write" => user::bio_write,
-                    "user::name_read" => user::name_read,
-                    "user::name_write" => user::name_write,
-                    "user::name_clear" => user::name_clear,
-                    "user::seen" => user::seen,
-                    "invite::block" => invite::block,
-                    "invite::unblock" => invite::unblock,
-                    "invite::deny" => invite::deny,
-                    "invite::pending" => invite::pending,
-                    "invite::accept" => invite::accept,
-                    "invite::send" => invite::send,
-                    "invite::leave" => invite::leave,
-                    "invite::remove" => invite::remove,
-                    "invite::help" => invite::help,
-                    "weather::current" => weather::current,
-                    _ => panic!("Unknown command function: {}", command.func),
+                allowed_when_banned: cfg.bans.allowed_commands.contains(&command.func),
+                allowed_in_public: cfg
+                    .public
+                    .as_ref()
+                    .is_some_and(|public| public.allowed_commands.contains(&command.func)),
+                func: command_func(&command.func),
+                public_func: match command.func.as_str() {
+                    "board::latest" => board::latest_public,
+                    func => command_func(func),
                 },
             })
             .collect();
@@ -232,3 +256,69 @@ pub fn command_structure(cfg: &BBSConfig) -> Menus {
 
     menus
 }
+
+/// Find the function that implements a command, by its name in the config.
+fn command_func(func: &str) -> CommandFunc {
+    match func {
+        "ping::ping" => ping::ping,
+        "board::author" => board::author,
+        "board::catch_up" => board::catch_up,
+        "board::catch_up_all" => board::catch_up_all,
+        "board::current" => board::current,
+        "board::enter" => board::enter,
+        "board::goto" => board::goto,
+        "board::headers" => board::headers,
+        "board::latest" => board::latest,
+        "board::lister" => board::lister,
+        "board::next" => board::next,
+        "board::previous" => board::previous,
+        "board::quick" => board::quick,
+        "board::reply" => board::reply,
+        "board::search" => board::search,
+        "board::search_result" => board::search_result,
+        "board::thread_next" => board::thread_next,
+        "board::thread_previous" => board::thread_previous,
+        "board::write" => board::write,
+        "dm::inbox" => dm::inbox,
+        "dm::outbox" => dm::outbox,
+        "dm::read" => dm::read,
+        "dm::receipts_off" => dm::receipts_off,
+        "dm::receipts_on" => dm::receipts_on,
+        "dm::reply" => dm::reply,
+        "dm::send" => dm::send,
+        "state::describe" => state::describe,
+        "state::more" => state::more,
+        "sysop::advertise" => sysop::advertise,
+        "sysop::airtime" => sysop::airtime,
+        "sysop::ban" => sysop::ban,
+        "sysop::delete_post" => sysop::delete_post,
+        "sysop::unban" => sysop::unban,
+        "sysop::user_info" => sysop::user_info,
+        "user::active" => user::active,
+        "user::bio_read" => user::bio_read,
+        "user::bio_write" => user::bio_write,
+        "user::digest" => user::digest,
+        "user::digest_off" => user::digest_off,
+        "user::digest_on" => user::digest_on,
+        "user::ignore" => user::ignore,
+        "user::ignore_list" => user::ignore_list,
+        "user::ignore_posts_off" => user::ignore_posts_off,
+        "user::ignore_posts_on" => user::ignore_posts_on,
+        "user::name_read" => user::name_read,
+        "user::name_write" => user::name_write,
+        "user::name_clear" => user::name_clear,
+        "user::seen" => user::seen,
+        "user::unignore" => user::unignore,
+        "invite::block" => invite::block,
+        "invite::unblock" => invite::unblock,
+        "invite::deny" => invite::deny,
+        "invite::pending" => invite::pending,
+        "invite::accept" => invite::accept,
+        "invite::send" => invite::send,
+        "invite::leave" => invite::leave,
+        "invite::remove" => invite::remove,
+        "invite::help" => invite::help,
+        "weather::current" => weather::current,
+        _ => panic!("Unknown command function: {func}"),
+    }
+}
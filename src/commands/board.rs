@@ -1,7 +1,7 @@
-use super::{Replies, ERROR_POSTING};
-use crate::db::{board_states, boards, posts, users, Post, User};
+use super::{is_sysop, Replies, ERROR_POSTING};
+use crate::db::{board_states, boards, ignores, posts, users, Post, User};
 use crate::{linefeed, BBSConfig};
-use diesel::SqliteConnection;
+use diesel::{QueryResult, SqliteConnection};
 
 const NOT_IN_BOARD: &str = "You are not in a board.";
 const NOT_VALID: &str = "That's a valid number.";
@@ -9,19 +9,107 @@ const NO_BOARDS: &str = "There are no boards.";
 const NO_MORE_POSTS: &str = "There are no more posts in this board.";
 const NO_MORE_UNREAD: &str = "There are no more unread posts in any board.";
 const NO_SUCH_POST: &str = "There is no post here.";
+const NO_MORE_IN_THREAD: &str = "There are no more posts in this thread.";
+const NOT_WRITABLE: &str = "You can't post in this board.";
+const NO_SEARCH_RESULTS: &str = "No posts matched.";
+const NO_SUCH_RESULT: &str = "There is no search result with that number.";
+/// How many words of the parent post to quote in a reply's header.
+const RE_WORDS: usize = 5;
+/// How many post headers to list if the user doesn't say.
+const DEFAULT_HEADERS: usize = 5;
+/// The most post headers to list at once.
+const MAX_HEADERS: usize = 20;
+/// Keep search results to single digits so the list stays short and easy to pick from.
+const MAX_SEARCH_RESULTS: i32 = 9;
+/// The longest snippet of a post to show in a search result.
+const SNIPPET_CHARS: usize = 30;
 
-/// Does this board have any unread posts for this user?
+/// Does this board have any unread posts for this user, not counting the ones they skip?
 fn has_unread(conn: &mut SqliteConnection, user: &User, board_id: i32) -> bool {
     let last_seen = board_states::get(conn, user.account_id(), board_id);
-    posts::after(conn, board_id, last_seen).is_ok()
+    first_wanted(conn, user, board_id, last_seen).0.is_some()
+}
+
+/// Find the first post after `last_seen`, skipping authors the user ignores if they've asked to.
+/// Also returns the time of the last post skipped on the way, or `last_seen` if none were.
+fn first_wanted(
+    conn: &mut SqliteConnection,
+    user: &User,
+    board_id: i32,
+    mut last_seen: i64,
+) -> (Option<(Post, User)>, i64) {
+    let ignored = if user.account.skip_ignored_posts {
+        ignores::ignored_account_ids(conn, user)
+    } else {
+        Vec::new()
+    };
+    while let Ok((post, post_user)) = posts::after(conn, board_id, last_seen) {
+        if !ignored.contains(&post.account_id) {
+            return (Some((post, post_user)), last_seen);
+        }
+        last_seen = post.created_at_us;
+    }
+    (None, last_seen)
+}
+
+/// Get the next post after `last_seen`, skipping authors the user ignores if they've asked to.
+/// If only skipped posts are left, the user's read position moves past them so they aren't
+/// counted as unread again.
+fn next_wanted(
+    conn: &mut SqliteConnection,
+    user: &User,
+    board_id: i32,
+    last_seen: i64,
+) -> QueryResult<(Post, User)> {
+    match first_wanted(conn, user, board_id, last_seen) {
+        (Some(wanted), _) => Ok(wanted),
+        (None, skipped_to) => {
+            if skipped_to > last_seen {
+                board_states::update(conn, user.account_id(), board_id, skipped_to);
+            }
+            Err(diesel::result::Error::NotFound)
+        }
+    }
+}
+
+/// The first few words of a post, to remind readers what a reply is answering.
+fn headline(body: &str) -> String {
+    let words: Vec<&str> = body.split_whitespace().collect();
+    if words.len() > RE_WORDS {
+        format!("{}...", words[..RE_WORDS].join(" "))
+    } else {
+        words.join(" ")
+    }
+}
+
+/// An even shorter summary of a post, to fit lots of them on a page.
+pub(super) fn snippet(body: &str) -> String {
+    let headline = headline(body);
+    if headline.chars().count() > SNIPPET_CHARS {
+        let short: String = headline.chars().take(SNIPPET_CHARS - 3).collect();
+        format!("{}...", short.trim_end())
+    } else {
+        headline
+    }
+}
+
+/// May this user post to this board?
+fn can_write(conn: &mut SqliteConnection, cfg: &BBSConfig, user: &User, board_id: i32) -> bool {
+    boards::get(conn, board_id).is_ok_and(|board| board.writable_by(is_sysop(cfg, user)))
 }
 
 /// Print a post and information about its author.
-fn post_print(post: &Post, user: &User) -> Vec<String> {
+fn post_print(conn: &mut SqliteConnection, post: &Post, user: &User) -> Vec<String> {
     let mut out = vec![
         format!("From: {}", user),
         format!("At: {}", post.created_at()),
     ];
+    // Don't quote posts by banned authors, who are hidden everywhere else.
+    if let Some(parent_id) = post.parent_id {
+        if let Ok(parent) = posts::get_visible(conn, parent_id) {
+            out.push(format!("Re: {}", headline(&parent.body)));
+        }
+    }
     // Split individual lines into separate strings to help the paginator deal with longer chunks.
     for line in post.body.split("\n") {
         linefeed!(out);
@@ -33,11 +121,15 @@ fn post_print(post: &Post, user: &User) -> Vec<String> {
 /// List all the boards.
 pub fn lister(
     conn: &mut SqliteConnection,
-    _cfg: &BBSConfig,
+    cfg: &BBSConfig,
     user: &mut User,
     _args: Vec<&str>,
 ) -> Replies {
-    let all_boards = boards::all(conn);
+    let sysop = is_sysop(cfg, user);
+    let all_boards: Vec<_> = boards::all(conn)
+        .into_iter()
+        .filter(|board| board.readable_by(sysop))
+        .collect();
     if all_boards.is_empty() {
         return NO_BOARDS.into();
     }
@@ -60,7 +152,12 @@ pub fn lister(
         if !prefix.is_empty() {
             prefix += " ";
         }
-        out.push(format!("{prefix}{board}"));
+        let suffix = if board.writable_by(sysop) {
+            ""
+        } else {
+            " (read-only)"
+        };
+        out.push(format!("{prefix}{board}{suffix}"));
     }
     if user.in_board().is_some() {
         linefeed!(out);
@@ -76,7 +173,7 @@ pub fn lister(
 #[allow(clippy::needless_pass_by_value)]
 pub fn enter(
     conn: &mut SqliteConnection,
-    _cfg: &BBSConfig,
+    cfg: &BBSConfig,
     user: &mut User,
     args: Vec<&str>,
 ) -> Replies {
@@ -86,15 +183,16 @@ pub fn enter(
     let Ok(num) = num.parse::<i32>() else {
         return NOT_VALID.into();
     };
-    let count = boards::count(conn);
-    if count == 0 {
+    if boards::count(conn) == 0 {
         return NO_BOARDS.into();
     }
-    if num < 1 || num > count {
-        return format!("Board number must be between 1 and {count}").into();
-    }
+    // Deleted boards leave gaps in the numbering, so look the board up rather than assuming
+    // they're numbered 1 through the count. Act like hidden boards don't exist at all.
+    let board = match boards::get(conn, num) {
+        Ok(board) if board.readable_by(is_sysop(cfg, user)) => board,
+        _ => return format!("There is no board #{num}.").into(),
+    };
     let _ = users::enter_board(conn, user, num);
-    let board = boards::get(conn, num).expect("we should find a board that we already know exists");
     format!("Entering board {num}, {}.", board.name).into()
 }
 
@@ -110,7 +208,7 @@ pub fn current(
     };
     let last_seen = board_states::get(conn, user.account_id(), in_board);
     if let Ok((post, post_user)) = posts::current(conn, in_board, last_seen) {
-        post_print(&post, &post_user).into()
+        post_print(conn, &post, &post_user).into()
     } else {
         NO_SUCH_POST.into()
     }
@@ -129,7 +227,7 @@ pub fn previous(
     let last_seen = board_states::get(conn, user.account_id(), in_board);
     if let Ok((post, post_user)) = posts::before(conn, in_board, last_seen) {
         board_states::update(conn, user.account_id(), in_board, post.created_at_us);
-        post_print(&post, &post_user).into()
+        post_print(conn, &post, &post_user).into()
     } else {
         NO_MORE_POSTS.into()
     }
@@ -146,9 +244,9 @@ pub fn next(
         return NOT_IN_BOARD.into();
     };
     let last_seen = board_states::get(conn, user.account_id(), in_board);
-    if let Ok((post, post_user)) = posts::after(conn, in_board, last_seen) {
+    if let Ok((post, post_user)) = next_wanted(conn, user, in_board, last_seen) {
         board_states::update(conn, user.account_id(), in_board, post.created_at_us);
-        post_print(&post, &post_user).into()
+        post_print(conn, &post, &post_user).into()
     } else {
         NO_MORE_POSTS.into()
     }
@@ -157,7 +255,7 @@ pub fn next(
 ///Get the next unread message in any board.
 pub fn quick(
     conn: &mut SqliteConnection,
-    _cfg: &BBSConfig,
+    cfg: &BBSConfig,
     user: &mut User,
     _args: Vec<&str>,
 ) -> Replies {
@@ -175,13 +273,22 @@ pub fn quick(
     //
     // That way they'll see everything in this board, then everything in the next, then the next,
     // and wrap around at the first board and keep going.
-    let mut board_nums: Vec<i32> = (1..=boards::count(conn)).collect();
-    board_nums.rotate_left(in_board as usize - 1);
+    let mut board_nums: Vec<i32> = boards::all(conn).iter().map(|board| board.id).collect();
+    let start = board_nums
+        .iter()
+        .position(|&num| num >= in_board)
+        .unwrap_or(0);
+    board_nums.rotate_left(start);
 
+    let sysop = is_sysop(cfg, user);
     let mut out = vec![];
     for board_num in board_nums {
+        match boards::get(conn, board_num) {
+            Ok(board) if board.readable_by(sysop) => {}
+            _ => continue,
+        }
         let last_seen = board_states::get(conn, user.account_id(), board_num);
-        if let Ok((post, post_user)) = posts::after(conn, board_num, last_seen) {
+        if let Ok((post, post_user)) = next_wanted(conn, user, board_num, last_seen) {
             if user.in_board().is_none() || board_num != in_board {
                 let _ = users::enter_board(conn, user, board_num);
                 // Let the user know they're moving to a different board to read the new post.
@@ -190,7 +297,7 @@ pub fn quick(
                 linefeed!(out);
             }
             board_states::update(conn, user.account_id(), board_num, post.created_at_us);
-            out.extend(post_print(&post, &post_user));
+            out.extend(post_print(conn, &post, &post_user));
             return out.into();
         }
     }
@@ -204,13 +311,16 @@ const DUPLICATE_POST: &str = "This is an exact copy of your last post in this bo
 #[allow(clippy::needless_pass_by_value)]
 pub fn write(
     conn: &mut SqliteConnection,
-    _cfg: &BBSConfig,
+    cfg: &BBSConfig,
     user: &mut User,
     args: Vec<&str>,
 ) -> Replies {
     let Some(in_board) = user.in_board() else {
         return NOT_IN_BOARD.into();
     };
+    if !can_write(conn, cfg, user, in_board) {
+        return NOT_WRITABLE.into();
+    }
     let Some(body) = args.get(1) else {
         return ERROR_POSTING.into();
     };
@@ -226,6 +336,337 @@ pub fn write(
     format!("Published at {}", post.created_at()).into()
 }
 
+/// Reply to the current post.
+#[allow(clippy::needless_pass_by_value)]
+pub fn reply(
+    conn: &mut SqliteConnection,
+    cfg: &BBSConfig,
+    user: &mut User,
+    args: Vec<&str>,
+) -> Replies {
+    let Some(in_board) = user.in_board() else {
+        return NOT_IN_BOARD.into();
+    };
+    if !can_write(conn, cfg, user, in_board) {
+        return NOT_WRITABLE.into();
+    }
+    let Some(body) = args.get(1) else {
+        return ERROR_POSTING.into();
+    };
+    let last_seen = board_states::get(conn, user.account_id(), in_board);
+    let Ok((parent, _)) = posts::current(conn, in_board, last_seen) else {
+        return NO_SUCH_POST.into();
+    };
+    if let Ok(last_post) = posts::last_by_account_in_board(conn, user.account_id(), in_board) {
+        if last_post.body == *body {
+            return DUPLICATE_POST.into();
+        }
+    }
+    let Ok(post) = posts::reply(conn, user.account_id(), &parent, body) else {
+        log::error!("User {user} was unable to reply {args:?} to post {}.", parent.id);
+        return ERROR_POSTING.into();
+    };
+    format!("Published at {}", post.created_at()).into()
+}
+
+/// Move to another post in the current post's thread, skipping any that were moved to boards
+/// the user can't read.
+fn thread_step(
+    conn: &mut SqliteConnection,
+    cfg: &BBSConfig,
+    user: &User,
+    forward: bool,
+) -> Replies {
+    let Some(in_board) = user.in_board() else {
+        return NOT_IN_BOARD.into();
+    };
+    let last_seen = board_states::get(conn, user.account_id(), in_board);
+    let Ok((current, _)) = posts::current(conn, in_board, last_seen) else {
+        return NO_SUCH_POST.into();
+    };
+    let sysop = is_sysop(cfg, user);
+    let readable: Vec<i32> = boards::all(conn)
+        .into_iter()
+        .filter(|board| board.readable_by(sysop))
+        .map(|board| board.id)
+        .collect();
+    let mut thread = posts::thread(conn, &current);
+    thread.retain(|(post, _)| post.board_id == in_board || readable.contains(&post.board_id));
+    let Some(here) = thread.iter().position(|(post, _)| post.id == current.id) else {
+        return NO_SUCH_POST.into();
+    };
+    let there = if forward {
+        thread.get(here + 1)
+    } else {
+        here.checked_sub(1).and_then(|i| thread.get(i))
+    };
+    let Some((post, post_user)) = there else {
+        return NO_MORE_IN_THREAD.into();
+    };
+    let mut out = vec![];
+    if post.board_id != in_board {
+        // Replies always land in their parent's board, but posts can be moved around later.
+        let _ = users::enter_board(conn, user, post.board_id);
+        let board = boards::get(conn, post.board_id).expect("this board should exist");
+        out.push(format!("In {}:", board.name));
+        linefeed!(out);
+    }
+    board_states::update(conn, user.account_id(), post.board_id, post.created_at_us);
+    out.extend(post_print(conn, post, post_user));
+    out.into()
+}
+
+/// Get the next message in the current post's thread.
+pub fn thread_next(
+    conn: &mut SqliteConnection,
+    cfg: &BBSConfig,
+    user: &mut User,
+    _args: Vec<&str>,
+) -> Replies {
+    thread_step(conn, cfg, user, true)
+}
+
+/// Get the previous message in the current post's thread.
+pub fn thread_previous(
+    conn: &mut SqliteConnection,
+    cfg: &BBSConfig,
+    user: &mut User,
+    _args: Vec<&str>,
+) -> Replies {
+    thread_step(conn, cfg, user, false)
+}
+
+/// List the headers of the newest posts in the board. If they're newer than where the user was
+/// reading, skip ahead to just before them so that N picks up with the first one listed.
+#[allow(clippy::needless_pass_by_value)]
+pub fn headers(
+    conn: &mut SqliteConnection,
+    _cfg: &BBSConfig,
+    user: &mut User,
+    args: Vec<&str>,
+) -> Replies {
+    let Some(in_board) = user.in_board() else {
+        return NOT_IN_BOARD.into();
+    };
+    let count = match args.get(1).map(|num| num.parse::<usize>()) {
+        None => DEFAULT_HEADERS,
+        Some(Ok(num)) if (1..=MAX_HEADERS).contains(&num) => num,
+        Some(_) => return format!("List 1 to {MAX_HEADERS} posts.").into(),
+    };
+    let total = posts::count_visible(conn, in_board);
+    if total == 0 {
+        return NO_SUCH_POST.into();
+    }
+    let start = total.saturating_sub(count);
+
+    // The post just before the first one listed.
+    if let Some(skip_to) = posts::newest_timestamp(conn, in_board, count) {
+        if skip_to > board_states::get(conn, user.account_id(), in_board) {
+            board_states::update(conn, user.account_id(), in_board, skip_to);
+        }
+    }
+
+    let mut out = vec![];
+    for (i, (post, post_user)) in posts::visible_range(conn, in_board, start, count)
+        .iter()
+        .enumerate()
+    {
+        out.push(format!(
+            "#{} {} {}: {}",
+            start + i + 1,
+            post.created_at_brief(),
+            post_user.short_name(),
+            snippet(&post.body)
+        ));
+    }
+    linefeed!(out);
+    out.push("Send Gn to read post #n.".to_string());
+    out.into()
+}
+
+/// List the headers of the newest posts in any board the user can read.
+#[allow(clippy::needless_pass_by_value)]
+pub fn latest(
+    conn: &mut SqliteConnection,
+    cfg: &BBSConfig,
+    user: &mut User,
+    args: Vec<&str>,
+) -> Replies {
+    newest(conn, &args, is_sysop(cfg, user))
+}
+
+/// List the headers of the newest posts in the boards everyone can read. Everyone on a public
+/// channel sees the reply, even when a sysop asked.
+#[allow(clippy::needless_pass_by_value)]
+pub fn latest_public(
+    conn: &mut SqliteConnection,
+    _cfg: &BBSConfig,
+    _user: &mut User,
+    args: Vec<&str>,
+) -> Replies {
+    newest(conn, &args, false)
+}
+
+/// List the headers of the newest posts, including hidden boards' if `sysop`.
+fn newest(conn: &mut SqliteConnection, args: &[&str], sysop: bool) -> Replies {
+    let count = match args.get(1).map(|num| num.parse::<usize>()) {
+        None => DEFAULT_HEADERS,
+        Some(Ok(num)) if (1..=MAX_HEADERS).contains(&num) => num,
+        Some(_) => return format!("List 1 to {MAX_HEADERS} posts.").into(),
+    };
+    let newest = posts::newest(conn, count, sysop);
+    if newest.is_empty() {
+        return NO_SUCH_POST.into();
+    }
+
+    newest
+        .iter()
+        .map(|(post, post_user)| {
+            format!(
+                "B{} {} {}: {}",
+                post.board_id,
+                post.created_at_brief(),
+                post_user.short_name(),
+                snippet(&post.body)
+            )
+        })
+        .collect::<Vec<_>>()
+        .into()
+}
+
+/// Read a post in the board by its number.
+#[allow(clippy::needless_pass_by_value)]
+pub fn goto(
+    conn: &mut SqliteConnection,
+    _cfg: &BBSConfig,
+    user: &mut User,
+    args: Vec<&str>,
+) -> Replies {
+    let Some(in_board) = user.in_board() else {
+        return NOT_IN_BOARD.into();
+    };
+    let Some(num) = args.get(1).and_then(|num| num.parse::<usize>().ok()) else {
+        return NOT_VALID.into();
+    };
+    let total = posts::count_visible(conn, in_board);
+    let Some((post, post_user)) = num
+        .checked_sub(1)
+        .and_then(|i| posts::visible_range(conn, in_board, i, 1).pop())
+    else {
+        return format!("There is no post #{num}. This board has {total}.").into();
+    };
+    board_states::update(conn, user.account_id(), in_board, post.created_at_us);
+    let mut out = vec![format!("Post #{num}/{total}")];
+    out.extend(post_print(conn, &post, &post_user));
+    out.into()
+}
+
+/// Mark every post in the current board as read.
+pub fn catch_up(
+    conn: &mut SqliteConnection,
+    _cfg: &BBSConfig,
+    user: &mut User,
+    _args: Vec<&str>,
+) -> Replies {
+    let Some(in_board) = user.in_board() else {
+        return NOT_IN_BOARD.into();
+    };
+    board_states::catch_up(conn, user.account_id(), in_board, 0);
+    "Marked this board as read.".into()
+}
+
+/// Mark every post in every board as read, except for optionally the newest few in each.
+#[allow(clippy::needless_pass_by_value)]
+pub fn catch_up_all(
+    conn: &mut SqliteConnection,
+    _cfg: &BBSConfig,
+    user: &mut User,
+    args: Vec<&str>,
+) -> Replies {
+    let leave_unread = match args.get(1).map(|num| num.parse::<usize>()) {
+        None => 0,
+        Some(Ok(num)) => num,
+        Some(Err(_)) => return NOT_VALID.into(),
+    };
+    for board in boards::all(conn) {
+        board_states::catch_up(conn, user.account_id(), board.id, leave_unread);
+    }
+    if leave_unread == 0 {
+        "Marked all boards as read.".into()
+    } else {
+        format!("Marked all but the newest {leave_unread} post(s) in each board as read.").into()
+    }
+}
+
+/// Find posts in all boards containing these keywords.
+#[allow(clippy::needless_pass_by_value)]
+pub fn search(
+    conn: &mut SqliteConnection,
+    cfg: &BBSConfig,
+    user: &mut User,
+    args: Vec<&str>,
+) -> Replies {
+    let Some(terms) = args.get(1) else {
+        return NO_SEARCH_RESULTS.into();
+    };
+    let results = posts::search(conn, terms, is_sysop(cfg, user), MAX_SEARCH_RESULTS);
+    let post_ids: Vec<i32> = results.iter().map(|(post, _)| post.id).collect();
+    if let Ok(updated) = users::set_search_results(conn, user, &post_ids) {
+        *user = updated;
+    }
+    if results.is_empty() {
+        return NO_SEARCH_RESULTS.into();
+    }
+    let mut out = vec!["Results:".to_string()];
+    linefeed!(out);
+    for (i, (post, post_user)) in results.iter().enumerate() {
+        out.push(format!(
+            "{}. B{} {}: {}",
+            i + 1,
+            post.board_id,
+            post_user.short_name(),
+            snippet(&post.body)
+        ));
+    }
+    linefeed!(out);
+    out.push("Send Fn to read result n.".to_string());
+    out.into()
+}
+
+/// Read a result from the latest search, and pick up reading in its board from there.
+#[allow(clippy::needless_pass_by_value)]
+pub fn search_result(
+    conn: &mut SqliteConnection,
+    cfg: &BBSConfig,
+    user: &mut User,
+    args: Vec<&str>,
+) -> Replies {
+    let Some(num) = args.get(1).and_then(|num| num.parse::<usize>().ok()) else {
+        return NOT_VALID.into();
+    };
+    let results = user.account.search_results();
+    let Some(post_id) = num.checked_sub(1).and_then(|i| results.get(i)) else {
+        return NO_SUCH_RESULT.into();
+    };
+    let Ok((post, post_user)) = posts::get_with_author(conn, *post_id) else {
+        return NO_SUCH_POST.into();
+    };
+    let Ok(board) = boards::get(conn, post.board_id) else {
+        return NO_SUCH_POST.into();
+    };
+    if !board.readable_by(is_sysop(cfg, user)) {
+        return NO_SUCH_POST.into();
+    }
+    if let Ok(updated) = users::enter_board(conn, user, board.id) {
+        *user = updated;
+    }
+    board_states::update(conn, user.account_id(), board.id, post.created_at_us);
+    let mut out = vec![format!("In {}:", board.name)];
+    linefeed!(out);
+    out.extend(post_print(conn, &post, &post_user));
+    out.into()
+}
+
 /// Show information about the current post's author
 #[allow(clippy::needless_pass_by_value)]
 pub fn author(
@@ -256,3 +697,338 @@ pub fn author(
         NO_SUCH_POST.into()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+
+    #[test]
+    fn headers_skip_ahead_and_goto_reads_by_number() {
+        let mut conn = db::test_connection();
+        let cfg = BBSConfig::for_tests();
+        let board = boards::add(&mut conn, "General", "General discussion").expect("board");
+        let (author, _) = users::record(&mut conn, "!40000041").expect("author");
+        let mut posted = vec![];
+        for i in 1..=4 {
+            let body = format!("Post number {i}");
+            posted.push(posts::add(&mut conn, author.account_id(), board.id, &body).expect("post"));
+            std::thread::sleep(std::time::Duration::from_micros(10));
+        }
+
+        let (user, _) = users::record(&mut conn, "!40000040").expect("user");
+        let mut user = users::enter_board(&mut conn, &user, board.id).expect("enter");
+        let text = headers(&mut conn, &cfg, &mut user, vec!["l2", "2"]).0[0]
+            .out
+            .join("\n");
+        assert!(text.starts_with("#3 "), "got: {text}");
+        assert!(text.contains("????: Post number 4"), "got: {text}");
+        assert!(!text.contains("#2 "), "got: {text}");
+        assert_eq!(
+            board_states::get(&mut conn, user.account_id(), board.id),
+            posted[1].created_at_us
+        );
+        let text = next(&mut conn, &cfg, &mut user, vec!["n"]).0[0]
+            .out
+            .join("\n");
+        assert!(text.ends_with("Post number 3"), "got: {text}");
+
+        // Listing again doesn't move the reader backward.
+        headers(&mut conn, &cfg, &mut user, vec!["l"]);
+        assert_eq!(
+            board_states::get(&mut conn, user.account_id(), board.id),
+            posted[2].created_at_us
+        );
+
+        let text = goto(&mut conn, &cfg, &mut user, vec!["g1", "1"]).0[0]
+            .out
+            .join("\n");
+        assert!(text.starts_with("Post #1/4"), "got: {text}");
+        assert!(text.ends_with("Post number 1"), "got: {text}");
+        assert_eq!(
+            board_states::get(&mut conn, user.account_id(), board.id),
+            posted[0].created_at_us
+        );
+        let replies = goto(&mut conn, &cfg, &mut user, vec!["g5", "5"]);
+        assert_eq!(
+            replies.0[0].out,
+            vec!["There is no post #5. This board has 4.".to_string()]
+        );
+    }
+
+    #[test]
+    fn catch_up_clears_unread_markers() {
+        let mut conn = db::test_connection();
+        let cfg = BBSConfig::for_tests();
+        let general = boards::add(&mut conn, "General", "General discussion").expect("board");
+        let gear = boards::add(&mut conn, "Gear", "Radios and antennas").expect("board");
+        let (author, _) = users::record(&mut conn, "!40000051").expect("author");
+        for board_id in [general.id, gear.id, gear.id] {
+            posts::add(&mut conn, author.account_id(), board_id, "Hello").expect("post");
+            std::thread::sleep(std::time::Duration::from_micros(10));
+        }
+
+        let (user, _) = users::record(&mut conn, "!40000050").expect("user");
+        let mut user = users::enter_board(&mut conn, &user, general.id).expect("enter");
+        catch_up(&mut conn, &cfg, &mut user, vec!["c"]);
+        assert!(!has_unread(&mut conn, &user, general.id));
+        assert!(has_unread(&mut conn, &user, gear.id));
+
+        catch_up_all(&mut conn, &cfg, &mut user, vec!["ca 1", "1"]);
+        let replies = next(&mut conn, &cfg, &mut user, vec!["n"]);
+        assert_eq!(replies.0[0].out, vec![NO_MORE_POSTS.to_string()]);
+        assert!(has_unread(&mut conn, &user, gear.id));
+        catch_up_all(&mut conn, &cfg, &mut user, vec!["ca"]);
+        assert!(!has_unread(&mut conn, &user, gear.id));
+    }
+
+    #[test]
+    fn search_results_jump_to_their_board() {
+        let mut conn = db::test_connection();
+        let cfg = BBSConfig::for_tests();
+        boards::add(&mut conn, "General", "General discussion").expect("board");
+        let board = boards::add(&mut conn, "Gear", "Radios and antennas").expect("board");
+        let (author, _) = users::record(&mut conn, "!40000031").expect("author");
+        let post = posts::add(
+            &mut conn,
+            author.account_id(),
+            board.id,
+            "Selling a very long and extraordinarily complicated antenna",
+        )
+        .expect("post");
+
+        let (mut user, _) = users::record(&mut conn, "!40000030").expect("user");
+        let text = search(&mut conn, &cfg, &mut user, vec!["find antenna", "antenna"]).0[0]
+            .out
+            .join("\n");
+        assert!(
+            text.contains("1. B2 ????: Selling a very long and..."),
+            "got: {text}"
+        );
+
+        let text = search_result(&mut conn, &cfg, &mut user, vec!["f1", "1"]).0[0]
+            .out
+            .join("\n");
+        assert!(text.starts_with("In Gear:"), "got: {text}");
+        let user = users::get(&mut conn, "!40000030").expect("user");
+        assert_eq!(user.in_board(), Some(board.id));
+        assert_eq!(
+            board_states::get(&mut conn, user.account_id(), board.id),
+            post.created_at_us
+        );
+
+        let mut user = user;
+        let replies = search_result(&mut conn, &cfg, &mut user, vec!["f2", "2"]);
+        assert_eq!(replies.0[0].out, vec![NO_SUCH_RESULT.to_string()]);
+        let replies = search(&mut conn, &cfg, &mut user, vec!["find zebra", "zebra"]);
+        assert_eq!(replies.0[0].out, vec![NO_SEARCH_RESULTS.to_string()]);
+        let replies = search_result(&mut conn, &cfg, &mut user, vec!["f1", "1"]);
+        assert_eq!(replies.0[0].out, vec![NO_SUCH_RESULT.to_string()]);
+    }
+
+    #[test]
+    fn hidden_boards_are_invisible_to_regular_users() {
+        let mut conn = db::test_connection();
+        let mut cfg = BBSConfig::for_tests();
+        cfg.sysops = vec!["!40000011".to_string()];
+        boards::add(&mut conn, "General", "General discussion").expect("board");
+        let staff = boards::add(&mut conn, "Staff", "Sysops only").expect("board");
+        boards::set_permissions(&mut conn, staff.id, Some(true), None, None).expect("hide");
+
+        let (mut user, _) = users::record(&mut conn, "!40000010").expect("user");
+        let text = lister(&mut conn, &cfg, &mut user, vec!["b"]).0[0].out.join("\n");
+        assert!(!text.contains("Staff"), "got: {text}");
+        let replies = enter(&mut conn, &cfg, &mut user, vec!["b2", "2"]);
+        assert_eq!(replies.0[0].out, vec!["There is no board #2.".to_string()]);
+
+        let (mut sysop, _) = users::record(&mut conn, "!40000011").expect("sysop");
+        let text = lister(&mut conn, &cfg, &mut sysop, vec!["b"]).0[0].out.join("\n");
+        assert!(text.contains("Staff"), "got: {text}");
+        let replies = enter(&mut conn, &cfg, &mut sysop, vec!["b2", "2"]);
+        assert!(replies.0[0].out[0].starts_with("Entering board 2"));
+    }
+
+    #[test]
+    fn latest_lists_the_newest_posts_in_readable_boards() {
+        let mut conn = db::test_connection();
+        let mut cfg = BBSConfig::for_tests();
+        cfg.sysops = vec!["!40000052".to_string()];
+        let general = boards::add(&mut conn, "General", "General discussion").expect("board");
+        let secret = boards::add(&mut conn, "Secret", "Sysops only").expect("board");
+        boards::set_permissions(&mut conn, secret.id, Some(true), None, None).expect("hide");
+        let (author, _) = users::record(&mut conn, "!40000050").expect("author");
+        for (board_id, body) in [
+            (general.id, "First"),
+            (secret.id, "Hidden"),
+            (general.id, "Second"),
+            (general.id, "Third"),
+        ] {
+            posts::add(&mut conn, author.account_id(), board_id, body).expect("post");
+            std::thread::sleep(std::time::Duration::from_micros(10));
+        }
+
+        let (mut user, _) = users::record(&mut conn, "!40000051").expect("user");
+        let out = latest(&mut conn, &cfg, &mut user, vec!["latest 2", "2"]).0[0]
+            .out
+            .clone();
+        assert_eq!(out.len(), 2, "got: {out:?}");
+        assert!(out[0].starts_with("B1 ") && out[0].ends_with("Second"));
+        assert!(out[1].ends_with("Third"));
+        let text = latest(&mut conn, &cfg, &mut user, vec!["latest"]).0[0]
+            .out
+            .join("\n");
+        assert!(!text.contains("Hidden"), "got: {text}");
+
+        // Sysops see hidden boards, but not when everyone on a public channel would see them too.
+        let (mut sysop, _) = users::record(&mut conn, "!40000052").expect("sysop");
+        let text = latest(&mut conn, &cfg, &mut sysop, vec!["latest"]).0[0]
+            .out
+            .join("\n");
+        assert!(text.contains("Hidden"), "got: {text}");
+        let text = latest_public(&mut conn, &cfg, &mut sysop, vec!["latest"]).0[0]
+            .out
+            .join("\n");
+        assert!(!text.contains("Hidden"), "got: {text}");
+    }
+
+    #[test]
+    fn sysop_post_boards_reject_regular_users() {
+        let mut conn = db::test_connection();
+        let mut cfg = BBSConfig::for_tests();
+        cfg.sysops = vec!["!40000021".to_string()];
+        let board = boards::add(&mut conn, "News", "Announcements").expect("board");
+        boards::set_permissions(&mut conn, board.id, None, None, Some(true)).expect("lock");
+
+        let (user, _) = users::record(&mut conn, "!40000020").expect("user");
+        let mut user = users::enter_board(&mut conn, &user, board.id).expect("enter");
+        let text = lister(&mut conn, &cfg, &mut user, vec!["b"]).0[0].out.join("\n");
+        assert!(text.contains("(read-only)"), "got: {text}");
+        let replies = write(&mut conn, &cfg, &mut user, vec!["w hi", "hi"]);
+        assert_eq!(replies.0[0].out, vec![NOT_WRITABLE.to_string()]);
+
+        let (sysop, _) = users::record(&mut conn, "!40000021").expect("sysop");
+        let mut sysop = users::enter_board(&mut conn, &sysop, board.id).expect("enter");
+        let replies = write(&mut conn, &cfg, &mut sysop, vec!["w hi", "hi"]);
+        assert!(replies.0[0].out[0].starts_with("Published at"));
+    }
+
+    #[test]
+    fn headline_keeps_short_bodies_whole() {
+        assert_eq!(headline("Anyone  around\ntonight?"), "Anyone around tonight?");
+    }
+
+    #[test]
+    fn headline_truncates_long_bodies() {
+        assert_eq!(
+            headline("one two three four five six seven"),
+            "one two three four five..."
+        );
+    }
+
+    #[test]
+    fn reply_threads_under_the_current_post_and_walks_the_thread() {
+        let mut conn = db::test_connection();
+        let cfg = BBSConfig::for_tests();
+        let board = boards::add(&mut conn, "General", "General discussion").expect("board");
+        let (author, _) = users::record(&mut conn, "!40000001").expect("author");
+        let root = posts::add(&mut conn, author.account_id(), board.id, "Who has a spare antenna")
+            .expect("root");
+        std::thread::sleep(std::time::Duration::from_micros(10));
+        posts::add(&mut conn, author.account_id(), board.id, "Unrelated").expect("other");
+
+        let (mut user, _) = users::record(&mut conn, "!40000002").expect("user");
+        user = users::enter_board(&mut conn, &user, board.id).expect("enter");
+        board_states::update(&mut conn, user.account_id(), board.id, root.created_at_us);
+
+        let replies = reply(&mut conn, &cfg, &mut user, vec!["re me", "me"]);
+        assert!(replies.0[0].out[0].starts_with("Published at"));
+
+        let replies = thread_next(&mut conn, &cfg, &mut user, vec!["tn"]);
+        let text = replies.0[0].out.join("\n");
+        assert!(text.contains("Re: Who has a spare antenna"), "got: {text}");
+        assert!(text.ends_with("me"), "got: {text}");
+
+        let replies = thread_next(&mut conn, &cfg, &mut user, vec!["tn"]);
+        assert_eq!(replies.0[0].out, vec![NO_MORE_IN_THREAD.to_string()]);
+
+        let replies = thread_previous(&mut conn, &cfg, &mut user, vec!["tp"]);
+        assert!(replies.0[0].out.join("\n").ends_with("Who has a spare antenna"));
+    }
+
+    #[test]
+    fn replies_dont_quote_banned_authors() {
+        let mut conn = db::test_connection();
+        let board = boards::add(&mut conn, "General", "General discussion").expect("board");
+        let (jerk, _) = users::record(&mut conn, "!40000005").expect("jerk");
+        let (author, _) = users::record(&mut conn, "!40000006").expect("author");
+        let root =
+            posts::add(&mut conn, jerk.account_id(), board.id, "Buy my stuff").expect("root");
+        let answer = posts::reply(&mut conn, author.account_id(), &root, "No").expect("reply");
+        assert!(post_print(&mut conn, &answer, &author).contains(&"Re: Buy my stuff".to_string()));
+
+        users::ban(&mut conn, "admin", &jerk, None).expect("ban");
+        let text = post_print(&mut conn, &answer, &author).join("\n");
+        assert!(!text.contains("Re:"), "got: {text}");
+        assert!(text.ends_with("No"), "got: {text}");
+    }
+
+    #[test]
+    fn threads_skip_replies_moved_to_hidden_boards() {
+        let mut conn = db::test_connection();
+        let cfg = BBSConfig::for_tests();
+        let board = boards::add(&mut conn, "General", "General discussion").expect("board");
+        let secret = boards::add(&mut conn, "Secret", "Sysops only").expect("board");
+        boards::set_permissions(&mut conn, secret.id, Some(true), None, None).expect("hide");
+        let (author, _) = users::record(&mut conn, "!40000003").expect("author");
+        let root = posts::add(&mut conn, author.account_id(), board.id, "Root").expect("root");
+        let hidden = posts::reply(&mut conn, author.account_id(), &root, "Moved away").expect("re");
+        posts::move_to_board(&mut conn, hidden.id, secret.id).expect("move");
+
+        let (user, _) = users::record(&mut conn, "!40000004").expect("user");
+        let mut user = users::enter_board(&mut conn, &user, board.id).expect("enter");
+        board_states::update(&mut conn, user.account_id(), board.id, root.created_at_us);
+        let replies = thread_next(&mut conn, &cfg, &mut user, vec!["tn"]);
+        assert_eq!(replies.0[0].out, vec![NO_MORE_IN_THREAD.to_string()]);
+    }
+
+    #[test]
+    fn ignored_authors_are_skipped_only_on_request() {
+        let mut conn = db::test_connection();
+        let cfg = BBSConfig::for_tests();
+        let board = boards::add(&mut conn, "General", "General discussion").expect("board");
+        let (pest, _) = users::record(&mut conn, "!40000050").expect("pest");
+        let (friend, _) = users::record(&mut conn, "!40000051").expect("friend");
+        let mut last_post = None;
+        for (author, body) in [(&pest, "Spam"), (&friend, "Hello"), (&pest, "More spam")] {
+            last_post =
+                Some(posts::add(&mut conn, author.account_id(), board.id, body).expect("post"));
+            std::thread::sleep(std::time::Duration::from_micros(10));
+        }
+        let last_post = last_post.expect("post");
+        let (user, _) = users::record(&mut conn, "!40000052").expect("user");
+        ignores::add(&mut conn, &user, &pest).expect("ignore");
+
+        // Ignoring someone doesn't hide their posts by itself.
+        let mut user = users::enter_board(&mut conn, &user, board.id).expect("enter");
+        let text = next(&mut conn, &cfg, &mut user, vec!["n"]).0[0]
+            .out
+            .join("\n");
+        assert!(text.ends_with("Spam"), "got: {text}");
+
+        let mut user = users::set_skip_ignored_posts(&mut conn, &user, true).expect("skip");
+        board_states::update(&mut conn, user.account_id(), board.id, 0);
+        let text = quick(&mut conn, &cfg, &mut user, vec!["q"]).0[0]
+            .out
+            .join("\n");
+        assert!(text.ends_with("Hello"), "got: {text}");
+        // Only skipped posts are left, so the board doesn't count as unread.
+        assert!(!has_unread(&mut conn, &user, board.id));
+        let replies = next(&mut conn, &cfg, &mut user, vec!["n"]);
+        assert_eq!(replies.0[0].out, vec![NO_MORE_POSTS.to_string()]);
+        // And the read position moved past them, so they aren't scanned again.
+        assert_eq!(
+            board_states::get(&mut conn, user.account_id(), board.id),
+            last_post.created_at_us
+        );
+    }
+}
@@ -1,11 +1,49 @@
-use super::{Replies, ERROR_POSTING};
-use crate::db::{queued_messages, users, User};
-use crate::{canonical_node_id, BBSConfig};
+use super::board::snippet;
+use super::{find_user, Replies, ERROR_POSTING};
+use crate::db::{
+    formatted_useconds, ignores, now_as_useconds, queued_messages, users, QueuedMessage, User,
+};
+use crate::{canonical_node_id, linefeed, BBSConfig};
 use diesel::SqliteConnection;
 
-const INVALID_NODEID: &str = "The given address is invalid.";
-const NO_SUCH_USER: &str = "That user does not exist.";
 const WILL_NOT_MESSAGE_MYSELF: &str = "You cannot send a direct message to this BBS.";
+const NO_MESSAGES: &str = "You have no messages.";
+const NO_SENT_MESSAGES: &str = "You haven't sent any messages.";
+/// How many messages to list if the user doesn't say.
+const DEFAULT_MESSAGES: usize = 5;
+/// The most messages to list at once, and the oldest one that can be reread.
+const MAX_MESSAGES: usize = 20;
+
+/// How many messages did the user ask to see?
+fn message_count(args: &[&str]) -> Result<usize, String> {
+    match args.get(1).map(|num| num.parse::<usize>()) {
+        None => Ok(DEFAULT_MESSAGES),
+        Some(Ok(num)) if (1..=MAX_MESSAGES).contains(&num) => Ok(num),
+        Some(_) => Err(format!("List 1 to {MAX_MESSAGES} messages.")),
+    }
+}
+
+/// The short name of a message's sender or recipient.
+fn short_name_of(conn: &mut SqliteConnection, account_id: i32) -> String {
+    users::get_by_account_id(conn, account_id)
+        .map_or_else(|_| "????".to_string(), |user| user.short_name().to_string())
+}
+
+/// Print a message the way it's delivered.
+fn message_print(
+    conn: &mut SqliteConnection,
+    cfg: &BBSConfig,
+    message: &QueuedMessage,
+) -> Vec<String> {
+    let headline = if message.notice {
+        format!("Notice from {} at {}:", cfg.bbs_name, message.created_at())
+    } else {
+        let sender = users::get_by_account_id(conn, message.sender_account_id)
+            .map_or_else(|_| "an unknown user".to_string(), |user| user.to_string());
+        format!("Message from {} at {}:", sender, message.created_at())
+    };
+    vec![headline, String::new(), message.body.to_string()]
+}
 
 /// Message another user
 #[allow(clippy::needless_pass_by_value)]
@@ -22,26 +60,349 @@ pub fn send(
         return "Unable to find the message".into();
     };
 
-    let recipient: User = if node_id.len() > 5 || node_id.starts_with('!') {
-        let Some(node_id) = canonical_node_id(node_id) else {
-            return INVALID_NODEID.into();
-        };
-        if cfg.my_id == node_id {
-            return WILL_NOT_MESSAGE_MYSELF.into();
-        }
-        match users::get(conn, &node_id) {
-            Ok(x) => x,
-            Err(_) => return NO_SUCH_USER.into(),
-        }
-    } else {
-        match users::get_by_short_name(conn, node_id) {
-            Some(x) => x,
-            None => return NO_SUCH_USER.into(),
-        }
+    if canonical_node_id(node_id).is_some_and(|node_id| node_id == cfg.my_id) {
+        return WILL_NOT_MESSAGE_MYSELF.into();
+    }
+    let recipient = match find_user(conn, node_id) {
+        Ok(x) => x,
+        Err(err) => return err.into(),
     };
+    queue(conn, cfg, user, &recipient, body)
+}
 
-    let Ok(post) = queued_messages::post(conn, user, &recipient, body) else {
+/// Reply to whoever last sent the user a message.
+#[allow(clippy::needless_pass_by_value)]
+pub fn reply(
+    conn: &mut SqliteConnection,
+    cfg: &BBSConfig,
+    user: &mut User,
+    args: Vec<&str>,
+) -> Replies {
+    let Some(body) = args.get(1) else {
+        return "Unable to find the message".into();
+    };
+    let Some(recipient) = queued_messages::last_sender(conn, user)
+        .and_then(|account_id| users::get_by_account_id(conn, account_id).ok())
+    else {
+        return "Nobody has sent you a message yet.".into();
+    };
+    queue(conn, cfg, user, &recipient, body)
+}
+
+/// Queue a message and tell the sender how it went.
+fn queue(
+    conn: &mut SqliteConnection,
+    cfg: &BBSConfig,
+    user: &User,
+    recipient: &User,
+    body: &str,
+) -> Replies {
+    // Don't let on that the recipient is ignoring them. That only invites them to try harder.
+    if ignores::is_ignoring(conn, recipient.account_id(), user.account_id()) {
+        return format!("Published at {}", formatted_useconds(now_as_useconds())).into();
+    }
+    let limits = &cfg.messages;
+    if queued_messages::pending_count(conn, Some(user.account_id()), recipient.account_id())
+        >= limits.max_pending_per_sender
+    {
+        return format!(
+            "You already have {} messages waiting for {recipient}. Try again after they've read them.",
+            limits.max_pending_per_sender
+        )
+        .into();
+    }
+    if queued_messages::pending_count(conn, None, recipient.account_id())
+        >= limits.max_pending_per_recipient
+    {
+        return format!("{recipient} has too many messages waiting. Try again later.").into();
+    }
+    let Ok(post) = queued_messages::post(conn, user, recipient, body) else {
         return ERROR_POSTING.into();
     };
     format!("Published at {}", post.created_at()).into()
 }
+
+/// List the messages the user most recently received.
+#[allow(clippy::needless_pass_by_value)]
+pub fn inbox(
+    conn: &mut SqliteConnection,
+    cfg: &BBSConfig,
+    user: &mut User,
+    args: Vec<&str>,
+) -> Replies {
+    let count = match message_count(&args) {
+        Ok(x) => x,
+        Err(err) => return err.into(),
+    };
+    let messages = queued_messages::received(conn, user, count as i64);
+    if messages.is_empty() {
+        return NO_MESSAGES.into();
+    }
+    let mut out = vec![];
+    for (i, message) in messages.iter().enumerate() {
+        out.push(format!(
+            "M{} {} {}: {}",
+            i + 1,
+            message.created_at_brief(),
+            if message.notice {
+                cfg.bbs_name.clone()
+            } else {
+                short_name_of(conn, message.sender_account_id)
+            },
+            snippet(&message.body)
+        ));
+    }
+    linefeed!(out);
+    out.push("Send Mn to reread message #n.".to_string());
+    out.into()
+}
+
+/// Reread one of the messages the user received, counting back from the newest.
+#[allow(clippy::needless_pass_by_value)]
+pub fn read(
+    conn: &mut SqliteConnection,
+    cfg: &BBSConfig,
+    user: &mut User,
+    args: Vec<&str>,
+) -> Replies {
+    let num = match args.get(1).map(|num| num.parse::<usize>()) {
+        Some(Ok(num)) if (1..=MAX_MESSAGES).contains(&num) => num,
+        _ => return format!("Read message 1 to {MAX_MESSAGES}.").into(),
+    };
+    let messages = queued_messages::received(conn, user, num as i64);
+    let Some(message) = messages.get(num - 1) else {
+        return format!("There is no message #{num}.").into();
+    };
+    // If they've read it here, there's no need to send it again.
+    if !message.is_delivered() {
+        queued_messages::sent(conn, message);
+    }
+    message_print(conn, cfg, message).into()
+}
+
+/// List the messages the user most recently sent, and whether they've been delivered.
+#[allow(clippy::needless_pass_by_value)]
+pub fn outbox(
+    conn: &mut SqliteConnection,
+    _cfg: &BBSConfig,
+    user: &mut User,
+    args: Vec<&str>,
+) -> Replies {
+    let count = match message_count(&args) {
+        Ok(x) => x,
+        Err(err) => return err.into(),
+    };
+    let messages = queued_messages::sent_by(conn, user, count as i64);
+    if messages.is_empty() {
+        return NO_SENT_MESSAGES.into();
+    }
+    let mut out = vec![];
+    for message in &messages {
+        out.push(format!(
+            "{} {} {}: {}",
+            message.created_at_brief(),
+            short_name_of(conn, message.recipient_account_id),
+            if message.is_delivered() {
+                "delivered"
+            } else if message.is_expired() {
+                "expired"
+            } else {
+                "pending"
+            },
+            snippet(&message.body)
+        ));
+    }
+    out.into()
+}
+
+/// Tell the user when their messages are delivered.
+pub fn receipts_on(
+    conn: &mut SqliteConnection,
+    _cfg: &BBSConfig,
+    user: &mut User,
+    _args: Vec<&str>,
+) -> Replies {
+    let _ = users::set_dm_receipts(conn, user, true);
+    "You'll get a message when your messages are delivered.".into()
+}
+
+/// Stop telling the user when their messages are delivered.
+pub fn receipts_off(
+    conn: &mut SqliteConnection,
+    _cfg: &BBSConfig,
+    user: &mut User,
+    _args: Vec<&str>,
+) -> Replies {
+    let _ = users::set_dm_receipts(conn, user, false);
+    "You won't get delivery receipts anymore.".into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+
+    fn text(replies: &Replies) -> String {
+        replies.0[0].out.join("\n")
+    }
+
+    #[test]
+    fn inbox_lists_and_rereads_received_messages() {
+        let mut conn = db::test_connection();
+        let cfg = BBSConfig::for_tests();
+        let (mut alice, _) = users::record(&mut conn, "!60000011").expect("alice");
+        let (mut bob, _) = users::record(&mut conn, "!60000012").expect("bob");
+        assert_eq!(
+            text(&inbox(&mut conn, &cfg, &mut bob, vec!["in"])),
+            NO_MESSAGES
+        );
+
+        send(
+            &mut conn,
+            &cfg,
+            &mut alice,
+            vec!["dm", "!60000012", "First"],
+        );
+        send(
+            &mut conn,
+            &cfg,
+            &mut alice,
+            vec!["dm", "!60000012", "Second"],
+        );
+        let listing = text(&inbox(&mut conn, &cfg, &mut bob, vec!["in"]));
+        assert!(listing.starts_with("M1 "), "got: {listing}");
+        assert!(listing.contains("????: Second"), "got: {listing}");
+        assert!(listing.contains("M2 "), "got: {listing}");
+
+        let message = text(&read(&mut conn, &cfg, &mut bob, vec!["m2", "2"]));
+        assert!(message.ends_with("First"), "got: {message}");
+        // Rereading it here counts as delivering it.
+        assert_eq!(queued_messages::get(&mut conn, &bob).len(), 1);
+        let missing = text(&read(&mut conn, &cfg, &mut bob, vec!["m3", "3"]));
+        assert_eq!(missing, "There is no message #3.");
+    }
+
+    #[test]
+    fn outbox_shows_delivery_status() {
+        let mut conn = db::test_connection();
+        let cfg = BBSConfig::for_tests();
+        let (mut alice, _) = users::record(&mut conn, "!60000013").expect("alice");
+        let (bob, _) = users::record(&mut conn, "!60000014").expect("bob");
+        send(
+            &mut conn,
+            &cfg,
+            &mut alice,
+            vec!["dm", "!60000014", "Hello"],
+        );
+        let listing = text(&outbox(&mut conn, &cfg, &mut alice, vec!["out"]));
+        assert!(listing.ends_with("pending: Hello"), "got: {listing}");
+
+        for message in queued_messages::get(&mut conn, &bob) {
+            queued_messages::sent(&mut conn, &message);
+        }
+        let listing = text(&outbox(&mut conn, &cfg, &mut alice, vec!["out"]));
+        assert!(listing.ends_with("delivered: Hello"), "got: {listing}");
+    }
+
+    #[test]
+    fn expiry_notices_come_from_the_bbs() {
+        let mut conn = db::test_connection();
+        let cfg = BBSConfig::for_tests();
+        let (mut alice, _) = users::record(&mut conn, "!60000015").expect("alice");
+        users::record(&mut conn, "!60000016").expect("bob");
+        send(
+            &mut conn,
+            &cfg,
+            &mut alice,
+            vec!["dm", "!60000016", "Hello"],
+        );
+        std::thread::sleep(std::time::Duration::from_micros(10));
+        queued_messages::expire(&mut conn, 1);
+
+        let listing = text(&inbox(&mut conn, &cfg, &mut alice, vec!["in"]));
+        assert!(listing.contains("Test BBS: Couldn't"), "got: {listing}");
+        let message = text(&read(&mut conn, &cfg, &mut alice, vec!["m1", "1"]));
+        assert!(
+            message.starts_with("Notice from Test BBS at "),
+            "got: {message}"
+        );
+    }
+
+    #[test]
+    fn reply_goes_to_the_last_sender() {
+        let mut conn = db::test_connection();
+        let cfg = BBSConfig::for_tests();
+        let (mut alice, _) = users::record(&mut conn, "!60000015").expect("alice");
+        let (mut bob, _) = users::record(&mut conn, "!60000016").expect("bob");
+        let (carol, _) = users::record(&mut conn, "!60000017").expect("carol");
+        let replies = reply(&mut conn, &cfg, &mut bob, vec!["dr hi", "hi"]);
+        assert_eq!(text(&replies), "Nobody has sent you a message yet.");
+
+        send(
+            &mut conn,
+            &cfg,
+            &mut alice,
+            vec!["dm", "!60000016", "Hi Bob"],
+        );
+        // System messages like invitations are queued from the inviting account.
+        queued_messages::queue_by_account_ids(
+            &mut conn,
+            carol.account_id(),
+            bob.account_id(),
+            "You have received an invitation",
+        )
+        .expect("invitation");
+        let replies = reply(&mut conn, &cfg, &mut bob, vec!["dr hi", "Hi Carol"]);
+        assert!(text(&replies).starts_with("Published at "));
+        let to_carol = queued_messages::get(&mut conn, &carol);
+        assert_eq!(to_carol.len(), 1);
+        assert_eq!(to_carol[0].body, "Hi Carol");
+        assert!(queued_messages::get(&mut conn, &alice).is_empty());
+    }
+
+    #[test]
+    fn ignored_senders_are_dropped_silently() {
+        let mut conn = db::test_connection();
+        let cfg = BBSConfig::for_tests();
+        let (mut pest, _) = users::record(&mut conn, "!60000018").expect("pest");
+        let (bob, _) = users::record(&mut conn, "!60000019").expect("bob");
+        ignores::add(&mut conn, &bob, &pest).expect("ignore");
+
+        let replies = send(&mut conn, &cfg, &mut pest, vec!["dm", "!60000019", "Hey"]);
+        assert!(text(&replies).starts_with("Published at "));
+        assert!(queued_messages::get(&mut conn, &bob).is_empty());
+    }
+
+    #[test]
+    fn pending_limits_apply_per_sender_and_per_recipient() {
+        let mut conn = db::test_connection();
+        let mut cfg = BBSConfig::for_tests();
+        cfg.messages.max_pending_per_sender = 2;
+        cfg.messages.max_pending_per_recipient = 3;
+        let (mut alice, _) = users::record(&mut conn, "!60000020").expect("alice");
+        let (mut carol, _) = users::record(&mut conn, "!60000021").expect("carol");
+        let (bob, _) = users::record(&mut conn, "!60000022").expect("bob");
+
+        for body in ["One", "Two"] {
+            send(&mut conn, &cfg, &mut alice, vec!["dm", "!60000022", body]);
+        }
+        let replies = send(
+            &mut conn,
+            &cfg,
+            &mut alice,
+            vec!["dm", "!60000022", "Three"],
+        );
+        assert!(text(&replies).starts_with("You already have 2 messages waiting"));
+        send(&mut conn, &cfg, &mut carol, vec!["dm", "!60000022", "Four"]);
+        let replies = send(&mut conn, &cfg, &mut carol, vec!["dm", "!60000022", "Five"]);
+        assert!(text(&replies).contains("has too many messages waiting"));
+        assert_eq!(queued_messages::get(&mut conn, &bob).len(), 3);
+
+        // Once Bob reads them, there's room again.
+        for message in queued_messages::get(&mut conn, &bob) {
+            queued_messages::sent(&mut conn, &message);
+        }
+        let replies = send(&mut conn, &cfg, &mut alice, vec!["dm", "!60000022", "Six"]);
+        assert!(text(&replies).starts_with("Published at "));
+    }
+}
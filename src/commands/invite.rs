@@ -1,7 +1,7 @@
 use super::Replies;
 use crate::db::{
-    board_states, invitations, invitations::EXPIRY_US, now_as_useconds, posts, queued_messages,
-    users, User,
+    audit, board_states, ignores, invitations, invitations::EXPIRY_US, now_as_useconds, posts,
+    queued_messages, users, User,
 };
 use crate::{canonical_node_id, BBSConfig};
 use diesel::Connection as _;
@@ -105,7 +105,7 @@ pub fn accept(
     let migrate = args.get(2).is_some();
 
     // (4) Reject if accepting node's account is banned
-    if user.jackass() {
+    if user.is_banned() {
         return ACCEPT_BANNED.into();
     }
 
@@ -139,6 +139,7 @@ pub fn accept(
 
     let old_account_id = user.account_id();
     let new_account_id = invitation.sender_account_id;
+    let inviter = users::get_by_account_id(conn, new_account_id).ok();
 
     // (6-10) Perform the acceptance in a transaction
     conn.transaction::<_, diesel::result::Error, _>(|conn| {
@@ -152,6 +153,8 @@ pub fn accept(
                 .expect("should be able to migrate posts");
             queued_messages::migrate_account(conn, old_account_id, new_account_id)
                 .expect("should be able to migrate queued messages");
+            ignores::migrate_account(conn, old_account_id, new_account_id)
+                .expect("should be able to migrate ignores");
             board_states::delete_for_account(conn, old_account_id)
                 .expect("should be able to delete board states");
             users::delete_account(conn, old_account_id)
@@ -166,6 +169,19 @@ pub fn accept(
         invitations::accept(conn, invitation)
             .expect("should be able to mark invitation as accepted");
 
+        let detail = format!(
+            "Moved from account #{old_account_id} to #{new_account_id}{}",
+            if migrate { " with migration" } else { "" }
+        );
+        audit::record(
+            conn,
+            user.node_id(),
+            "invite_accept",
+            inviter.as_ref().map(User::node_id),
+            &detail,
+        )
+        .expect("should be able to record the acceptance");
+
         Ok(())
     })
     .expect("accept transaction should succeed");
@@ -270,7 +286,7 @@ pub fn send(
     };
 
     // (5) Sender's account is not banned
-    if user.jackass() {
+    if user.is_banned() {
         return SENDER_BANNED.into();
     }
 
@@ -421,6 +437,10 @@ pub fn leave(
             // that were sent by this departing node's old account
             invitations::delete_pending_for_sender(conn, old_account_id);
 
+            let detail = format!("Moved from account #{old_account_id} to #{}", account.id);
+            audit::record(conn, user.node_id(), "invite_leave", None, &detail)
+                .expect("should be able to record the departure");
+
             Ok(account)
         })
         .expect("leave transaction should succeed");
@@ -493,6 +513,19 @@ pub fn remove(
         // Clean up: delete any pending inbound invitations for the removed node
         invitations::delete_pending_for_invitee(conn, target_user.node.id);
 
+        let detail = format!(
+            "Moved from account #{old_account_id} to #{}",
+            new_account.id
+        );
+        audit::record(
+            conn,
+            user.node_id(),
+            "invite_remove",
+            Some(&target_node_id),
+            &detail,
+        )
+        .expect("should be able to record the removal");
+
         Ok(())
     })
     .expect("remove transaction should succeed");
@@ -511,23 +544,6 @@ mod tests {
     use crate::db;
     use crate::db::users;
     use crate::BBSConfig;
-    use config::Map;
-
-    fn test_config() -> BBSConfig {
-        BBSConfig {
-            bbs_name: "Test BBS".to_string(),
-            my_id: "!00000001".to_string(),
-            db_path: ":memory:".to_string(),
-            serial_device: None,
-            tcp_address: None,
-            sysops: Vec::new(),
-            public_channel: 0,
-            ad_text: String::new(),
-            weather: None,
-            menus: Map::new(),
-            page_delay_ms: None,
-        }
-    }
 
     /// Create a user via the standard record + observe path, then optionally set invite_allowed.
     fn create_test_user(conn: &mut SqliteConnection, node_id: &str, invite_allowed: bool) -> User {
@@ -561,7 +577,7 @@ mod tests {
     #[test]
     fn test_block_sets_invite_allowed_false() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut user = create_test_user(&mut conn, "!aabb0001", true);
         assert!(user.account.invite_allowed);
 
@@ -576,7 +592,7 @@ mod tests {
     #[test]
     fn test_unblock_sets_invite_allowed_true() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut user = create_test_user(&mut conn, "!aabb0002", false);
         assert!(!user.account.invite_allowed);
 
@@ -591,7 +607,7 @@ mod tests {
     #[test]
     fn test_block_idempotent() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut user = create_test_user(&mut conn, "!aabb0003", false);
         assert!(!user.account.invite_allowed);
 
@@ -605,7 +621,7 @@ mod tests {
     #[test]
     fn test_unblock_idempotent() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut user = create_test_user(&mut conn, "!aabb0004", true);
         assert!(user.account.invite_allowed);
 
@@ -619,7 +635,7 @@ mod tests {
     #[test]
     fn test_block_unblock_toggle() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut user = create_test_user(&mut conn, "!aabb0005", false);
 
         let replies = unblock(&mut conn, &cfg, &mut user, vec!["invite unblock"]);
@@ -638,7 +654,7 @@ mod tests {
     #[test]
     fn test_block_is_account_wide_multi_node() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
 
         let mut user_a = create_test_user(&mut conn, "!aabb0006", true);
         let account_id = user_a.account_id();
@@ -670,7 +686,7 @@ mod tests {
         use crate::commands::ReplyDestination;
 
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut user = create_test_user(&mut conn, "!aabb0009", false);
 
         let replies = block(&mut conn, &cfg, &mut user, vec!["invite block"]);
@@ -740,7 +756,7 @@ mod tests {
     #[test]
     fn test_send_happy_path() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut sender = create_test_user(&mut conn, "!aa000001", false);
         let _target = create_test_user(&mut conn, "!aa000002", true); // unblocked
 
@@ -793,7 +809,7 @@ mod tests {
     #[test]
     fn test_send_rejected_unknown_node() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut sender = create_test_user(&mut conn, "!aa000010", false);
 
         let replies = send(
@@ -808,7 +824,7 @@ mod tests {
     #[test]
     fn test_send_rejected_invalid_node_id() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut sender = create_test_user(&mut conn, "!aa000011", false);
 
         let replies = send(
@@ -823,7 +839,7 @@ mod tests {
     #[test]
     fn test_send_rejected_target_blocks_invitations() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut sender = create_test_user(&mut conn, "!aa000020", false);
         let _target = create_test_user(&mut conn, "!aa000021", false); // blocked (default)
 
@@ -839,7 +855,7 @@ mod tests {
     #[test]
     fn test_send_rejected_target_multi_node_same_message() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut sender = create_test_user(&mut conn, "!aa000030", false);
         let target = create_test_user(&mut conn, "!aa000031", true);
 
@@ -859,7 +875,7 @@ mod tests {
     #[test]
     fn test_send_rejected_target_has_pending_inbound() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut sender_a = create_test_user(&mut conn, "!aa000040", false);
         let mut sender_b = create_test_user(&mut conn, "!aa000041", false);
         let _target = create_test_user(&mut conn, "!aa000042", true);
@@ -889,12 +905,12 @@ mod tests {
     #[test]
     fn test_send_rejected_sender_banned() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut sender = create_test_user(&mut conn, "!aa000050", false);
         let _target = create_test_user(&mut conn, "!aa000051", true);
 
         // Ban the sender
-        users::ban(&mut conn, &sender).expect("should ban");
+        users::ban(&mut conn, "admin", &sender, None).expect("should ban");
         sender.account.jackass = true;
 
         let replies = send(
@@ -909,7 +925,7 @@ mod tests {
     #[test]
     fn test_send_rejected_invite_self() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut sender = create_test_user(&mut conn, "!aa000060", true); // unblock own invitations
 
         let replies = send(
@@ -924,7 +940,7 @@ mod tests {
     #[test]
     fn test_send_rejected_inflight_outbound() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut sender = create_test_user(&mut conn, "!aa000070", false);
         let _target_a = create_test_user(&mut conn, "!aa000071", true);
         let _target_b = create_test_user(&mut conn, "!aa000072", true);
@@ -951,7 +967,7 @@ mod tests {
     #[test]
     fn test_send_rate_limited_after_denial() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut sender = create_test_user(&mut conn, "!aa000080", false);
         let target_a = create_test_user(&mut conn, "!aa000081", true);
         let _target_b = create_test_user(&mut conn, "!aa000082", true);
@@ -987,7 +1003,7 @@ mod tests {
     #[test]
     fn test_send_rate_limited_shows_time_remaining() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut sender = create_test_user(&mut conn, "!aa000090", false);
         let target_a = create_test_user(&mut conn, "!aa000091", true);
         let _target_b = create_test_user(&mut conn, "!aa000092", true);
@@ -1027,7 +1043,7 @@ mod tests {
     #[test]
     fn test_send_rate_limit_resets_on_acceptance() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut sender = create_test_user(&mut conn, "!aa000100", false);
         let target_a = create_test_user(&mut conn, "!aa000101", true);
         let _target_b = create_test_user(&mut conn, "!aa000102", true);
@@ -1064,7 +1080,7 @@ mod tests {
     #[test]
     fn test_send_allowed_after_cooldown_expires() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut sender = create_test_user(&mut conn, "!aa000110", false);
         let target_a = create_test_user(&mut conn, "!aa000111", true);
         let _target_b = create_test_user(&mut conn, "!aa000112", true);
@@ -1100,7 +1116,7 @@ mod tests {
     #[test]
     fn test_send_dm_does_not_contain_password() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut sender = create_test_user(&mut conn, "!aa000120", false);
         let _target = create_test_user(&mut conn, "!aa000121", true);
 
@@ -1130,7 +1146,7 @@ mod tests {
     #[test]
     fn test_send_dm_contains_sender_node_list() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut sender = create_test_user(&mut conn, "!aa000130", false);
         let _target = create_test_user(&mut conn, "!aa000131", true);
 
@@ -1157,7 +1173,7 @@ mod tests {
     #[test]
     fn test_deny_success() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut sender = create_test_user(&mut conn, "!bb000001", false);
         let mut target = create_test_user(&mut conn, "!bb000002", true);
 
@@ -1191,7 +1207,7 @@ mod tests {
     #[test]
     fn test_deny_no_pending_invitation() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut user = create_test_user(&mut conn, "!bb000010", false);
 
         let replies = deny(&mut conn, &cfg, &mut user, vec!["invite deny"]);
@@ -1203,7 +1219,7 @@ mod tests {
     #[test]
     fn test_pending_no_invitations() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut user = create_test_user(&mut conn, "!cc000001", false);
 
         let replies = pending(&mut conn, &cfg, &mut user, vec!["invite pending"]);
@@ -1213,7 +1229,7 @@ mod tests {
     #[test]
     fn test_pending_shows_outbound() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut sender = create_test_user(&mut conn, "!cc000010", false);
         let _target = create_test_user(&mut conn, "!cc000011", true);
 
@@ -1249,7 +1265,7 @@ mod tests {
     #[test]
     fn test_pending_shows_inbound() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut sender = create_test_user(&mut conn, "!cc000020", false);
         let mut target = create_test_user(&mut conn, "!cc000021", true);
 
@@ -1290,7 +1306,7 @@ mod tests {
     #[test]
     fn test_pending_shows_both_outbound_and_inbound() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut user_a = create_test_user(&mut conn, "!cc000030", true);
         let mut user_b = create_test_user(&mut conn, "!cc000031", true);
 
@@ -1331,7 +1347,7 @@ mod tests {
     #[test]
     fn test_pending_excludes_expired() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut sender = create_test_user(&mut conn, "!cc000040", false);
         let target = create_test_user(&mut conn, "!cc000041", true);
 
@@ -1354,7 +1370,7 @@ mod tests {
     #[test]
     fn test_pending_expired_not_shown_for_invitee() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let sender = create_test_user(&mut conn, "!cc000050", false);
         let mut target = create_test_user(&mut conn, "!cc000051", true);
 
@@ -1399,7 +1415,7 @@ mod tests {
     #[test]
     fn test_accept_success_without_migrate() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut sender = create_test_user(&mut conn, "!dd000001", false);
         let mut target = create_test_user(&mut conn, "!dd000002", true);
 
@@ -1452,7 +1468,7 @@ mod tests {
     #[test]
     fn test_accept_success_with_migrate() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut sender = create_test_user(&mut conn, "!dd000010", false);
         let mut target = create_test_user(&mut conn, "!dd000011", true);
 
@@ -1512,7 +1528,7 @@ mod tests {
     #[test]
     fn test_accept_wrong_password() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut sender = create_test_user(&mut conn, "!dd000020", false);
         let mut target = create_test_user(&mut conn, "!dd000021", true);
 
@@ -1536,7 +1552,7 @@ mod tests {
     #[test]
     fn test_accept_expired_invitation() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let sender = create_test_user(&mut conn, "!dd000030", false);
         let mut target = create_test_user(&mut conn, "!dd000031", true);
 
@@ -1564,7 +1580,7 @@ mod tests {
     #[test]
     fn test_accept_no_pending_invitation() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut user = create_test_user(&mut conn, "!dd000040", false);
 
         let replies = accept(
@@ -1580,7 +1596,7 @@ mod tests {
     fn test_accept_expired_returns_expiry_not_generic_message() {
         // Verify that expired invitations return a distinct message from "no invitation exists"
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let sender = create_test_user(&mut conn, "!dd000041", false);
         let mut target = create_test_user(&mut conn, "!dd000042", true);
         let mut no_invite_user = create_test_user(&mut conn, "!dd000043", false);
@@ -1624,7 +1640,7 @@ mod tests {
     #[test]
     fn test_accept_non_target_node_rejected() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut sender = create_test_user(&mut conn, "!dd000050", false);
         let _target = create_test_user(&mut conn, "!dd000051", true);
         let mut interloper = create_test_user(&mut conn, "!dd000052", false);
@@ -1645,14 +1661,14 @@ mod tests {
     #[test]
     fn test_accept_banned_node_rejected() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut sender = create_test_user(&mut conn, "!dd000060", false);
         let mut target = create_test_user(&mut conn, "!dd000061", true);
 
         let password = send_invitation(&mut conn, &cfg, &mut sender, "!dd000061");
 
         // Ban the target
-        users::ban(&mut conn, &target).expect("should ban");
+        users::ban(&mut conn, "admin", &target, None).expect("should ban");
         target.account.jackass = true;
 
         let replies = accept(
@@ -1667,7 +1683,7 @@ mod tests {
     #[test]
     fn test_accept_cleans_up_outbound_invitation() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut sender = create_test_user(&mut conn, "!dd000070", false);
         let mut target = create_test_user(&mut conn, "!dd000071", true);
         let _other = create_test_user(&mut conn, "!dd000072", true);
@@ -1710,7 +1726,7 @@ mod tests {
     fn test_accept_rate_limit_reset_for_sender() {
         // VAL-SEND-006: After acceptance, sender can immediately send another invitation
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut sender = create_test_user(&mut conn, "!dd000080", false);
         let mut target = create_test_user(&mut conn, "!dd000081", true);
         let _next_target = create_test_user(&mut conn, "!dd000082", true);
@@ -1744,7 +1760,7 @@ mod tests {
     #[test]
     fn test_accept_both_nodes_on_same_account() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut sender = create_test_user(&mut conn, "!dd000090", false);
         let mut target = create_test_user(&mut conn, "!dd000091", true);
 
@@ -1912,7 +1928,7 @@ mod tests {
     #[test]
     fn test_help_lists_all_subcommands() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut user = create_test_user(&mut conn, "!ff000001", false);
 
         let replies = help(&mut conn, &cfg, &mut user, vec!["invite"]);
@@ -1953,7 +1969,7 @@ mod tests {
     #[test]
     fn test_help_includes_header() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut user = create_test_user(&mut conn, "!ff000002", false);
 
         let replies = help(&mut conn, &cfg, &mut user, vec!["invite"]);
@@ -1969,7 +1985,7 @@ mod tests {
     #[test]
     fn test_help_includes_leave() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut user = create_test_user(&mut conn, "!ff000003", false);
 
         let replies = help(&mut conn, &cfg, &mut user, vec!["invite"]);
@@ -1987,7 +2003,7 @@ mod tests {
     #[test]
     fn test_leave_from_two_node_account() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut user_a = create_test_user(&mut conn, "!ab000001", false);
         let mut user_b = create_test_user(&mut conn, "!ab000002", true);
 
@@ -2042,7 +2058,7 @@ mod tests {
     #[test]
     fn test_leave_from_three_node_account() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut user_a = create_test_user(&mut conn, "!ab000010", false);
         let mut user_b = create_test_user(&mut conn, "!ab000011", true);
         let mut user_c = create_test_user(&mut conn, "!ab000012", true);
@@ -2077,7 +2093,7 @@ mod tests {
     #[test]
     fn test_leave_from_single_node_account_error() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut user = create_test_user(&mut conn, "!ab000020", false);
 
         let replies = leave(&mut conn, &cfg, &mut user, vec!["invite leave"]);
@@ -2087,7 +2103,7 @@ mod tests {
     #[test]
     fn test_leave_new_account_defaults() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut user_a = create_test_user(&mut conn, "!ab000030", false);
         let mut user_b = create_test_user(&mut conn, "!ab000031", true);
 
@@ -2131,7 +2147,7 @@ mod tests {
     #[test]
     fn test_leave_old_posts_unchanged() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut user_a = create_test_user(&mut conn, "!ab000040", false);
         let mut user_b = create_test_user(&mut conn, "!ab000041", true);
 
@@ -2172,7 +2188,7 @@ mod tests {
     #[test]
     fn test_leave_cleans_up_outbound_invitation() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut user_a = create_test_user(&mut conn, "!ab000050", false);
         let mut user_b = create_test_user(&mut conn, "!ab000051", true);
         let _user_c = create_test_user(&mut conn, "!ab000052", true);
@@ -2211,7 +2227,7 @@ mod tests {
     #[test]
     fn test_leave_and_rejoin_cycle() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut user_a = create_test_user(&mut conn, "!ab000060", false);
         let mut user_b = create_test_user(&mut conn, "!ab000061", true);
 
@@ -2261,7 +2277,7 @@ mod tests {
     #[test]
     fn test_remove_success() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut user_a = create_test_user(&mut conn, "!ac000001", false);
         let mut user_b = create_test_user(&mut conn, "!ac000002", true);
 
@@ -2318,10 +2334,57 @@ mod tests {
         assert_eq!(remaining_nodes[0].node_id, "!ac000001");
     }
 
+    #[test]
+    fn test_accept_leave_and_remove_are_audited() {
+        let mut conn = db::test_connection();
+        let cfg = BBSConfig::for_tests();
+        let mut user_a = create_test_user(&mut conn, "!ad000001", false);
+        let mut user_b = create_test_user(&mut conn, "!ad000002", true);
+        let mut user_c = create_test_user(&mut conn, "!ad000003", true);
+
+        let password = send_invitation(&mut conn, &cfg, &mut user_a, "!ad000002");
+        let _r = accept(
+            &mut conn,
+            &cfg,
+            &mut user_b,
+            vec!["invite accept", &password],
+        );
+        let password = send_invitation(&mut conn, &cfg, &mut user_a, "!ad000003");
+        let _r = accept(
+            &mut conn,
+            &cfg,
+            &mut user_c,
+            vec!["invite accept", &password],
+        );
+        let _r = leave(&mut conn, &cfg, &mut user_b, vec!["invite leave"]);
+        let mut user_a = users::get(&mut conn, "!ad000001").expect("user_a");
+        let _r = remove(
+            &mut conn,
+            &cfg,
+            &mut user_a,
+            vec!["invite remove", "!ad000003"],
+        );
+
+        let entries = audit::list(&mut conn, None, None, None, None);
+        let summary: Vec<(&str, &str, Option<&str>)> = entries
+            .iter()
+            .map(|e| (e.actor.as_str(), e.action.as_str(), e.target.as_deref()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("!ad000002", "invite_accept", Some("!ad000001")),
+                ("!ad000003", "invite_accept", Some("!ad000001")),
+                ("!ad000002", "invite_leave", None),
+                ("!ad000001", "invite_remove", Some("!ad000003")),
+            ]
+        );
+    }
+
     #[test]
     fn test_remove_from_three_node_account() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut user_a = create_test_user(&mut conn, "!ac000010", false);
         let mut user_b = create_test_user(&mut conn, "!ac000011", true);
         let mut user_c = create_test_user(&mut conn, "!ac000012", true);
@@ -2364,7 +2427,7 @@ mod tests {
     #[test]
     fn test_remove_nonexistent_node() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut user = create_test_user(&mut conn, "!ac000020", false);
 
         let replies = remove(
@@ -2379,7 +2442,7 @@ mod tests {
     #[test]
     fn test_remove_node_not_in_account() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut user_a = create_test_user(&mut conn, "!ac000030", false);
         let _user_b = create_test_user(&mut conn, "!ac000031", false);
 
@@ -2396,7 +2459,7 @@ mod tests {
     #[test]
     fn test_remove_self_error() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut user = create_test_user(&mut conn, "!ac000040", false);
 
         let replies = remove(
@@ -2417,7 +2480,7 @@ mod tests {
     #[test]
     fn test_remove_new_account_has_clean_defaults() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut user_a = create_test_user(&mut conn, "!ac000050", false);
         let mut user_b = create_test_user(&mut conn, "!ac000051", true);
 
@@ -2466,7 +2529,7 @@ mod tests {
     #[test]
     fn test_remove_posts_stay_with_original_account() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut user_a = create_test_user(&mut conn, "!ac000060", false);
         let mut user_b = create_test_user(&mut conn, "!ac000061", true);
 
@@ -2512,7 +2575,7 @@ mod tests {
     #[test]
     fn test_remove_cleans_up_outbound_invitation() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut user_a = create_test_user(&mut conn, "!ac000070", false);
         let mut user_b = create_test_user(&mut conn, "!ac000071", true);
         let _user_c = create_test_user(&mut conn, "!ac000072", true);
@@ -2556,7 +2619,7 @@ mod tests {
     #[test]
     fn test_remove_cleans_up_inbound_invitation() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut user_a = create_test_user(&mut conn, "!ac000080", false);
         let mut user_b = create_test_user(&mut conn, "!ac000081", true);
         let user_d = create_test_user(&mut conn, "!ac000083", false);
@@ -2612,7 +2675,7 @@ mod tests {
     #[test]
     fn test_remove_invalid_node_id() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut user = create_test_user(&mut conn, "!ac000090", false);
 
         let replies = remove(
@@ -2627,7 +2690,7 @@ mod tests {
     #[test]
     fn test_remove_no_args() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut user = create_test_user(&mut conn, "!ac000100", false);
 
         let replies = remove(&mut conn, &cfg, &mut user, vec!["invite remove"]);
@@ -2638,7 +2701,7 @@ mod tests {
     #[test]
     fn test_help_includes_remove() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut user = create_test_user(&mut conn, "!ac000110", false);
 
         let replies = help(&mut conn, &cfg, &mut user, vec!["invite"]);
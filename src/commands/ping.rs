@@ -47,7 +47,6 @@ mod tests {
     use super::{ping, pong_with_case};
     use crate::db::{Account, Node, User};
     use crate::BBSConfig;
-    use config::Map;
 
     fn dummy_user() -> User {
         // Minimal user; fields not relevant to ping behaviour.
@@ -61,6 +60,11 @@ mod tests {
                 last_acted_at_us: None,
                 in_board: None,
                 invite_allowed: false,
+                banned_until_us: None,
+                search_post_ids: None,
+                digest_enabled: true,
+                dm_receipts: false,
+                skip_ignored_posts: false,
             },
             node: Node {
                 id: 1,
@@ -92,19 +96,7 @@ mod tests {
     #[test]
     fn ping_uses_full_command_line_and_preserves_case() {
         let mut user = dummy_user();
-        let cfg = BBSConfig {
-            bbs_name: "Test BBS".to_string(),
-            my_id: "!00000001".to_string(),
-            db_path: ":memory:".to_string(),
-            serial_device: None,
-            tcp_address: None,
-            sysops: Vec::new(),
-            public_channel: 0,
-            ad_text: String::new(),
-            weather: None,
-            menus: Map::new(),
-            page_delay_ms: None,
-        };
+        let cfg = BBSConfig::for_tests();
         let mut conn = crate::db::test_connection();
 
         // Simulate the dispatcher passing argv[0] as the trimmed command line.
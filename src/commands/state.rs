@@ -1,9 +1,10 @@
 use super::Replies;
-use crate::db::{boards, users, User};
+use crate::db::{boards, more_pages, users, User};
 use crate::{linefeed, system_info, BBSConfig};
 use diesel::SqliteConnection;
 
 const INVALID_BOARD: &str = "That's not a valid board number.";
+const NOTHING_MORE: &str = "There's nothing more to send.";
 
 /// Tell the user where they are.
 pub fn describe(
@@ -47,30 +48,30 @@ pub fn describe(
     out.into()
 }
 
+/// Send the next page of the user's last long reply.
+pub fn more(
+    conn: &mut SqliteConnection,
+    cfg: &BBSConfig,
+    user: &mut User,
+    _args: Vec<&str>,
+) -> Replies {
+    let Some(more) = &cfg.more else {
+        return NOTHING_MORE.into();
+    };
+    match more_pages::take(conn, user.account_id(), more.expire_us()) {
+        Some(page) => page.into(),
+        None => NOTHING_MORE.into(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::db;
     use crate::db::users;
-    use config::Map;
+    use crate::MoreConfig;
     use diesel::connection::SimpleConnection;
 
-    fn test_config() -> BBSConfig {
-        BBSConfig {
-            bbs_name: "Test BBS".to_string(),
-            my_id: "!00000001".to_string(),
-            db_path: ":memory:".to_string(),
-            serial_device: None,
-            tcp_address: None,
-            sysops: Vec::new(),
-            public_channel: 0,
-            ad_text: String::new(),
-            weather: None,
-            menus: Map::new(),
-            page_delay_ms: None,
-        }
-    }
-
     fn get_full_text(replies: &Replies) -> String {
         replies.0[0].out.join("\n")
     }
@@ -78,7 +79,7 @@ mod tests {
     #[test]
     fn test_describe_shows_invite_blocked_by_default() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let (mut user, _) = users::record(&mut conn, "!aabb0001").expect("should create user");
 
         let replies = describe(&mut conn, &cfg, &mut user, vec!["?"]);
@@ -93,7 +94,7 @@ mod tests {
     #[test]
     fn test_describe_shows_invite_open_when_allowed() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let (mut user, _) = users::record(&mut conn, "!aabb0002").expect("should create user");
         user = users::update_invite_allowed(&mut conn, &user, true)
             .expect("should update invite_allowed");
@@ -110,7 +111,7 @@ mod tests {
     #[test]
     fn test_describe_single_node_no_node_list() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let (mut user, _) = users::record(&mut conn, "!aabb0003").expect("should create user");
 
         let replies = describe(&mut conn, &cfg, &mut user, vec!["?"]);
@@ -125,7 +126,7 @@ mod tests {
     #[test]
     fn test_describe_multi_node_shows_node_list() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let (mut user, _) = users::record(&mut conn, "!aabb0004").expect("should create user");
         let account_id = user.account_id();
 
@@ -165,7 +166,7 @@ mod tests {
     #[test]
     fn test_describe_includes_greeting_and_help() {
         let mut conn = db::test_connection();
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let (mut user, _) = users::record(&mut conn, "!aabb0006").expect("should create user");
 
         let replies = describe(&mut conn, &cfg, &mut user, vec!["?"]);
@@ -177,4 +178,22 @@ mod tests {
             text
         );
     }
+
+    #[test]
+    fn more_sends_the_next_page() {
+        let mut conn = db::test_connection();
+        let mut cfg = BBSConfig::for_tests();
+        let (mut user, _) = users::record(&mut conn, "!aabb0007").expect("should create user");
+        more_pages::replace(&mut conn, user.account_id(), &["Page 2/2".to_string()]);
+
+        // Without the mode turned on, there's never anything more.
+        let replies = more(&mut conn, &cfg, &mut user, vec!["more"]);
+        assert_eq!(get_full_text(&replies), NOTHING_MORE);
+
+        cfg.more = Some(MoreConfig::default());
+        let replies = more(&mut conn, &cfg, &mut user, vec!["more"]);
+        assert_eq!(get_full_text(&replies), "Page 2/2");
+        let replies = more(&mut conn, &cfg, &mut user, vec!["more"]);
+        assert_eq!(get_full_text(&replies), NOTHING_MORE);
+    }
 }
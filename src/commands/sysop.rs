@@ -1,8 +1,26 @@
-use super::{Replies, Reply, ReplyDestination};
-use crate::db::User;
-use crate::{system_info, BBSConfig};
+use super::{find_user, is_sysop, Replies, Reply, ReplyDestination};
+use crate::db::{
+    airtime as db_airtime, board_states, now_as_useconds, posts, users, Account, User,
+};
+use crate::{advertisement, linefeed, BBSConfig};
 use diesel::SqliteConnection;
 
+const NOT_IN_BOARD: &str = "You are not in a board.";
+const NO_SUCH_POST: &str = "There is no post here.";
+const MISSING_USER: &str = "Please say which user.";
+const WILL_NOT_BAN_SYSOP: &str = "You can't ban a sysop.";
+/// The longest temporary ban a sysop can hand out over the radio, in hours.
+const MAX_BAN_HOURS: i64 = 24 * 365;
+
+/// Describe how long an account is banned for.
+fn ban_length(account: &Account) -> String {
+    if account.banned_until_us.is_some() {
+        format!("Banned until {}", account.banned_until())
+    } else {
+        "Banned indefinitely".to_string()
+    }
+}
+
 /// Send a BBS advertisement to the main channel.
 pub fn advertise(
     _conn: &mut SqliteConnection,
@@ -12,7 +30,7 @@ pub fn advertise(
 ) -> Replies {
     Replies(vec![
         Reply {
-            out: vec![cfg.ad_text.clone(), String::new(), system_info(cfg)],
+            out: advertisement(cfg),
             destination: ReplyDestination::Broadcast,
         },
         Reply {
@@ -21,3 +39,270 @@ pub fn advertise(
         },
     ])
 }
+
+/// Show how much of the airtime budget the BBS has used.
+pub fn airtime(
+    conn: &mut SqliteConnection,
+    cfg: &BBSConfig,
+    _user: &mut User,
+    _args: Vec<&str>,
+) -> Replies {
+    let Some(budget) = &cfg.airtime else {
+        let used_us = db_airtime::used(conn, 3_600_000_000);
+        return format!(
+            "Airtime in the last hour: {:.1}s. No budget is configured.",
+            used_us as f64 / 1_000_000.0
+        )
+        .into();
+    };
+    let used_us = db_airtime::used(conn, budget.window_us());
+    format!(
+        "Airtime in the last {} minutes: {:.1}s of {:.1}s allowed ({:.0}% of budget).",
+        budget.window_minutes,
+        used_us as f64 / 1_000_000.0,
+        budget.allowed_us() as f64 / 1_000_000.0,
+        used_us as f64 * 100.0 / budget.allowed_us().max(1) as f64
+    )
+    .into()
+}
+
+/// Delete the post the sysop is currently reading.
+pub fn delete_post(
+    conn: &mut SqliteConnection,
+    _cfg: &BBSConfig,
+    user: &mut User,
+    _args: Vec<&str>,
+) -> Replies {
+    let Some(in_board) = user.in_board() else {
+        return NOT_IN_BOARD.into();
+    };
+    let last_seen = board_states::get(conn, user.account_id(), in_board);
+    let Ok((post, author)) = posts::at(conn, in_board, last_seen) else {
+        return NO_SUCH_POST.into();
+    };
+    if posts::delete(conn, user.node_id(), post.id).is_err() {
+        return "Unable to delete this post.".into();
+    }
+    format!("Deleted post #{} by {}.", post.id, author).into()
+}
+
+/// Ban a user, optionally for a number of hours.
+#[allow(clippy::needless_pass_by_value)]
+pub fn ban(
+    conn: &mut SqliteConnection,
+    cfg: &BBSConfig,
+    user: &mut User,
+    args: Vec<&str>,
+) -> Replies {
+    let Some(who) = args.get(1) else {
+        return MISSING_USER.into();
+    };
+    let target = match find_user(conn, who) {
+        Ok(x) => x,
+        Err(err) => return err.into(),
+    };
+    if is_sysop(cfg, &target) {
+        return WILL_NOT_BAN_SYSOP.into();
+    }
+    let until = match args.get(2).map(|hours| hours.parse::<i64>()) {
+        None => None,
+        Some(Ok(hours)) if (1..=MAX_BAN_HOURS).contains(&hours) => {
+            Some(now_as_useconds() + hours * 3_600_000_000)
+        }
+        Some(_) => return format!("Ban for 1 to {MAX_BAN_HOURS} hours.").into(),
+    };
+    let Ok(target) = users::ban(conn, user.node_id(), &target, until) else {
+        return "Unable to ban that user.".into();
+    };
+    format!("{}: {target}", ban_length(&target.account)).into()
+}
+
+/// Lift a user's ban.
+#[allow(clippy::needless_pass_by_value)]
+pub fn unban(
+    conn: &mut SqliteConnection,
+    _cfg: &BBSConfig,
+    user: &mut User,
+    args: Vec<&str>,
+) -> Replies {
+    let Some(who) = args.get(1) else {
+        return MISSING_USER.into();
+    };
+    let target = match find_user(conn, who) {
+        Ok(x) => x,
+        Err(err) => return err.into(),
+    };
+    let Ok(target) = users::unban(conn, user.node_id(), &target) else {
+        return "Unable to unban that user.".into();
+    };
+    format!("Unbanned {target}").into()
+}
+
+/// Show everything we know about a user.
+#[allow(clippy::needless_pass_by_value)]
+pub fn user_info(
+    conn: &mut SqliteConnection,
+    _cfg: &BBSConfig,
+    _user: &mut User,
+    args: Vec<&str>,
+) -> Replies {
+    let Some(who) = args.get(1) else {
+        return MISSING_USER.into();
+    };
+    let target = match find_user(conn, who) {
+        Ok(x) => x,
+        Err(err) => return err.into(),
+    };
+    let mut out = vec![
+        format!("User: {}", target.display_name()),
+        format!("Account #{}", target.account_id()),
+        format!("Created: {}", target.account.created_at()),
+        format!("Last acted: {}", target.account.last_acted_at()),
+        format!(
+            "Posts: {}",
+            posts::count_by_account(conn, target.account_id())
+        ),
+    ];
+    if target.is_banned() {
+        out.push(ban_length(&target.account));
+    }
+    linefeed!(out);
+    out.push("Nodes:".to_string());
+    for node in users::get_nodes_for_account(conn, target.account_id()) {
+        out.push(format!("{node}, seen {}", node.last_seen_at()));
+    }
+    out.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{self, audit, boards};
+
+    fn test_config() -> BBSConfig {
+        BBSConfig {
+            sysops: vec!["!50000001".to_string()],
+            ..BBSConfig::for_tests()
+        }
+    }
+
+    fn audit_actions(conn: &mut SqliteConnection) -> Vec<String> {
+        audit::list(conn, None, None, None, None)
+            .into_iter()
+            .map(|entry| entry.action)
+            .collect()
+    }
+
+    #[test]
+    fn delete_post_removes_the_current_post() {
+        let mut conn = db::test_connection();
+        let cfg = test_config();
+        let board = boards::add(&mut conn, "General", "General discussion").expect("board");
+        let (spammer, _) = users::record(&mut conn, "!50000002").expect("spammer");
+        let post =
+            posts::add(&mut conn, spammer.account_id(), board.id, "buy stuff").expect("post");
+        let (sysop, _) = users::record(&mut conn, "!50000001").expect("sysop");
+        let mut sysop = users::enter_board(&mut conn, &sysop, board.id).expect("enter");
+        board_states::update(&mut conn, sysop.account_id(), board.id, post.created_at_us);
+
+        let replies = delete_post(&mut conn, &cfg, &mut sysop, vec!["!d"]);
+        assert!(replies.0[0].out[0].starts_with("Deleted post #"));
+        assert!(posts::get(&mut conn, post.id).is_err());
+        let replies = delete_post(&mut conn, &cfg, &mut sysop, vec!["!d"]);
+        assert_eq!(replies.0[0].out, vec![NO_SUCH_POST.to_string()]);
+        assert_eq!(audit_actions(&mut conn), vec!["post_delete"]);
+    }
+
+    #[test]
+    fn ban_and_unban_by_node_id() {
+        let mut conn = db::test_connection();
+        let cfg = test_config();
+        let (mut sysop, _) = users::record(&mut conn, "!50000001").expect("sysop");
+        users::record(&mut conn, "!50000003").expect("user");
+
+        ban(
+            &mut conn,
+            &cfg,
+            &mut sysop,
+            vec!["!ban 50000003 2", "50000003", "2"],
+        );
+        let target = users::get(&mut conn, "!50000003").expect("user");
+        assert!(target.is_banned());
+        assert!(target.account.banned_until_us.is_some());
+
+        unban(
+            &mut conn,
+            &cfg,
+            &mut sysop,
+            vec!["!unban !50000003", "!50000003"],
+        );
+        let target = users::get(&mut conn, "!50000003").expect("user");
+        assert!(!target.is_banned());
+        assert_eq!(audit_actions(&mut conn), vec!["ban", "unban"]);
+    }
+
+    #[test]
+    fn ban_refuses_sysops_and_bad_durations() {
+        let mut conn = db::test_connection();
+        let cfg = test_config();
+        let (mut sysop, _) = users::record(&mut conn, "!50000001").expect("sysop");
+        users::record(&mut conn, "!50000004").expect("user");
+
+        let replies = ban(
+            &mut conn,
+            &cfg,
+            &mut sysop,
+            vec!["!ban 50000001", "50000001"],
+        );
+        assert_eq!(replies.0[0].out, vec![WILL_NOT_BAN_SYSOP.to_string()]);
+        let replies = ban(
+            &mut conn,
+            &cfg,
+            &mut sysop,
+            vec!["!ban 50000004 0", "50000004", "0"],
+        );
+        assert!(replies.0[0].out[0].starts_with("Ban for 1 to "));
+        assert!(!users::get(&mut conn, "!50000004")
+            .expect("user")
+            .is_banned());
+        assert!(audit_actions(&mut conn).is_empty());
+    }
+
+    #[test]
+    fn user_info_shows_nodes_and_post_count() {
+        let mut conn = db::test_connection();
+        let cfg = test_config();
+        let board = boards::add(&mut conn, "General", "General discussion").expect("board");
+        let (mut sysop, _) = users::record(&mut conn, "!50000001").expect("sysop");
+        let (user, _) = users::record(&mut conn, "!50000005").expect("user");
+        posts::add(&mut conn, user.account_id(), board.id, "hello").expect("post");
+
+        let text = user_info(&mut conn, &cfg, &mut sysop, vec!["!u 50000005", "50000005"]).0[0]
+            .out
+            .join("\n");
+        assert!(text.contains("Posts: 1"), "got: {text}");
+        assert!(text.contains("!50000005"), "got: {text}");
+        assert!(audit_actions(&mut conn).is_empty());
+    }
+
+    #[test]
+    fn airtime_reports_budget_use() {
+        let mut conn = db::test_connection();
+        let mut cfg = test_config();
+        let (mut sysop, _) = users::record(&mut conn, "!50000001").expect("sysop");
+        db_airtime::record(&mut conn, 1_800_000);
+        let text = airtime(&mut conn, &cfg, &mut sysop, vec!["!air"]).0[0].out[0].clone();
+        assert!(text.ends_with("No budget is configured."), "got: {text}");
+
+        cfg.airtime = Some(crate::airtime::AirtimeConfig {
+            modem_preset: crate::airtime::ModemPreset::LongFast,
+            duty_cycle_percent: 1.0,
+            window_minutes: 60,
+        });
+        let text = airtime(&mut conn, &cfg, &mut sysop, vec!["!air"]).0[0].out[0].clone();
+        assert_eq!(
+            text,
+            "Airtime in the last 60 minutes: 1.8s of 36.0s allowed (5% of budget)."
+        );
+    }
+}
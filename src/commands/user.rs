@@ -1,11 +1,12 @@
-use super::Replies;
-use crate::db::{users, User};
+use super::{find_user, is_sysop, Replies};
+use crate::db::{board_states, boards, ignores, invitations, posts, queued_messages, users, User};
 use crate::{linefeed, BBSConfig};
 use diesel::SqliteConnection;
 
 const NO_BIO: &str = "You haven't set a bio.";
 const MISSING_BIO: &str = "Unable to find the bio.";
 const MISSING_NAME: &str = "Please provide a username.";
+const MISSING_USER: &str = "Please say which user.";
 
 /// Show the most recently active users.
 pub fn active(
@@ -108,3 +109,173 @@ pub fn name_clear(
     let _ = users::update_username(conn, user, None);
     format!("Your name is now: {}", user.long_name()).into()
 }
+
+/// Summarize what's new for the user: unread posts in each board, and waiting messages and
+/// invitations. Returns nothing if there's nothing new.
+pub fn unread_digest(
+    conn: &mut SqliteConnection,
+    cfg: &BBSConfig,
+    user: &User,
+) -> Option<Vec<String>> {
+    let mut lines = Vec::new();
+    let sysop = is_sysop(cfg, user);
+    for board in boards::all(conn) {
+        if !board.readable_by(sysop) {
+            continue;
+        }
+        let last_seen = board_states::get(conn, user.account_id(), board.id);
+        let unread = posts::count_after(conn, board.id, last_seen);
+        if unread > 0 {
+            lines.push(format!("B{} {}: {unread} new", board.id, board.name));
+        }
+    }
+    let messages = queued_messages::get(conn, user).len();
+    if messages > 0 {
+        lines.push(format!("{messages} DM(s) waiting"));
+    }
+    let invitations = invitations::get_pending_for_invitee(conn, user.node.id).len();
+    if invitations > 0 {
+        lines.push(format!("{invitations} invitation(s) pending"));
+    }
+    if lines.is_empty() {
+        return None;
+    }
+
+    let mut out = vec!["Since you were last here:".to_string()];
+    linefeed!(out);
+    out.extend(lines);
+    linefeed!(out);
+    out.push("Send DIGEST OFF to stop these.".to_string());
+    Some(out)
+}
+
+/// Show the user's unread digest right now.
+pub fn digest(
+    conn: &mut SqliteConnection,
+    cfg: &BBSConfig,
+    user: &mut User,
+    _args: Vec<&str>,
+) -> Replies {
+    match unread_digest(conn, cfg, user) {
+        Some(out) => out.into(),
+        None => "Nothing new since you were last here.".into(),
+    }
+}
+
+/// Send the user a digest when they come back after being away.
+pub fn digest_on(
+    conn: &mut SqliteConnection,
+    _cfg: &BBSConfig,
+    user: &mut User,
+    _args: Vec<&str>,
+) -> Replies {
+    let _ = users::set_digest_enabled(conn, user, true);
+    "You'll get a digest when you come back after being away.".into()
+}
+
+/// Stop sending the user digests.
+pub fn digest_off(
+    conn: &mut SqliteConnection,
+    _cfg: &BBSConfig,
+    user: &mut User,
+    _args: Vec<&str>,
+) -> Replies {
+    let _ = users::set_digest_enabled(conn, user, false);
+    "You won't get digests anymore.".into()
+}
+
+/// Stop seeing messages from another user.
+#[allow(clippy::needless_pass_by_value)]
+pub fn ignore(
+    conn: &mut SqliteConnection,
+    _cfg: &BBSConfig,
+    user: &mut User,
+    args: Vec<&str>,
+) -> Replies {
+    let Some(who) = args.get(1) else {
+        return MISSING_USER.into();
+    };
+    let target = match find_user(conn, who) {
+        Ok(x) => x,
+        Err(err) => return err.into(),
+    };
+    if target.account_id() == user.account_id() {
+        return "You can't ignore yourself.".into();
+    }
+    if ignores::add(conn, user, &target).is_err() {
+        return "Unable to ignore that user.".into();
+    }
+    format!("Ignoring {target}").into()
+}
+
+/// Start seeing messages from an ignored user again.
+#[allow(clippy::needless_pass_by_value)]
+pub fn unignore(
+    conn: &mut SqliteConnection,
+    _cfg: &BBSConfig,
+    user: &mut User,
+    args: Vec<&str>,
+) -> Replies {
+    let Some(who) = args.get(1) else {
+        return MISSING_USER.into();
+    };
+    let target = match find_user(conn, who) {
+        Ok(x) => x,
+        Err(err) => return err.into(),
+    };
+    match ignores::remove(conn, user, &target) {
+        Ok(true) => format!("No longer ignoring {target}").into(),
+        Ok(false) => format!("You weren't ignoring {target}").into(),
+        Err(_) => "Unable to unignore that user.".into(),
+    }
+}
+
+/// Show who the user is ignoring.
+pub fn ignore_list(
+    conn: &mut SqliteConnection,
+    _cfg: &BBSConfig,
+    user: &mut User,
+    _args: Vec<&str>,
+) -> Replies {
+    let ignored = ignores::list(conn, user);
+    if ignored.is_empty() {
+        return "You aren't ignoring anyone.".into();
+    }
+    let mut out = vec!["Ignoring:".to_string()];
+    linefeed!(out);
+    for target in ignored {
+        out.push(format!("{}: {target}", target.node_id()));
+    }
+    linefeed!(out);
+    out.push(format!(
+        "Their posts are {} when reading new posts.",
+        if user.account.skip_ignored_posts {
+            "skipped"
+        } else {
+            "shown"
+        }
+    ));
+    out.into()
+}
+
+/// Skip posts by ignored users when reading new posts.
+pub fn ignore_posts_on(
+    conn: &mut SqliteConnection,
+    _cfg: &BBSConfig,
+    user: &mut User,
+    _args: Vec<&str>,
+) -> Replies {
+    let _ = users::set_skip_ignored_posts(conn, user, true);
+    "You'll skip posts by users you ignore.".into()
+}
+
+/// Show posts by ignored users when reading new posts.
+pub fn ignore_posts_off(
+    conn: &mut SqliteConnection,
+    _cfg: &BBSConfig,
+    user: &mut User,
+    _args: Vec<&str>,
+) -> Replies {
+    let _ = users::set_skip_ignored_posts(conn, user, false);
+    "You'll see posts by users you ignore.".into()
+}
@@ -220,22 +220,6 @@ mod tests {
     use std::net::TcpListener;
     use std::thread;
 
-    fn test_config() -> BBSConfig {
-        BBSConfig {
-            bbs_name: "Test BBS".to_string(),
-            my_id: "!00000001".to_string(),
-            db_path: ":memory:".to_string(),
-            serial_device: None,
-            tcp_address: None,
-            sysops: vec![],
-            public_channel: 0,
-            ad_text: "".to_string(),
-            weather: None,
-            menus: config::Map::new(),
-            page_delay_ms: None,
-        }
-    }
-
     #[test]
     fn directions_make_sense_around_0_degress() {
         // Just slightly more NNW than N
@@ -252,7 +236,7 @@ mod tests {
 
     #[test]
     fn weather_requires_configuration() {
-        let cfg = test_config();
+        let cfg = BBSConfig::for_tests();
         let mut conn = db::test_connection();
         let (mut user, _) = users::record(&mut conn, "!00000001").expect("user record");
         let replies = current(&mut conn, &cfg, &mut user, vec![]);
@@ -286,7 +270,7 @@ mod tests {
             stream.write_all(response.as_bytes()).expect("write");
         });
 
-        let mut cfg = test_config();
+        let mut cfg = BBSConfig::for_tests();
         cfg.weather = Some(WeatherConfig {
             latitude: 40.0,
             longitude: -75.0,
@@ -1,11 +1,17 @@
+pub mod airtime;
+pub mod announcements;
+pub mod audit;
 pub mod board_states;
 pub mod boards;
+pub mod ignores;
 pub mod invitations;
+pub mod more_pages;
 pub mod posts;
 pub mod queued_messages;
+pub mod rate_limits;
 pub mod users;
 use chrono::{Local, MappedLocalTime, TimeZone, Utc};
-pub use models::{Account, Board, Node, Post, User};
+pub use models::{Account, AuditEntry, Board, Node, Post, QueuedMessage, RateLimit, User};
 mod models;
 mod schema;
 use crate::BBSConfig;
@@ -49,8 +55,18 @@ pub fn now_as_useconds() -> i64 {
 }
 
 /// Format the number of microseconds since the Unix epoch as a local timestamp.
-fn formatted_useconds(dstamp: i64) -> String {
-    let fmt = "%Y-%m-%dT%H:%M:%S";
+pub fn formatted_useconds(dstamp: i64) -> String {
+    formatted_useconds_as(dstamp, "%Y-%m-%dT%H:%M:%S")
+}
+
+/// Format the number of microseconds since the Unix epoch as a short local timestamp, for
+/// places where every byte counts.
+fn brief_useconds(dstamp: i64) -> String {
+    formatted_useconds_as(dstamp, "%m-%d %H:%M")
+}
+
+/// Format the number of microseconds since the Unix epoch as a local timestamp in this format.
+fn formatted_useconds_as(dstamp: i64, fmt: &str) -> String {
     match Local.timestamp_micros(dstamp) {
         // This should be the path except during daylight saving time changes.
         MappedLocalTime::Single(t) => t.format(fmt).to_string(),
@@ -62,7 +78,7 @@ fn formatted_useconds(dstamp: i64) -> String {
         // I don't think this should ever happen, again, because the input is the UTC Unix epoch.
         // How would we ever end up at time that doesn't exist without using a localized time
         // offset? I don't know. But deal with it anyway in case I'm missing something. Note that
-        // the error message here is exactly the same length as the normal timestamp so that
+        // the error message here is exactly the same length as the default timestamp so that
         // something nitpicky about formatting will still work.
         MappedLocalTime::None => "No such local time.".to_string(),
     }
@@ -75,11 +91,15 @@ pub fn stats(conn: &mut SqliteConnection) -> String {
 Seen users  : {}
 Active users: {}
 Boards      : {}
-Posts       : {}",
+Posts       : {}
+Airtime (1h): {:.1}s",
         seen,
         active,
         boards::count(conn),
-        posts::count(conn)
+        posts::count(conn),
+        // The airtime table only holds as much history as the budget window needs, so this may
+        // undercount if the window is shorter than an hour.
+        airtime::used(conn, 3_600_000_000) as f64 / 1_000_000.0
     )
 }
 
@@ -90,11 +110,23 @@ pub(crate) fn test_connection() -> SqliteConnection {
     conn.batch_execute(
         r#"
         PRAGMA foreign_keys = ON;
+        CREATE TABLE airtime (
+            id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+            sent_at_us BIGINT NOT NULL,
+            airtime_us BIGINT NOT NULL
+        );
+        CREATE TABLE announcements (
+            name TEXT NOT NULL PRIMARY KEY,
+            last_sent_at_us BIGINT NOT NULL
+        );
         CREATE TABLE boards (
             id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
             name TEXT NOT NULL UNIQUE,
             description TEXT NOT NULL,
-            created_at_us BIGINT NOT NULL
+            created_at_us BIGINT NOT NULL,
+            hidden BOOL NOT NULL DEFAULT FALSE,
+            read_only BOOL NOT NULL DEFAULT FALSE,
+            sysop_post BOOL NOT NULL DEFAULT FALSE
         );
         CREATE TABLE accounts (
             id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
@@ -105,6 +137,11 @@ pub(crate) fn test_connection() -> SqliteConnection {
             last_acted_at_us BIGINT,
             in_board INTEGER,
             invite_allowed BOOL NOT NULL DEFAULT FALSE,
+            banned_until_us BIGINT,
+            search_post_ids TEXT,
+            digest_enabled BOOL NOT NULL DEFAULT TRUE,
+            dm_receipts BOOL NOT NULL DEFAULT FALSE,
+            skip_ignored_posts BOOL NOT NULL DEFAULT FALSE,
             FOREIGN KEY (in_board) REFERENCES boards (id)
         );
         CREATE TABLE nodes (
@@ -123,10 +160,23 @@ pub(crate) fn test_connection() -> SqliteConnection {
             account_id INTEGER NOT NULL,
             body TEXT NOT NULL,
             created_at_us BIGINT NOT NULL,
+            parent_id INTEGER,
             UNIQUE(created_at_us),
             FOREIGN KEY (account_id) REFERENCES accounts (id),
-            FOREIGN KEY (board_id) REFERENCES boards (id)
+            FOREIGN KEY (board_id) REFERENCES boards (id),
+            FOREIGN KEY (parent_id) REFERENCES posts (id)
         );
+        CREATE VIRTUAL TABLE posts_fts USING fts5(body, content='posts', content_rowid='id');
+        CREATE TRIGGER posts_fts_insert AFTER INSERT ON posts BEGIN
+            INSERT INTO posts_fts(rowid, body) VALUES (new.id, new.body);
+        END;
+        CREATE TRIGGER posts_fts_delete AFTER DELETE ON posts BEGIN
+            INSERT INTO posts_fts(posts_fts, rowid, body) VALUES ('delete', old.id, old.body);
+        END;
+        CREATE TRIGGER posts_fts_update AFTER UPDATE OF body ON posts BEGIN
+            INSERT INTO posts_fts(posts_fts, rowid, body) VALUES ('delete', old.id, old.body);
+            INSERT INTO posts_fts(rowid, body) VALUES (new.id, new.body);
+        END;
         CREATE TABLE board_states (
             id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
             account_id INTEGER NOT NULL,
@@ -135,6 +185,15 @@ pub(crate) fn test_connection() -> SqliteConnection {
             FOREIGN KEY (board_id) REFERENCES boards (id),
             FOREIGN KEY (account_id) REFERENCES accounts (id)
         );
+        CREATE TABLE ignores (
+            id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+            account_id INTEGER NOT NULL,
+            ignored_account_id INTEGER NOT NULL,
+            created_at_us BIGINT NOT NULL,
+            FOREIGN KEY (account_id) REFERENCES accounts (id),
+            FOREIGN KEY (ignored_account_id) REFERENCES accounts (id),
+            UNIQUE (account_id, ignored_account_id)
+        );
         CREATE TABLE queued_messages (
             id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
             sender_account_id INTEGER NOT NULL,
@@ -142,9 +201,28 @@ pub(crate) fn test_connection() -> SqliteConnection {
             body TEXT NOT NULL,
             created_at_us BIGINT NOT NULL,
             sent_at_us BIGINT,
+            receipt BOOL NOT NULL DEFAULT FALSE,
+            expired_at_us BIGINT,
+            notice BOOL NOT NULL DEFAULT FALSE,
             FOREIGN KEY (sender_account_id) REFERENCES accounts (id),
             FOREIGN KEY (recipient_account_id) REFERENCES accounts (id)
         );
+        CREATE TABLE more_pages (
+            id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+            account_id INTEGER NOT NULL,
+            body TEXT NOT NULL,
+            created_at_us BIGINT NOT NULL,
+            FOREIGN KEY (account_id) REFERENCES accounts (id) ON DELETE CASCADE
+        );
+        CREATE TABLE rate_limits (
+            account_id INTEGER NOT NULL PRIMARY KEY,
+            tokens DOUBLE NOT NULL,
+            updated_at_us BIGINT NOT NULL,
+            warned BOOL NOT NULL DEFAULT FALSE,
+            strikes INTEGER NOT NULL DEFAULT 0,
+            muted_until_us BIGINT,
+            FOREIGN KEY (account_id) REFERENCES accounts (id) ON DELETE CASCADE
+        );
         CREATE TABLE invitations (
             id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
             sender_account_id INTEGER NOT NULL,
@@ -156,6 +234,14 @@ pub(crate) fn test_connection() -> SqliteConnection {
             FOREIGN KEY (sender_account_id) REFERENCES accounts (id),
             FOREIGN KEY (invitee_node_id) REFERENCES nodes (id)
         );
+        CREATE TABLE audit_log (
+            id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+            created_at_us BIGINT NOT NULL,
+            actor TEXT NOT NULL,
+            action TEXT NOT NULL,
+            target TEXT,
+            detail TEXT NOT NULL
+        );
         "#,
     )
     .expect("should create schema for tests");
@@ -0,0 +1,55 @@
+use super::models::NewAirtime;
+use super::now_as_useconds;
+use super::schema::airtime::{dsl, table};
+use diesel::prelude::*;
+
+/// Record that we just spent this long transmitting.
+pub fn record(conn: &mut SqliteConnection, airtime_us: i64) {
+    diesel::insert_into(table)
+        .values(&NewAirtime {
+            sent_at_us: now_as_useconds(),
+            airtime_us,
+        })
+        .execute(conn)
+        .expect("should always be able to record airtime");
+}
+
+/// How long we've spent transmitting in the last `window_us` microseconds.
+pub fn used(conn: &mut SqliteConnection, window_us: i64) -> i64 {
+    // Diesel's SUM() of a BIGINT comes back as a NUMERIC, which doesn't map to anything handy.
+    // There are only ever a window's worth of rows, so adding them up here is cheap enough.
+    table
+        .select(dsl::airtime_us)
+        .filter(dsl::sent_at_us.gt(now_as_useconds() - window_us))
+        .load::<i64>(conn)
+        .expect("should always be able to total airtime")
+        .iter()
+        .sum()
+}
+
+/// Forget transmissions older than `window_us` microseconds, which no longer count against
+/// anything.
+pub fn prune(conn: &mut SqliteConnection, window_us: i64) {
+    diesel::delete(table.filter(dsl::sent_at_us.le(now_as_useconds() - window_us)))
+        .execute(conn)
+        .expect("should always be able to prune airtime");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+
+    #[test]
+    fn used_totals_the_window() {
+        let mut conn = db::test_connection();
+        assert_eq!(used(&mut conn, 1_000_000), 0);
+        record(&mut conn, 1_500);
+        record(&mut conn, 2_500);
+        assert_eq!(used(&mut conn, 3_600_000_000), 4_000);
+
+        std::thread::sleep(std::time::Duration::from_micros(10));
+        prune(&mut conn, 1);
+        assert_eq!(used(&mut conn, 3_600_000_000), 0);
+    }
+}
@@ -0,0 +1,43 @@
+use super::models::NewAnnouncement;
+use super::schema::announcements::{dsl, table};
+use diesel::prelude::*;
+
+/// When this announcement was last broadcast, if ever.
+pub fn last_sent(conn: &mut SqliteConnection, name: &str) -> Option<i64> {
+    table
+        .select(dsl::last_sent_at_us)
+        .filter(dsl::name.eq(name))
+        .first(conn)
+        .optional()
+        .expect("should always be possible to load an announcement")
+}
+
+/// Remember that this announcement was just broadcast.
+pub fn sent(conn: &mut SqliteConnection, name: &str, sent_at_us: i64) {
+    diesel::insert_into(table)
+        .values(&NewAnnouncement {
+            name,
+            last_sent_at_us: sent_at_us,
+        })
+        .on_conflict(dsl::name)
+        .do_update()
+        .set(dsl::last_sent_at_us.eq(sent_at_us))
+        .execute(conn)
+        .expect("should always be possible to record an announcement");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+
+    #[test]
+    fn remembers_the_last_time() {
+        let mut conn = db::test_connection();
+        assert_eq!(last_sent(&mut conn, "ad"), None);
+        sent(&mut conn, "ad", 1);
+        sent(&mut conn, "ad", 2);
+        assert_eq!(last_sent(&mut conn, "ad"), Some(2));
+        assert_eq!(last_sent(&mut conn, "news"), None);
+    }
+}
@@ -0,0 +1,109 @@
+use super::models::{AuditEntry, NewAuditEntry};
+use super::schema::audit_log::{dsl, table};
+use super::{now_as_useconds, Result};
+use diesel::prelude::*;
+use validator::Validate as _;
+
+/// The actor for changes made from the command line on the BBS host.
+pub const ADMIN: &str = "admin";
+
+/// Record that `actor` did `action` to `target`.
+///
+/// Actors and targets are node IDs, like "!cafeb33d".
+pub fn record(
+    conn: &mut SqliteConnection,
+    actor: &str,
+    action: &str,
+    target: Option<&str>,
+    detail: &str,
+) -> Result<AuditEntry> {
+    let new_entry = NewAuditEntry {
+        created_at_us: &now_as_useconds(),
+        actor,
+        action,
+        target,
+        detail,
+    };
+    new_entry.validate()?;
+
+    Ok(diesel::insert_into(table)
+        .values(&new_entry)
+        .returning(AuditEntry::as_returning())
+        .get_result(conn)
+        .expect("should always be able to insert an audit log entry"))
+}
+
+/// Get audit log entries, oldest first, optionally limited to a given actor, target, and time
+/// range. `since` and `until` are inclusive.
+pub fn list(
+    conn: &mut SqliteConnection,
+    actor: Option<&str>,
+    target: Option<&str>,
+    since: Option<i64>,
+    until: Option<i64>,
+) -> Vec<AuditEntry> {
+    let mut query = table.select(AuditEntry::as_select()).into_boxed();
+    if let Some(actor) = actor {
+        query = query.filter(dsl::actor.eq(actor));
+    }
+    if let Some(target) = target {
+        query = query.filter(dsl::target.eq(target));
+    }
+    if let Some(since) = since {
+        query = query.filter(dsl::created_at_us.ge(since));
+    }
+    if let Some(until) = until {
+        query = query.filter(dsl::created_at_us.le(until));
+    }
+    query
+        .order((dsl::created_at_us, dsl::id))
+        .load(conn)
+        .expect("should always be able to load the audit log")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+
+    #[test]
+    fn record_requires_an_actor_and_action() {
+        let mut conn = db::test_connection();
+        let entry = record(
+            &mut conn,
+            "!cafeb33d",
+            "ban",
+            Some("!12345678"),
+            "indefinitely",
+        )
+        .expect("should record entry");
+        assert_eq!(entry.actor, "!cafeb33d");
+        assert_eq!(entry.target.as_deref(), Some("!12345678"));
+        assert!(record(&mut conn, "", "ban", None, "").is_err());
+        assert!(record(&mut conn, "!cafeb33d", "", None, "").is_err());
+    }
+
+    #[test]
+    fn list_filters_by_actor_target_and_time() {
+        let mut conn = db::test_connection();
+        let first = record(&mut conn, "!cafeb33d", "ban", Some("!12345678"), "").expect("entry");
+        record(&mut conn, ADMIN, "unban", Some("!12345678"), "").expect("entry");
+        let last = record(&mut conn, ADMIN, "ban", Some("!87654321"), "").expect("entry");
+
+        assert_eq!(list(&mut conn, None, None, None, None).len(), 3);
+        assert_eq!(list(&mut conn, Some(ADMIN), None, None, None).len(), 2);
+        assert_eq!(
+            list(&mut conn, None, Some("!12345678"), None, None).len(),
+            2
+        );
+        let both = list(&mut conn, Some(ADMIN), Some("!12345678"), None, None);
+        assert_eq!(both.len(), 1);
+        assert_eq!(both[0].action, "unban");
+        let latest = list(&mut conn, None, None, Some(last.created_at_us), None);
+        assert_eq!(latest.len(), 1);
+        assert_eq!(latest[0].id, last.id);
+        let earliest = list(&mut conn, None, None, None, Some(first.created_at_us));
+        assert_eq!(earliest.len(), 1);
+        assert_eq!(earliest[0].id, first.id);
+    }
+}
@@ -1,4 +1,5 @@
 use super::models::{BoardState, NewBoardState};
+use super::posts;
 use super::schema::board_states::{dsl, table};
 use diesel::prelude::*;
 
@@ -17,6 +18,17 @@ pub fn get(conn: &mut SqliteConnection, account_id: i32, board_id: i32) -> i64 {
     }
 }
 
+/// Mark everything in the board as read except for the newest `leave_unread` posts. This never
+/// moves the account's place backward, so posts they've already read stay read.
+pub fn catch_up(conn: &mut SqliteConnection, account_id: i32, board_id: i32, leave_unread: usize) {
+    let Some(last_post_us) = posts::newest_timestamp(conn, board_id, leave_unread) else {
+        return;
+    };
+    if last_post_us > get(conn, account_id, board_id) {
+        update(conn, account_id, board_id, last_post_us);
+    }
+}
+
 /// Delete all board states for an account.
 pub fn delete_for_account(conn: &mut SqliteConnection, account_id: i32) -> QueryResult<usize> {
     diesel::delete(dsl::board_states.filter(dsl::account_id.eq(account_id))).execute(conn)
@@ -71,3 +83,44 @@ pub fn update(conn: &mut SqliteConnection, account_id: i32, board_id: i32, last_
     })
     .expect("we must be able to commit database transactions");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{self, boards, users};
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    #[test]
+    fn catch_up_leaves_the_newest_posts_unread() {
+        let mut conn = db::test_connection();
+        let board = boards::add(&mut conn, "General", "General discussion").expect("board");
+        let (user, _) = users::record(&mut conn, "!60000001").expect("user");
+        let mut posted = vec![];
+        for i in 1..=4 {
+            let body = format!("Post {i}");
+            posted.push(posts::add(&mut conn, user.account_id(), board.id, &body).expect("post"));
+            sleep(Duration::from_micros(10));
+        }
+
+        catch_up(&mut conn, user.account_id(), board.id, 2);
+        assert_eq!(
+            get(&mut conn, user.account_id(), board.id),
+            posted[1].created_at_us
+        );
+        // Asking for more unread posts doesn't move backward.
+        catch_up(&mut conn, user.account_id(), board.id, 3);
+        assert_eq!(
+            get(&mut conn, user.account_id(), board.id),
+            posted[1].created_at_us
+        );
+        catch_up(&mut conn, user.account_id(), board.id, 0);
+        assert_eq!(
+            get(&mut conn, user.account_id(), board.id),
+            posted[3].created_at_us
+        );
+        // Boards without posts stay untouched.
+        catch_up(&mut conn, user.account_id(), board.id + 1, 0);
+        assert_eq!(get(&mut conn, user.account_id(), board.id + 1), 0);
+    }
+}
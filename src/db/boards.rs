@@ -1,6 +1,9 @@
-use super::models::{Board, NewBoard};
+use super::models::{Board, BoardUpdate, NewBoard};
+use super::schema::accounts::dsl as accounts_dsl;
+use super::schema::board_states::dsl as board_states_dsl;
 use super::schema::boards::{dsl, table};
-use super::{now_as_useconds, Result};
+use super::schema::posts::dsl as posts_dsl;
+use super::{audit, now_as_useconds, Result};
 use diesel::prelude::*;
 use validator::Validate as _;
 
@@ -34,6 +37,88 @@ pub fn get(conn: &mut SqliteConnection, board_id: i32) -> QueryResult<Board> {
         .first(conn)
 }
 
+/// Change who can read and write a board. Flags left as None stay as they are.
+pub fn set_permissions(
+    conn: &mut SqliteConnection,
+    board_id: i32,
+    hidden: Option<bool>,
+    read_only: Option<bool>,
+    sysop_post: Option<bool>,
+) -> QueryResult<Board> {
+    let board_update = BoardUpdate {
+        name: None,
+        description: None,
+        hidden,
+        read_only,
+        sysop_post,
+    };
+    diesel::update(dsl::boards.filter(dsl::id.eq(board_id)))
+        .set(&board_update)
+        .returning(Board::as_returning())
+        .get_result(conn)
+}
+
+/// Rename a board or change its description. Fields left as None stay as they are. Renaming a
+/// board to another board's name is an error.
+pub fn edit(
+    conn: &mut SqliteConnection,
+    board_id: i32,
+    name: Option<&str>,
+    description: Option<&str>,
+) -> std::result::Result<Board, Box<dyn std::error::Error>> {
+    let board_update = BoardUpdate {
+        name: name.map(str::trim),
+        description: description.map(str::trim),
+        hidden: None,
+        read_only: None,
+        sysop_post: None,
+    };
+    board_update.validate()?;
+
+    Ok(diesel::update(dsl::boards.filter(dsl::id.eq(board_id)))
+        .set(&board_update)
+        .returning(Board::as_returning())
+        .get_result(conn)?)
+}
+
+/// Delete a board on behalf of `actor`, along with everything that refers to it: its posts,
+/// everyone's read position in it, and anyone's note that they're currently in it. Returns the
+/// number of deleted posts.
+pub fn delete(conn: &mut SqliteConnection, actor: &str, board_id: i32) -> QueryResult<usize> {
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        // Make sure the board exists so that deleting a typo isn't silently successful.
+        let board = get(conn, board_id)?;
+
+        let post_ids: Vec<i32> = posts_dsl::posts
+            .select(posts_dsl::id)
+            .filter(posts_dsl::board_id.eq(board_id))
+            .load(conn)?;
+        // Replies in other boards to posts in this one become top-level posts.
+        diesel::update(posts_dsl::posts.filter(posts_dsl::parent_id.eq_any(&post_ids)))
+            .set(posts_dsl::parent_id.eq(None::<i32>))
+            .execute(conn)?;
+        let deleted = diesel::delete(posts_dsl::posts.filter(posts_dsl::board_id.eq(board_id)))
+            .execute(conn)?;
+
+        diesel::delete(
+            board_states_dsl::board_states.filter(board_states_dsl::board_id.eq(board_id)),
+        )
+        .execute(conn)?;
+        diesel::update(accounts_dsl::accounts.filter(accounts_dsl::in_board.eq(board_id)))
+            .set(accounts_dsl::in_board.eq(None::<i32>))
+            .execute(conn)?;
+        diesel::delete(dsl::boards.filter(dsl::id.eq(board_id))).execute(conn)?;
+
+        let detail = format!(
+            "Board #{board_id}, '{}', with {deleted} post(s)",
+            board.name
+        );
+        audit::record(conn, actor, "board_delete", None, &detail)
+            .expect("should be able to record the board deletion");
+        Ok(deleted)
+    })
+}
+
 /// Get the number of configured boards
 #[allow(clippy::cast_possible_truncation)] // We'll never have more than 4 billion boards.
 pub fn count(conn: &mut SqliteConnection) -> i32 {
@@ -42,3 +127,53 @@ pub fn count(conn: &mut SqliteConnection) -> i32 {
         .get_result::<i64>(conn)
         .expect("Error counting boards") as i32
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{self, board_states, posts, users};
+
+    #[test]
+    fn edit_changes_only_given_fields() {
+        let mut conn = db::test_connection();
+        let board = add(&mut conn, "General", "General discussion").expect("board");
+
+        let edited = edit(&mut conn, board.id, Some(" Chat "), None).expect("should edit");
+        assert_eq!(edited.name, "Chat");
+        assert_eq!(edited.description, "General discussion");
+
+        assert!(edit(&mut conn, board.id, Some(""), None).is_err());
+        // Names are unique.
+        add(&mut conn, "Local", "Things happening nearby").expect("board");
+        assert!(edit(&mut conn, board.id, Some("Local"), None).is_err());
+    }
+
+    #[test]
+    fn delete_cleans_up_references() {
+        let mut conn = db::test_connection();
+        let doomed = add(&mut conn, "Doomed", "Going away").expect("board");
+        let kept = add(&mut conn, "Kept", "Staying").expect("board");
+        let (user, _) = users::record(&mut conn, "!50000001").expect("user");
+
+        let post = posts::add(&mut conn, user.account_id(), doomed.id, "bye").expect("post");
+        let reply = posts::reply(&mut conn, user.account_id(), &post, "later").expect("reply");
+        posts::move_to_board(&mut conn, reply.id, kept.id).expect("move");
+        board_states::update(&mut conn, user.account_id(), doomed.id, post.created_at_us);
+        users::enter_board(&mut conn, &user, doomed.id).expect("enter");
+
+        let deleted = delete(&mut conn, "admin", doomed.id).expect("should delete");
+        assert_eq!(deleted, 1);
+        assert!(get(&mut conn, doomed.id).is_err());
+        assert_eq!(board_states::get(&mut conn, user.account_id(), doomed.id), 0);
+        let user = users::get(&mut conn, "!50000001").expect("user");
+        assert_eq!(user.in_board(), None);
+        let reply = posts::get(&mut conn, reply.id).expect("reply should survive");
+        assert_eq!(reply.parent_id, None);
+    }
+
+    #[test]
+    fn delete_missing_board_fails() {
+        let mut conn = db::test_connection();
+        assert!(delete(&mut conn, "admin", 42).is_err());
+    }
+}
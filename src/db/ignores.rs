@@ -0,0 +1,147 @@
+use super::models::{Ignore, NewIgnore, User};
+use super::schema::ignores::{dsl, table};
+use super::{now_as_useconds, users, Result};
+use diesel::prelude::*;
+use diesel::sql_types::Integer;
+use validator::Validate as _;
+
+/// Ignore another account. Ignoring someone twice is harmless.
+pub fn add(conn: &mut SqliteConnection, user: &User, ignored: &User) -> Result<usize> {
+    let new_ignore = NewIgnore {
+        account_id: user.account_id(),
+        ignored_account_id: ignored.account_id(),
+        created_at_us: now_as_useconds(),
+    };
+    new_ignore.validate()?;
+
+    Ok(diesel::insert_into(table)
+        .values(&new_ignore)
+        .on_conflict((dsl::account_id, dsl::ignored_account_id))
+        .do_nothing()
+        .execute(conn)
+        .expect("Error saving ignore"))
+}
+
+/// Stop ignoring another account. Returns whether they'd been ignored.
+pub fn remove(conn: &mut SqliteConnection, user: &User, ignored: &User) -> QueryResult<bool> {
+    let deleted = diesel::delete(
+        table
+            .filter(dsl::account_id.eq(user.account_id()))
+            .filter(dsl::ignored_account_id.eq(ignored.account_id())),
+    )
+    .execute(conn)?;
+    Ok(deleted > 0)
+}
+
+/// Is this account ignoring that one?
+pub fn is_ignoring(conn: &mut SqliteConnection, account_id: i32, other_account_id: i32) -> bool {
+    diesel::select(diesel::dsl::exists(
+        table
+            .filter(dsl::account_id.eq(account_id))
+            .filter(dsl::ignored_account_id.eq(other_account_id)),
+    ))
+    .get_result(conn)
+    .expect("should always be possible to check the ignore list")
+}
+
+/// Get the IDs of the accounts this user is ignoring.
+pub fn ignored_account_ids(conn: &mut SqliteConnection, user: &User) -> Vec<i32> {
+    table
+        .select(dsl::ignored_account_id)
+        .filter(dsl::account_id.eq(user.account_id()))
+        .load(conn)
+        .expect("should always be possible to load the ignore list")
+}
+
+/// Get the users this user is ignoring, in the order they were ignored.
+pub fn list(conn: &mut SqliteConnection, user: &User) -> Vec<User> {
+    let ignores: Vec<Ignore> = table
+        .select(Ignore::as_select())
+        .filter(dsl::account_id.eq(user.account_id()))
+        .order(dsl::id)
+        .load(conn)
+        .expect("should always be possible to load the ignore list");
+    ignores
+        .iter()
+        .filter_map(|ignore| users::get_by_account_id(conn, ignore.ignored_account_id).ok())
+        .collect()
+}
+
+/// Move ignores from one account to another, on both sides. Ignores the new account already has
+/// are kept, and nobody ends up ignoring themselves.
+pub fn migrate_account(
+    conn: &mut SqliteConnection,
+    old_account_id: i32,
+    new_account_id: i32,
+) -> QueryResult<()> {
+    // Diesel doesn't speak SQLite's "UPDATE OR IGNORE", which skips rows that would collide with
+    // the unique constraint. The leftovers get deleted below.
+    for column in ["account_id", "ignored_account_id"] {
+        diesel::sql_query(format!(
+            "UPDATE OR IGNORE ignores SET {column} = ? WHERE {column} = ?"
+        ))
+        .bind::<Integer, _>(new_account_id)
+        .bind::<Integer, _>(old_account_id)
+        .execute(conn)?;
+    }
+    diesel::delete(
+        table.filter(
+            dsl::account_id
+                .eq(old_account_id)
+                .or(dsl::ignored_account_id.eq(old_account_id))
+                .or(dsl::account_id.eq(dsl::ignored_account_id)),
+        ),
+    )
+    .execute(conn)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+
+    #[test]
+    fn add_list_and_remove() {
+        let mut conn = db::test_connection();
+        let (alice, _) = users::record(&mut conn, "!70000001").expect("alice");
+        let (bob, _) = users::record(&mut conn, "!70000002").expect("bob");
+
+        add(&mut conn, &alice, &bob).expect("ignore");
+        add(&mut conn, &alice, &bob).expect("ignore again");
+        assert!(is_ignoring(&mut conn, alice.account_id(), bob.account_id()));
+        assert!(!is_ignoring(
+            &mut conn,
+            bob.account_id(),
+            alice.account_id()
+        ));
+        let ignored = list(&mut conn, &alice);
+        assert_eq!(ignored.len(), 1);
+        assert_eq!(ignored[0].account_id(), bob.account_id());
+
+        assert!(remove(&mut conn, &alice, &bob).expect("unignore"));
+        assert!(!remove(&mut conn, &alice, &bob).expect("unignore again"));
+        assert!(ignored_account_ids(&mut conn, &alice).is_empty());
+    }
+
+    #[test]
+    fn migrate_account_merges_both_sides() {
+        let mut conn = db::test_connection();
+        let (old, _) = users::record(&mut conn, "!70000003").expect("old");
+        let (new, _) = users::record(&mut conn, "!70000004").expect("new");
+        let (pest, _) = users::record(&mut conn, "!70000005").expect("pest");
+        let (grump, _) = users::record(&mut conn, "!70000006").expect("grump");
+        add(&mut conn, &old, &pest).expect("ignore");
+        add(&mut conn, &new, &pest).expect("ignore");
+        add(&mut conn, &old, &new).expect("ignore");
+        add(&mut conn, &grump, &old).expect("ignore");
+
+        migrate_account(&mut conn, old.account_id(), new.account_id()).expect("migrate");
+        assert_eq!(
+            ignored_account_ids(&mut conn, &new),
+            vec![pest.account_id()]
+        );
+        assert!(is_ignoring(&mut conn, grump.account_id(), new.account_id()));
+        assert!(ignored_account_ids(&mut conn, &old).is_empty());
+    }
+}
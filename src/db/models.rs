@@ -1,5 +1,8 @@
-use super::formatted_useconds;
-use super::schema::{accounts, board_states, boards, invitations, nodes, posts};
+use super::schema::{
+    accounts, airtime, announcements, audit_log, board_states, boards, ignores, invitations,
+    more_pages, nodes, posts, rate_limits,
+};
+use super::{brief_useconds, formatted_useconds, now_as_useconds};
 use crate::hex_id_to_num;
 use diesel::prelude::*;
 use regex::Regex;
@@ -23,12 +26,23 @@ pub struct Board {
     pub name: String,
     pub description: String,
     pub created_at_us: i64,
+    pub hidden: bool,
+    pub read_only: bool,
+    pub sysop_post: bool,
 }
 
 impl Board {
     pub fn created_at(&self) -> String {
         formatted_useconds(self.created_at_us)
     }
+    /// Can this user see and read the board?
+    pub fn readable_by(&self, is_sysop: bool) -> bool {
+        !self.hidden || is_sysop
+    }
+    /// Can this user post to the board over the radio?
+    pub fn writable_by(&self, is_sysop: bool) -> bool {
+        !self.read_only && (!self.sysop_post || is_sysop)
+    }
 }
 
 impl fmt::Display for Board {
@@ -48,6 +62,18 @@ pub struct NewBoard<'a> {
     pub created_at_us: &'a i64,
 }
 
+#[derive(AsChangeset, Validate)]
+#[diesel(table_name = boards)]
+pub struct BoardUpdate<'a> {
+    #[validate(length(min = 1, max = 30))]
+    pub name: Option<&'a str>,
+    #[validate(length(min = 1, max = 100))]
+    pub description: Option<&'a str>,
+    pub hidden: Option<bool>,
+    pub read_only: Option<bool>,
+    pub sysop_post: Option<bool>,
+}
+
 #[derive(Debug, Queryable, Selectable)]
 #[diesel(belongs_to(Board))]
 #[diesel(table_name = crate::db::schema::posts)]
@@ -58,12 +84,16 @@ pub struct Post {
     pub account_id: i32,
     pub body: String,
     pub created_at_us: i64,
+    pub parent_id: Option<i32>,
 }
 
 impl Post {
     pub fn created_at(&self) -> String {
         formatted_useconds(self.created_at_us)
     }
+    pub fn created_at_brief(&self) -> String {
+        brief_useconds(self.created_at_us)
+    }
 }
 
 #[derive(Insertable, Validate)]
@@ -77,6 +107,17 @@ pub struct NewPost<'a> {
     pub body: &'a str,
     #[validate(range(min = EARLY_2024, max=EARLY_2200))]
     pub created_at_us: &'a i64,
+    #[validate(range(min = 1))]
+    pub parent_id: Option<i32>,
+}
+
+#[derive(AsChangeset, Validate)]
+#[diesel(table_name = posts)]
+pub struct PostUpdate<'a> {
+    #[validate(range(min = 1))]
+    pub board_id: Option<i32>,
+    #[validate(length(min = 1))]
+    pub body: Option<&'a str>,
 }
 
 /// An account represents a human (or machine) user of the BBS.
@@ -93,6 +134,11 @@ pub struct Account {
     pub last_acted_at_us: Option<i64>,
     pub in_board: Option<i32>,
     pub invite_allowed: bool,
+    pub banned_until_us: Option<i64>,
+    pub search_post_ids: Option<String>,
+    pub digest_enabled: bool,
+    pub dm_receipts: bool,
+    pub skip_ignored_posts: bool,
 }
 
 impl Account {
@@ -106,6 +152,29 @@ impl Account {
             String::new()
         }
     }
+    /// Is this account currently banned? A ban with an expiration in the past no longer counts.
+    pub fn is_banned(&self) -> bool {
+        self.jackass
+            && self
+                .banned_until_us
+                .is_none_or(|until| until > now_as_useconds())
+    }
+    /// The post IDs from the account's latest search, in the order they were shown.
+    pub fn search_results(&self) -> Vec<i32> {
+        self.search_post_ids
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|id| id.parse().ok())
+            .collect()
+    }
+    pub fn banned_until(&self) -> String {
+        if let Some(until) = self.banned_until_us {
+            formatted_useconds(until)
+        } else {
+            String::new()
+        }
+    }
 }
 
 #[derive(Insertable, Validate)]
@@ -231,6 +300,9 @@ impl User {
     pub fn jackass(&self) -> bool {
         self.account.jackass
     }
+    pub fn is_banned(&self) -> bool {
+        self.account.is_banned()
+    }
     pub fn bio(&self) -> &Option<String> {
         &self.account.bio
     }
@@ -281,6 +353,27 @@ pub struct NewBoardState {
     pub last_post_us: i64,
 }
 
+#[derive(Debug, Identifiable, Queryable, Selectable)]
+#[diesel(table_name = crate::db::schema::ignores)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Ignore {
+    pub id: i32,
+    pub account_id: i32,
+    pub ignored_account_id: i32,
+    pub created_at_us: i64,
+}
+
+#[derive(Debug, Insertable, Validate)]
+#[diesel(table_name = ignores)]
+pub struct NewIgnore {
+    #[validate(range(min = 1))]
+    pub account_id: i32,
+    #[validate(range(min = 1))]
+    pub ignored_account_id: i32,
+    #[validate(range(min = EARLY_2024, max=EARLY_2200))]
+    pub created_at_us: i64,
+}
+
 #[derive(Debug, Identifiable, Queryable, Selectable)]
 #[diesel(table_name = crate::db::schema::queued_messages)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
@@ -291,12 +384,27 @@ pub struct QueuedMessage {
     pub body: String,
     pub created_at_us: i64,
     pub sent_at_us: Option<i64>,
+    pub receipt: bool,
+    pub expired_at_us: Option<i64>,
+    pub notice: bool,
 }
 
 impl QueuedMessage {
     pub fn created_at(&self) -> String {
         formatted_useconds(self.created_at_us)
     }
+
+    pub fn created_at_brief(&self) -> String {
+        brief_useconds(self.created_at_us)
+    }
+
+    pub fn is_delivered(&self) -> bool {
+        self.sent_at_us.is_some()
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expired_at_us.is_some()
+    }
 }
 
 #[derive(Insertable, Validate)]
@@ -310,6 +418,79 @@ pub struct QueuedMessageNew<'a> {
     pub body: &'a str,
     #[validate(range(min = EARLY_2024, max=EARLY_2200))]
     pub created_at_us: &'a i64,
+    pub receipt: bool,
+    pub notice: bool,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = airtime)]
+pub struct NewAirtime {
+    pub sent_at_us: i64,
+    pub airtime_us: i64,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = announcements)]
+pub struct NewAnnouncement<'a> {
+    pub name: &'a str,
+    pub last_sent_at_us: i64,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = more_pages)]
+pub struct NewMorePage<'a> {
+    pub account_id: i32,
+    pub body: &'a str,
+    pub created_at_us: i64,
+}
+
+/// Where an account stands with the command rate limiter.
+#[derive(AsChangeset, Clone, Debug, Insertable, PartialEq, Queryable, Selectable)]
+#[diesel(table_name = rate_limits)]
+#[diesel(treat_none_as_null = true)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct RateLimit {
+    pub account_id: i32,
+    /// How many commands the account may run right now, as of `updated_at_us`.
+    pub tokens: f64,
+    pub updated_at_us: i64,
+    /// Whether they've already been told to slow down since they ran out of tokens.
+    pub warned: bool,
+    /// How many times they've run out of tokens recently.
+    pub strikes: i32,
+    pub muted_until_us: Option<i64>,
+}
+
+/// A record of a moderation or account action.
+#[derive(Debug, Identifiable, Queryable, Selectable)]
+#[diesel(table_name = crate::db::schema::audit_log)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct AuditEntry {
+    pub id: i32,
+    pub created_at_us: i64,
+    pub actor: String,
+    pub action: String,
+    pub target: Option<String>,
+    pub detail: String,
+}
+
+impl AuditEntry {
+    pub fn created_at(&self) -> String {
+        formatted_useconds(self.created_at_us)
+    }
+}
+
+#[derive(Insertable, Validate)]
+#[diesel(table_name = audit_log)]
+pub struct NewAuditEntry<'a> {
+    #[validate(range(min = EARLY_2024, max=EARLY_2200))]
+    pub created_at_us: &'a i64,
+    #[validate(length(min = 1))]
+    pub actor: &'a str,
+    #[validate(length(min = 1))]
+    pub action: &'a str,
+    pub target: Option<&'a str>,
+    pub detail: &'a str,
 }
 
 /// An invitation for a node to join a sender's account.
@@ -346,6 +527,7 @@ pub struct NewInvitation<'a> {
 }
 
 #[cfg(test)]
+#[allow(clippy::unreadable_literal)]
 mod tests {
     use super::*;
 
@@ -359,6 +541,11 @@ mod tests {
             last_acted_at_us: Some(1_710_000_001_000_000),
             in_board: Some(1),
             invite_allowed: false,
+            banned_until_us: None,
+            search_post_ids: None,
+            digest_enabled: true,
+            dm_receipts: false,
+            skip_ignored_posts: false,
         }
     }
 
@@ -381,6 +568,36 @@ mod tests {
         }
     }
 
+    fn make_board(hidden: bool, read_only: bool, sysop_post: bool) -> Board {
+        Board {
+            id: 1,
+            name: "General".to_string(),
+            description: "General discussion".to_string(),
+            created_at_us: 1_710_000_000_000_000,
+            hidden,
+            read_only,
+            sysop_post,
+        }
+    }
+
+    #[test]
+    fn board_permissions() {
+        let open = make_board(false, false, false);
+        assert!(open.readable_by(false) && open.writable_by(false));
+
+        let staff = make_board(true, false, false);
+        assert!(!staff.readable_by(false));
+        assert!(staff.readable_by(true) && staff.writable_by(true));
+
+        let announcements = make_board(false, false, true);
+        assert!(announcements.readable_by(false));
+        assert!(!announcements.writable_by(false));
+        assert!(announcements.writable_by(true));
+
+        let archive = make_board(false, true, false);
+        assert!(!archive.writable_by(false) && !archive.writable_by(true));
+    }
+
     #[test]
     fn account_created_at_formats_timestamp() {
         let account = make_account(1, None);
@@ -497,4 +714,16 @@ mod tests {
         user.account.jackass = true;
         assert!(user.jackass());
     }
+
+    #[test]
+    fn account_is_banned_respects_expiry() {
+        let mut account = make_account(1, None);
+        assert!(!account.is_banned());
+        account.jackass = true;
+        assert!(account.is_banned());
+        account.banned_until_us = Some(now_as_useconds() + 60_000_000);
+        assert!(account.is_banned());
+        account.banned_until_us = Some(now_as_useconds() - 60_000_000);
+        assert!(!account.is_banned());
+    }
 }
@@ -0,0 +1,80 @@
+use super::models::NewMorePage;
+use super::now_as_useconds;
+use super::schema::more_pages::{dsl, table};
+use diesel::prelude::*;
+
+/// Hold these pages for the account until it asks for them, in place of any it already had.
+pub fn replace(conn: &mut SqliteConnection, account_id: i32, pages: &[String]) {
+    let now = now_as_useconds();
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        diesel::delete(table.filter(dsl::account_id.eq(account_id))).execute(conn)?;
+        for body in pages {
+            diesel::insert_into(table)
+                .values(&NewMorePage {
+                    account_id,
+                    body,
+                    created_at_us: now,
+                })
+                .execute(conn)?;
+        }
+        Ok(())
+    })
+    .expect("should always be possible to save more pages");
+}
+
+/// Take the account's next waiting page, unless it's older than `max_age_us` microseconds.
+pub fn take(conn: &mut SqliteConnection, account_id: i32, max_age_us: i64) -> Option<String> {
+    let (id, body): (i32, String) = table
+        .select((dsl::id, dsl::body))
+        .filter(dsl::account_id.eq(account_id))
+        .filter(dsl::created_at_us.gt(now_as_useconds() - max_age_us))
+        .order(dsl::id)
+        .first(conn)
+        .optional()
+        .expect("should always be possible to load more pages")?;
+    diesel::delete(table.filter(dsl::id.eq(id)))
+        .execute(conn)
+        .expect("should always be possible to delete a page");
+    Some(body)
+}
+
+/// Forget pages older than `max_age_us` microseconds that nobody asked for. Returns how many
+/// were forgotten.
+pub fn expire(conn: &mut SqliteConnection, max_age_us: i64) -> usize {
+    diesel::delete(table.filter(dsl::created_at_us.le(now_as_useconds() - max_age_us)))
+        .execute(conn)
+        .expect("should always be possible to expire more pages")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{self, users};
+
+    const HOUR: i64 = 3_600_000_000;
+
+    #[test]
+    fn pages_come_out_in_order() {
+        let mut conn = db::test_connection();
+        let (user, _) = users::record(&mut conn, "!70000031").expect("user");
+        let id = user.account_id();
+        replace(&mut conn, id, &["old".to_string()]);
+        replace(&mut conn, id, &["two".to_string(), "three".to_string()]);
+
+        assert_eq!(take(&mut conn, id, HOUR).as_deref(), Some("two"));
+        assert_eq!(take(&mut conn, id, HOUR).as_deref(), Some("three"));
+        assert_eq!(take(&mut conn, id, HOUR), None);
+    }
+
+    #[test]
+    fn stale_pages_expire() {
+        let mut conn = db::test_connection();
+        let (user, _) = users::record(&mut conn, "!70000032").expect("user");
+        replace(&mut conn, user.account_id(), &["two".to_string()]);
+        std::thread::sleep(std::time::Duration::from_micros(10));
+
+        assert_eq!(take(&mut conn, user.account_id(), 1), None);
+        assert_eq!(expire(&mut conn, HOUR), 0);
+        assert_eq!(expire(&mut conn, 1), 1);
+    }
+}
@@ -1,8 +1,9 @@
-use super::models::{Account, NewPost, Node, Post, User};
+use super::models::{Account, NewPost, Node, Post, PostUpdate, User};
 use super::schema::accounts::dsl as accounts_dsl;
+use super::schema::boards::dsl as boards_dsl;
 use super::schema::nodes::dsl as nodes_dsl;
 use super::schema::posts::{dsl as posts_dsl, table};
-use super::{now_as_useconds, Result};
+use super::{audit, now_as_useconds, users, Result};
 use diesel::prelude::*;
 use validator::Validate as _;
 
@@ -38,17 +39,20 @@ fn make_user(conn: &mut SqliteConnection, account: Account) -> User {
     }
 }
 
-pub fn add(
+/// Save a new post, or a reply if it has a parent.
+fn insert(
     conn: &mut SqliteConnection,
     account_id: i32,
     board_id: i32,
     body: &str,
+    parent_id: Option<i32>,
 ) -> Result<Post> {
     let new_post = NewPost {
         account_id,
         board_id,
         body,
         created_at_us: &now_as_useconds(),
+        parent_id,
     };
     new_post.validate()?;
 
@@ -59,6 +63,170 @@ pub fn add(
         .expect("Error saving new post"))
 }
 
+pub fn add(
+    conn: &mut SqliteConnection,
+    account_id: i32,
+    board_id: i32,
+    body: &str,
+) -> Result<Post> {
+    insert(conn, account_id, board_id, body, None)
+}
+
+/// Add a reply to another post. The reply goes in the same board as its parent.
+pub fn reply(
+    conn: &mut SqliteConnection,
+    account_id: i32,
+    parent: &Post,
+    body: &str,
+) -> Result<Post> {
+    insert(conn, account_id, parent.board_id, body, Some(parent.id))
+}
+
+/// Get a post by its ID.
+pub fn get(conn: &mut SqliteConnection, post_id: i32) -> QueryResult<Post> {
+    posts_dsl::posts
+        .select(Post::as_select())
+        .filter(posts_dsl::id.eq(post_id))
+        .first(conn)
+}
+
+/// Get a post by its ID, unless its author is banned.
+pub fn get_visible(conn: &mut SqliteConnection, post_id: i32) -> QueryResult<Post> {
+    let now = now_as_useconds();
+    posts_dsl::posts
+        .inner_join(accounts_dsl::accounts)
+        .select(Post::as_select())
+        .filter(posts_dsl::id.eq(post_id))
+        .filter(
+            accounts_dsl::jackass
+                .eq(false)
+                .or(accounts_dsl::banned_until_us.le(now)),
+        )
+        .first(conn)
+}
+
+/// Get a post and its author by the post's ID.
+pub fn get_with_author(conn: &mut SqliteConnection, post_id: i32) -> QueryResult<(Post, User)> {
+    let (post, account): (Post, Account) = posts_dsl::posts
+        .inner_join(accounts_dsl::accounts)
+        .select((Post::as_select(), Account::as_select()))
+        .filter(posts_dsl::id.eq(post_id))
+        .first::<(Post, Account)>(conn)?;
+    Ok((post, make_user(conn, account)))
+}
+
+/// Turn a user's keywords into an FTS5 query. Words are split on punctuation the same way the
+/// FTS5 tokenizer splits post bodies, so "don't" searches for "don" and "t". Each word becomes a
+/// quoted prefix search, and all of them have to match. Quoting everything means nobody can sneak
+/// FTS5 syntax in.
+fn search_expression(terms: &str) -> String {
+    terms
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("\"{word}\"*"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[derive(QueryableByName)]
+struct SearchHit {
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    id: i32,
+}
+
+/// Find the newest visible posts containing all of these keywords. Posts in hidden boards only
+/// show up for sysops.
+pub fn search(
+    conn: &mut SqliteConnection,
+    terms: &str,
+    is_sysop: bool,
+    limit: i32,
+) -> Vec<(Post, User)> {
+    let expression = search_expression(terms);
+    if expression.is_empty() {
+        return Vec::new();
+    }
+    let hits: Vec<SearchHit> = diesel::sql_query(
+        "SELECT posts.id AS id FROM posts_fts
+        JOIN posts ON posts.id = posts_fts.rowid
+        JOIN accounts ON accounts.id = posts.account_id
+        JOIN boards ON boards.id = posts.board_id
+        WHERE posts_fts MATCH ?
+        AND (accounts.jackass = FALSE OR accounts.banned_until_us <= ?)
+        AND (boards.hidden = FALSE OR ?)
+        ORDER BY posts.created_at_us DESC
+        LIMIT ?",
+    )
+    .bind::<diesel::sql_types::Text, _>(&expression)
+    .bind::<diesel::sql_types::BigInt, _>(now_as_useconds())
+    .bind::<diesel::sql_types::Bool, _>(is_sysop)
+    .bind::<diesel::sql_types::Integer, _>(limit)
+    .load(conn)
+    .expect("Error searching posts");
+    let ids: Vec<i32> = hits.into_iter().map(|hit| hit.id).collect();
+
+    let results: Vec<(Post, Account)> = posts_dsl::posts
+        .inner_join(accounts_dsl::accounts)
+        .select((Post::as_select(), Account::as_select()))
+        .filter(posts_dsl::id.eq_any(&ids))
+        .order(posts_dsl::created_at_us.desc())
+        .load::<(Post, Account)>(conn)
+        .expect("Error loading search results");
+
+    results
+        .into_iter()
+        .map(|(post, account)| (post, make_user(conn, account)))
+        .collect()
+}
+
+/// Get every visible post in the same thread as this one, oldest first.
+///
+/// A thread is the post at the top of the chain of replies, and every reply beneath it. Threads
+/// are short and this walks them one level at a time, which keeps the queries dead simple.
+pub fn thread(conn: &mut SqliteConnection, post: &Post) -> Vec<(Post, User)> {
+    // Climb to the top of the thread.
+    let mut root_id = post.id;
+    let mut parent_id = post.parent_id;
+    while let Some(id) = parent_id {
+        let Ok(parent) = get(conn, id) else {
+            break;
+        };
+        root_id = parent.id;
+        parent_id = parent.parent_id;
+    }
+
+    // Then collect every generation of replies beneath it.
+    let mut ids = vec![root_id];
+    let mut generation = vec![root_id];
+    while !generation.is_empty() {
+        generation = posts_dsl::posts
+            .select(posts_dsl::id)
+            .filter(posts_dsl::parent_id.eq_any(&generation))
+            .load(conn)
+            .expect("Error loading replies");
+        ids.extend(&generation);
+    }
+
+    let now = now_as_useconds();
+    let results: Vec<(Post, Account)> = posts_dsl::posts
+        .inner_join(accounts_dsl::accounts)
+        .select((Post::as_select(), Account::as_select()))
+        .filter(posts_dsl::id.eq_any(&ids))
+        .filter(
+            accounts_dsl::jackass
+                .eq(false)
+                .or(accounts_dsl::banned_until_us.le(now)),
+        )
+        .order(posts_dsl::created_at_us)
+        .load::<(Post, Account)>(conn)
+        .expect("Error loading thread");
+
+    results
+        .into_iter()
+        .map(|(post, account)| (post, make_user(conn, account)))
+        .collect()
+}
+
 pub fn in_board(conn: &mut SqliteConnection, board_id: i32) -> Vec<(Post, User)> {
     let results: Vec<(Post, Account)> = posts_dsl::posts
         .inner_join(accounts_dsl::accounts)
@@ -74,35 +242,197 @@ pub fn in_board(conn: &mut SqliteConnection, board_id: i32) -> Vec<(Post, User)>
         .collect()
 }
 
+/// Count the posts in the board that aren't hidden by their authors' bans. Posts are numbered
+/// from 1, oldest first, among these.
+#[allow(clippy::cast_possible_truncation)] // We'll never have more than 4 billion posts.
+pub fn count_visible(conn: &mut SqliteConnection, board_id: i32) -> usize {
+    let now = now_as_useconds();
+    posts_dsl::posts
+        .inner_join(accounts_dsl::accounts)
+        .filter(posts_dsl::board_id.eq(board_id))
+        .filter(
+            accounts_dsl::jackass
+                .eq(false)
+                .or(accounts_dsl::banned_until_us.le(now)),
+        )
+        .count()
+        .get_result::<i64>(conn)
+        .expect("Error counting posts") as usize
+}
+
+/// Get up to `limit` of the board's visible posts, oldest first, after skipping the first `skip`
+/// of them. The first one returned is post number `skip + 1`.
+pub fn visible_range(
+    conn: &mut SqliteConnection,
+    board_id: i32,
+    skip: usize,
+    limit: usize,
+) -> Vec<(Post, User)> {
+    let now = now_as_useconds();
+    let results: Vec<(Post, Account)> = posts_dsl::posts
+        .inner_join(accounts_dsl::accounts)
+        .select((Post::as_select(), Account::as_select()))
+        .filter(posts_dsl::board_id.eq(board_id))
+        .filter(
+            accounts_dsl::jackass
+                .eq(false)
+                .or(accounts_dsl::banned_until_us.le(now)),
+        )
+        .order(posts_dsl::created_at_us)
+        .offset(i64::try_from(skip).unwrap_or(i64::MAX))
+        .limit(i64::try_from(limit).unwrap_or(i64::MAX))
+        .load::<(Post, Account)>(conn)
+        .expect("Error loading posts");
+
+    results
+        .into_iter()
+        .map(|(post, account)| (post, make_user(conn, account)))
+        .collect()
+}
+
+/// Get up to `limit` of the newest visible posts in any board, oldest first. Posts in hidden boards
+/// are only included if `include_hidden`.
+pub fn newest(
+    conn: &mut SqliteConnection,
+    limit: usize,
+    include_hidden: bool,
+) -> Vec<(Post, User)> {
+    let now = now_as_useconds();
+    let mut query = posts_dsl::posts
+        .inner_join(accounts_dsl::accounts)
+        .inner_join(boards_dsl::boards)
+        .select((Post::as_select(), Account::as_select()))
+        .filter(
+            accounts_dsl::jackass
+                .eq(false)
+                .or(accounts_dsl::banned_until_us.le(now)),
+        )
+        .into_boxed();
+    if !include_hidden {
+        query = query.filter(boards_dsl::hidden.eq(false));
+    }
+    let results: Vec<(Post, Account)> = query
+        .order(posts_dsl::created_at_us.desc())
+        .limit(i64::try_from(limit).unwrap_or(i64::MAX))
+        .load::<(Post, Account)>(conn)
+        .expect("Error loading posts");
+
+    results
+        .into_iter()
+        .rev()
+        .map(|(post, account)| (post, make_user(conn, account)))
+        .collect()
+}
+
+/// Get the timestamp of the newest visible post in the board, after skipping past `skip` of the
+/// newest ones.
+pub fn newest_timestamp(conn: &mut SqliteConnection, board_id: i32, skip: usize) -> Option<i64> {
+    let now = now_as_useconds();
+    posts_dsl::posts
+        .inner_join(accounts_dsl::accounts)
+        .select(posts_dsl::created_at_us)
+        .filter(posts_dsl::board_id.eq(board_id))
+        .filter(
+            accounts_dsl::jackass
+                .eq(false)
+                .or(accounts_dsl::banned_until_us.le(now)),
+        )
+        .order(posts_dsl::created_at_us.desc())
+        .offset(i64::try_from(skip).unwrap_or(i64::MAX))
+        .first(conn)
+        .optional()
+        .expect("Error finding the newest post")
+}
+
+/// Count the visible posts in the board newer than this timestamp.
+#[allow(clippy::cast_possible_truncation)] // We'll never have more than 4 billion posts.
+pub fn count_after(conn: &mut SqliteConnection, board_id: i32, last_timestamp: i64) -> i32 {
+    let now = now_as_useconds();
+    posts_dsl::posts
+        .inner_join(accounts_dsl::accounts)
+        .filter(posts_dsl::board_id.eq(board_id))
+        .filter(posts_dsl::created_at_us.gt(last_timestamp))
+        .filter(
+            accounts_dsl::jackass
+                .eq(false)
+                .or(accounts_dsl::banned_until_us.le(now)),
+        )
+        .count()
+        .get_result::<i64>(conn)
+        .expect("Error counting posts") as i32
+}
+
+/// Count the visible posts in boards everyone can see that are newer than this timestamp.
+#[allow(clippy::cast_possible_truncation)] // We'll never have more than 4 billion posts.
+pub fn count_public_after(conn: &mut SqliteConnection, last_timestamp: i64) -> i32 {
+    let now = now_as_useconds();
+    posts_dsl::posts
+        .inner_join(accounts_dsl::accounts)
+        .inner_join(boards_dsl::boards)
+        .filter(posts_dsl::created_at_us.gt(last_timestamp))
+        .filter(boards_dsl::hidden.eq(false))
+        .filter(
+            accounts_dsl::jackass
+                .eq(false)
+                .or(accounts_dsl::banned_until_us.le(now)),
+        )
+        .count()
+        .get_result::<i64>(conn)
+        .expect("Error counting posts") as i32
+}
+
 /// Get the post with this timestamp.
 pub fn current(
     conn: &mut SqliteConnection,
     board_id: i32,
     last_timestamp: i64,
 ) -> QueryResult<(Post, User)> {
+    // Hide posts by banned accounts, unless their ban has expired.
+    let now = now_as_useconds();
     let (post, account): (Post, Account) = posts_dsl::posts
         .inner_join(accounts_dsl::accounts)
         .select((Post::as_select(), Account::as_select()))
         .filter(posts_dsl::board_id.eq(board_id))
         .filter(posts_dsl::created_at_us.eq(last_timestamp))
-        .filter(accounts_dsl::jackass.eq(false))
+        .filter(
+            accounts_dsl::jackass
+                .eq(false)
+                .or(accounts_dsl::banned_until_us.le(now)),
+        )
         .limit(1)
         .first::<(Post, Account)>(conn)?;
     Ok((post, make_user(conn, account)))
 }
 
+/// Get the post with this timestamp, even if its author is banned. Sysops need to see spam to
+/// clean it up.
+pub fn at(conn: &mut SqliteConnection, board_id: i32, timestamp: i64) -> QueryResult<(Post, User)> {
+    let (post, account): (Post, Account) = posts_dsl::posts
+        .inner_join(accounts_dsl::accounts)
+        .select((Post::as_select(), Account::as_select()))
+        .filter(posts_dsl::board_id.eq(board_id))
+        .filter(posts_dsl::created_at_us.eq(timestamp))
+        .first::<(Post, Account)>(conn)?;
+    Ok((post, make_user(conn, account)))
+}
+
 /// Get the first post in the board newer than this one.
 pub fn after(
     conn: &mut SqliteConnection,
     board_id: i32,
     last_timestamp: i64,
 ) -> QueryResult<(Post, User)> {
+    let now = now_as_useconds();
     let (post, account): (Post, Account) = posts_dsl::posts
         .inner_join(accounts_dsl::accounts)
         .select((Post::as_select(), Account::as_select()))
         .filter(posts_dsl::board_id.eq(board_id))
         .filter(posts_dsl::created_at_us.gt(last_timestamp))
-        .filter(accounts_dsl::jackass.eq(false))
+        .filter(
+            accounts_dsl::jackass
+                .eq(false)
+                .or(accounts_dsl::banned_until_us.le(now)),
+        )
         .order(posts_dsl::created_at_us)
         .limit(1)
         .first::<(Post, Account)>(conn)?;
@@ -115,12 +445,17 @@ pub fn before(
     board_id: i32,
     last_timestamp: i64,
 ) -> QueryResult<(Post, User)> {
+    let now = now_as_useconds();
     let (post, account): (Post, Account) = posts_dsl::posts
         .inner_join(accounts_dsl::accounts)
         .select((Post::as_select(), Account::as_select()))
         .filter(posts_dsl::board_id.eq(board_id))
         .filter(posts_dsl::created_at_us.lt(last_timestamp))
-        .filter(accounts_dsl::jackass.eq(false))
+        .filter(
+            accounts_dsl::jackass
+                .eq(false)
+                .or(accounts_dsl::banned_until_us.le(now)),
+        )
         .order(posts_dsl::created_at_us.desc())
         .limit(1)
         .first::<(Post, Account)>(conn)?;
@@ -152,6 +487,69 @@ pub fn migrate_account(
         .execute(conn)
 }
 
+/// Change a post's body.
+pub fn edit(
+    conn: &mut SqliteConnection,
+    post_id: i32,
+    body: &str,
+) -> std::result::Result<Post, Box<dyn std::error::Error>> {
+    let post_update = PostUpdate {
+        board_id: None,
+        body: Some(body),
+    };
+    post_update.validate()?;
+
+    Ok(
+        diesel::update(posts_dsl::posts.filter(posts_dsl::id.eq(post_id)))
+            .set(&post_update)
+            .returning(Post::as_returning())
+            .get_result(conn)?,
+    )
+}
+
+/// Move a post to another board. Its replies stay where they are.
+pub fn move_to_board(
+    conn: &mut SqliteConnection,
+    post_id: i32,
+    board_id: i32,
+) -> QueryResult<Post> {
+    let post_update = PostUpdate {
+        board_id: Some(board_id),
+        body: None,
+    };
+    diesel::update(posts_dsl::posts.filter(posts_dsl::id.eq(post_id)))
+        .set(&post_update)
+        .returning(Post::as_returning())
+        .get_result(conn)
+}
+
+/// Delete a post on behalf of `actor`. Its replies move up to answer the deleted post's parent
+/// so that the rest of the thread stays together.
+pub fn delete(conn: &mut SqliteConnection, actor: &str, post_id: i32) -> QueryResult<Post> {
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        let post = get(conn, post_id)?;
+        diesel::update(posts_dsl::posts.filter(posts_dsl::parent_id.eq(post_id)))
+            .set(posts_dsl::parent_id.eq(post.parent_id))
+            .execute(conn)?;
+        diesel::delete(posts_dsl::posts.filter(posts_dsl::id.eq(post_id))).execute(conn)?;
+
+        let author = users::get_by_account_id(conn, post.account_id).ok();
+        let detail = format!(
+            "Post #{} in board #{}: {}",
+            post.id, post.board_id, post.body
+        );
+        audit::record(
+            conn,
+            actor,
+            "post_delete",
+            author.as_ref().map(User::node_id),
+            &detail,
+        )
+        .expect("should be able to record the post deletion");
+        Ok(post)
+    })
+}
+
 /// Get the number of posts
 #[allow(clippy::cast_possible_truncation)] // We'll never have more than 4 billion posts.
 pub fn count(conn: &mut SqliteConnection) -> i32 {
@@ -161,6 +559,16 @@ pub fn count(conn: &mut SqliteConnection) -> i32 {
         .expect("Error counting posts") as i32
 }
 
+/// Get the number of posts written by this account.
+#[allow(clippy::cast_possible_truncation)] // We'll never have more than 4 billion posts.
+pub fn count_by_account(conn: &mut SqliteConnection, account_id: i32) -> i32 {
+    posts_dsl::posts
+        .filter(posts_dsl::account_id.eq(account_id))
+        .count()
+        .get_result::<i64>(conn)
+        .expect("Error counting posts") as i32
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -244,7 +652,7 @@ mod tests {
         sleep(Duration::from_micros(10));
         let post3 = add(&mut conn, user3.account_id(), board.id, "all good").expect("post3");
 
-        let _ = users::ban(&mut conn, &user2).expect("should mark jackass");
+        let _ = users::ban(&mut conn, "admin", &user2, None).expect("should mark jackass");
 
         let (next_post, next_user) = after(&mut conn, board.id, post1.created_at_us)
             .expect("should find next non-jackass post");
@@ -266,4 +674,170 @@ mod tests {
         assert_eq!(timeline[0].0.id, post1.id);
         assert_eq!(timeline[2].0.id, post3.id);
     }
+
+    #[test]
+    fn fetches_show_posts_once_a_ban_expires() {
+        let mut conn = db::test_connection();
+
+        let board =
+            boards::add(&mut conn, "General", "General discussion").expect("should create board");
+        let (user, _) = users::record(&mut conn, "!20000011").expect("user");
+        let post = add(&mut conn, user.account_id(), board.id, "back again").expect("post");
+
+        let _ = users::ban(
+            &mut conn,
+            "admin",
+            &user,
+            Some(now_as_useconds() + 60_000_000),
+        )
+        .expect("should ban temporarily");
+        assert!(current(&mut conn, board.id, post.created_at_us).is_err());
+
+        let _ = users::ban(
+            &mut conn,
+            "admin",
+            &user,
+            Some(now_as_useconds() - 60_000_000),
+        )
+        .expect("should ban with an expiration in the past");
+        let (current_post, _) =
+            current(&mut conn, board.id, post.created_at_us).expect("ban should have expired");
+        assert_eq!(current_post.id, post.id);
+    }
+
+    #[test]
+    fn thread_collects_the_whole_reply_tree_in_order() {
+        let mut conn = db::test_connection();
+        let board =
+            boards::add(&mut conn, "General", "General discussion").expect("should create board");
+        let (user, _) = users::record(&mut conn, "!20000021").expect("user");
+
+        let root = add(&mut conn, user.account_id(), board.id, "root").expect("root");
+        sleep(Duration::from_micros(10));
+        let _other = add(&mut conn, user.account_id(), board.id, "unrelated").expect("other");
+        sleep(Duration::from_micros(10));
+        let child = reply(&mut conn, user.account_id(), &root, "child").expect("child");
+        sleep(Duration::from_micros(10));
+        let grandchild =
+            reply(&mut conn, user.account_id(), &child, "grandchild").expect("grandchild");
+
+        assert_eq!(child.parent_id, Some(root.id));
+        assert_eq!(child.board_id, board.id);
+
+        let ids: Vec<i32> = thread(&mut conn, &grandchild)
+            .into_iter()
+            .map(|(post, _)| post.id)
+            .collect();
+        assert_eq!(ids, vec![root.id, child.id, grandchild.id]);
+    }
+
+    #[test]
+    fn search_matches_all_keywords_and_hides_banned_authors() {
+        let mut conn = db::test_connection();
+        let board = boards::add(&mut conn, "General", "General discussion").expect("board");
+        let hidden = boards::add(&mut conn, "Staff", "Sysops only").expect("board");
+        boards::set_permissions(&mut conn, hidden.id, Some(true), None, None).expect("hide");
+        let (user, _) = users::record(&mut conn, "!30000041").expect("user");
+        let (jerk, _) = users::record(&mut conn, "!30000042").expect("jerk");
+
+        add(&mut conn, user.account_id(), board.id, "Antenna for sale").expect("post");
+        sleep(Duration::from_micros(10));
+        add(
+            &mut conn,
+            user.account_id(),
+            board.id,
+            "Anyone near the repeater?",
+        )
+        .expect("post");
+        sleep(Duration::from_micros(10));
+        add(&mut conn, user.account_id(), hidden.id, "Antenna budget").expect("post");
+        sleep(Duration::from_micros(10));
+        add(&mut conn, jerk.account_id(), board.id, "Antenna spam").expect("post");
+        users::ban(&mut conn, "admin", &jerk, None).expect("ban");
+
+        let bodies = |results: Vec<(Post, User)>| -> Vec<String> {
+            results.into_iter().map(|(post, _)| post.body).collect()
+        };
+        assert_eq!(
+            bodies(search(&mut conn, "ANTENNA", false, 10)),
+            vec!["Antenna for sale"]
+        );
+        assert_eq!(
+            bodies(search(&mut conn, "ant", true, 10)),
+            vec!["Antenna budget", "Antenna for sale"]
+        );
+        assert_eq!(
+            bodies(search(&mut conn, "antenna sale", false, 10)),
+            vec!["Antenna for sale"]
+        );
+        assert!(search(&mut conn, "\"*( OR", false, 10).is_empty());
+        assert!(search(&mut conn, "", false, 10).is_empty());
+    }
+
+    #[test]
+    fn search_follows_edits_and_deletes() {
+        let mut conn = db::test_connection();
+        let board = boards::add(&mut conn, "General", "General discussion").expect("board");
+        let (user, _) = users::record(&mut conn, "!30000043").expect("user");
+        let post = add(&mut conn, user.account_id(), board.id, "Old words").expect("post");
+
+        edit(&mut conn, post.id, "New words").expect("edit");
+        assert!(search(&mut conn, "old", false, 10).is_empty());
+        assert_eq!(search(&mut conn, "new", false, 10).len(), 1);
+        delete(&mut conn, "admin", post.id).expect("delete");
+        assert!(search(&mut conn, "words", false, 10).is_empty());
+    }
+
+    #[test]
+    fn search_splits_words_on_punctuation() {
+        let mut conn = db::test_connection();
+        let board = boards::add(&mut conn, "General", "General discussion").expect("board");
+        let (user, _) = users::record(&mut conn, "!30000044").expect("user");
+        add(
+            &mut conn,
+            user.account_id(),
+            board.id,
+            "Don't forget the net-check",
+        )
+        .expect("post");
+
+        assert_eq!(search(&mut conn, "don't", false, 10).len(), 1);
+        assert_eq!(search(&mut conn, "net-check", false, 10).len(), 1);
+        assert!(search(&mut conn, "dont", false, 10).is_empty());
+    }
+
+    #[test]
+    fn delete_reparents_replies() {
+        let mut conn = db::test_connection();
+        let board =
+            boards::add(&mut conn, "General", "General discussion").expect("should create board");
+        let (user, _) = users::record(&mut conn, "!20000031").expect("user");
+
+        let root = add(&mut conn, user.account_id(), board.id, "root").expect("root");
+        let middle = reply(&mut conn, user.account_id(), &root, "middle").expect("middle");
+        let leaf = reply(&mut conn, user.account_id(), &middle, "leaf").expect("leaf");
+
+        let deleted = delete(&mut conn, "admin", middle.id).expect("should delete");
+        assert_eq!(deleted.id, middle.id);
+        assert!(get(&mut conn, middle.id).is_err());
+        assert_eq!(get(&mut conn, leaf.id).expect("leaf").parent_id, Some(root.id));
+    }
+
+    #[test]
+    fn edit_and_move_update_the_post() {
+        let mut conn = db::test_connection();
+        let board1 = boards::add(&mut conn, "General", "General discussion").expect("board1");
+        let board2 = boards::add(&mut conn, "Random", "Random chat").expect("board2");
+        let (user, _) = users::record(&mut conn, "!20000032").expect("user");
+        let post = add(&mut conn, user.account_id(), board1.id, "tpyo").expect("post");
+
+        let post = edit(&mut conn, post.id, "typo").expect("should edit");
+        assert_eq!(post.body, "typo");
+        assert!(edit(&mut conn, post.id, "").is_err());
+        assert!(edit(&mut conn, 42, "typo").is_err());
+
+        let post = move_to_board(&mut conn, post.id, board2.id).expect("should move");
+        assert_eq!(post.board_id, board2.id);
+        assert!(move_to_board(&mut conn, post.id, 42).is_err());
+    }
 }
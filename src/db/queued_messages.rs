@@ -1,4 +1,5 @@
 use super::models::{QueuedMessage, QueuedMessageNew, User};
+use super::schema::accounts::dsl as accounts_dsl;
 use super::schema::queued_messages::{dsl, table};
 use super::{now_as_useconds, Result};
 use diesel::prelude::*;
@@ -10,10 +11,122 @@ pub fn get(conn: &mut SqliteConnection, user: &User) -> Vec<QueuedMessage> {
         .select(QueuedMessage::as_select())
         .filter(dsl::recipient_account_id.eq(user.account_id()))
         .filter(dsl::sent_at_us.is_null())
+        .filter(dsl::expired_at_us.is_null())
         .load(conn)
         .expect("should always be possible to get queued messages")
 }
 
+/// Count the messages waiting for this recipient, optionally only those from one sender.
+/// Delivery receipts and notices from the BBS don't count against anyone.
+pub fn pending_count(
+    conn: &mut SqliteConnection,
+    sender_account_id: Option<i32>,
+    recipient_account_id: i32,
+) -> i64 {
+    let mut query = table
+        .filter(dsl::recipient_account_id.eq(recipient_account_id))
+        .filter(dsl::sent_at_us.is_null())
+        .filter(dsl::expired_at_us.is_null())
+        .filter(dsl::receipt.eq(false))
+        .filter(dsl::notice.eq(false))
+        .into_boxed();
+    if let Some(sender_account_id) = sender_account_id {
+        query = query.filter(dsl::sender_account_id.eq(sender_account_id));
+    }
+    query
+        .count()
+        .get_result(conn)
+        .expect("should always be possible to count queued messages")
+}
+
+/// Give up on messages that have waited longer than `max_age_us` to be delivered, and let their
+/// senders know with a notice from the BBS. Returns how many messages expired.
+pub fn expire(conn: &mut SqliteConnection, max_age_us: i64) -> usize {
+    let now = now_as_useconds();
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        let stale: Vec<QueuedMessage> = table
+            .select(QueuedMessage::as_select())
+            .filter(dsl::sent_at_us.is_null())
+            .filter(dsl::expired_at_us.is_null())
+            .filter(dsl::created_at_us.lt(now - max_age_us))
+            .load(conn)?;
+        for message in &stale {
+            diesel::update(message)
+                .set(dsl::expired_at_us.eq(now))
+                .execute(conn)?;
+            // Nobody needs to hear that their receipt or notice went missing.
+            if message.receipt || message.notice {
+                continue;
+            }
+            let body = format!(
+                "Couldn't deliver your message from {} before it expired.",
+                message.created_at()
+            );
+            diesel::insert_into(table)
+                .values(&QueuedMessageNew {
+                    sender_account_id: message.sender_account_id,
+                    recipient_account_id: message.sender_account_id,
+                    body: &body,
+                    created_at_us: &now,
+                    receipt: false,
+                    notice: true,
+                })
+                .execute(conn)?;
+        }
+        Ok(stale.len())
+    })
+    .expect("should always be possible to expire queued messages")
+}
+
+/// Get a queued message by its ID.
+pub fn find(conn: &mut SqliteConnection, id: i32) -> QueryResult<QueuedMessage> {
+    table
+        .select(QueuedMessage::as_select())
+        .find(id)
+        .first(conn)
+}
+
+/// Get the messages most recently sent to this user, newest first, whether or not they've been
+/// delivered yet. Expired messages never reached them, so they're not included.
+pub fn received(conn: &mut SqliteConnection, user: &User, limit: i64) -> Vec<QueuedMessage> {
+    table
+        .select(QueuedMessage::as_select())
+        .filter(dsl::recipient_account_id.eq(user.account_id()))
+        .filter(dsl::expired_at_us.is_null())
+        .order((dsl::created_at_us.desc(), dsl::id.desc()))
+        .limit(limit)
+        .load(conn)
+        .expect("should always be possible to get received messages")
+}
+
+/// Get the messages this user most recently sent, newest first, not counting delivery receipts or
+/// notices from the BBS.
+pub fn sent_by(conn: &mut SqliteConnection, user: &User, limit: i64) -> Vec<QueuedMessage> {
+    table
+        .select(QueuedMessage::as_select())
+        .filter(dsl::sender_account_id.eq(user.account_id()))
+        .filter(dsl::receipt.eq(false))
+        .filter(dsl::notice.eq(false))
+        .order((dsl::created_at_us.desc(), dsl::id.desc()))
+        .limit(limit)
+        .load(conn)
+        .expect("should always be possible to get sent messages")
+}
+
+/// Get the account ID of whoever most recently sent this user a message, not counting delivery
+/// receipts or notices from the BBS.
+pub fn last_sender(conn: &mut SqliteConnection, user: &User) -> Option<i32> {
+    table
+        .select(dsl::sender_account_id)
+        .filter(dsl::recipient_account_id.eq(user.account_id()))
+        .filter(dsl::receipt.eq(false))
+        .filter(dsl::notice.eq(false))
+        .order((dsl::created_at_us.desc(), dsl::id.desc()))
+        .first(conn)
+        .optional()
+        .expect("should always be possible to find the last sender")
+}
+
 /// Queue a message for a user.
 pub fn post(
     conn: &mut SqliteConnection,
@@ -26,6 +139,8 @@ pub fn post(
         recipient_account_id: recipient.account_id(),
         body,
         created_at_us: &now_as_useconds(),
+        receipt: false,
+        notice: false,
     };
     new_post.validate()?;
 
@@ -48,6 +163,8 @@ pub fn queue_by_account_ids(
         recipient_account_id,
         body,
         created_at_us: &now_as_useconds(),
+        receipt: false,
+        notice: false,
     };
     new_post.validate()?;
 
@@ -78,11 +195,151 @@ pub fn migrate_account(
     Ok((sender_count, recipient_count))
 }
 
-/// Mark a message as sent.
+/// Mark a message as sent, and queue a delivery receipt for the sender if they want one. Marking
+/// it again does nothing, so the sender only ever gets one receipt.
 pub fn sent(conn: &mut SqliteConnection, message: &QueuedMessage) {
     let now = now_as_useconds();
-    diesel::update(&message)
-        .set(dsl::sent_at_us.eq(now))
-        .execute(conn)
-        .expect("should always be possible to mark a queued message as sent");
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        let updated = diesel::update(&message)
+            .filter(dsl::sent_at_us.is_null())
+            .set(dsl::sent_at_us.eq(now))
+            .execute(conn)?;
+        if updated == 0 || message.receipt || message.notice {
+            return Ok(());
+        }
+        let wants_receipt = accounts_dsl::accounts
+            .find(message.sender_account_id)
+            .select(accounts_dsl::dm_receipts)
+            .first::<bool>(conn)?;
+        if wants_receipt {
+            let body = format!("Delivered your message from {}.", message.created_at());
+            diesel::insert_into(table)
+                .values(&QueuedMessageNew {
+                    sender_account_id: message.recipient_account_id,
+                    recipient_account_id: message.sender_account_id,
+                    body: &body,
+                    created_at_us: &now,
+                    receipt: true,
+                    notice: false,
+                })
+                .execute(conn)?;
+        }
+        Ok(())
+    })
+    .expect("should always be possible to mark a queued message as sent");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{self, users};
+
+    #[test]
+    fn receipts_go_only_to_senders_who_want_them() {
+        let mut conn = db::test_connection();
+        let (alice, _) = users::record(&mut conn, "!60000001").expect("alice");
+        let (bob, _) = users::record(&mut conn, "!60000002").expect("bob");
+
+        let first = post(&mut conn, &alice, &bob, "No receipt, please").expect("message");
+        sent(&mut conn, &first);
+        assert!(get(&mut conn, &alice).is_empty());
+
+        let alice = users::set_dm_receipts(&mut conn, &alice, true).expect("receipts");
+        let second = post(&mut conn, &alice, &bob, "Let me know").expect("message");
+        sent(&mut conn, &second);
+        let receipts = get(&mut conn, &alice);
+        assert_eq!(receipts.len(), 1);
+        assert!(receipts[0].receipt);
+        assert_eq!(receipts[0].sender_account_id, bob.account_id());
+        // Hearing about the delivery twice doesn't send a second receipt.
+        sent(&mut conn, &second);
+        assert_eq!(get(&mut conn, &alice).len(), 1);
+
+        // Receipts don't get receipts, even if both sides want them.
+        users::set_dm_receipts(&mut conn, &bob, true).expect("receipts");
+        sent(&mut conn, &receipts[0]);
+        assert!(get(&mut conn, &bob).is_empty());
+        // Nor do they count as someone messaging you.
+        assert_eq!(last_sender(&mut conn, &alice), None);
+        assert_eq!(last_sender(&mut conn, &bob), Some(alice.account_id()));
+    }
+
+    #[test]
+    fn received_and_sent_lists_are_newest_first() {
+        let mut conn = db::test_connection();
+        let (alice, _) = users::record(&mut conn, "!60000003").expect("alice");
+        let (bob, _) = users::record(&mut conn, "!60000004").expect("bob");
+        let alice = users::set_dm_receipts(&mut conn, &alice, true).expect("receipts");
+        let first = post(&mut conn, &alice, &bob, "One").expect("message");
+        post(&mut conn, &alice, &bob, "Two").expect("message");
+        sent(&mut conn, &first);
+
+        let inbox = received(&mut conn, &bob, 10);
+        assert_eq!(inbox.len(), 2);
+        assert_eq!(inbox[0].body, "Two");
+        assert!(!inbox[0].is_delivered());
+        assert!(inbox[1].is_delivered());
+        assert_eq!(received(&mut conn, &bob, 1).len(), 1);
+
+        let outbox = sent_by(&mut conn, &alice, 10);
+        assert_eq!(outbox.len(), 2);
+        assert_eq!(outbox[0].body, "Two");
+        // Bob's receipt to Alice isn't something Bob sent.
+        assert!(sent_by(&mut conn, &bob, 10).is_empty());
+    }
+
+    #[test]
+    fn pending_counts_skip_receipts_and_delivered_messages() {
+        let mut conn = db::test_connection();
+        let (alice, _) = users::record(&mut conn, "!60000005").expect("alice");
+        let (bob, _) = users::record(&mut conn, "!60000006").expect("bob");
+        let (carol, _) = users::record(&mut conn, "!60000007").expect("carol");
+        let bob = users::set_dm_receipts(&mut conn, &bob, true).expect("receipts");
+        post(&mut conn, &alice, &carol, "One").expect("message");
+        post(&mut conn, &alice, &carol, "Two").expect("message");
+        let delivered = post(&mut conn, &bob, &carol, "Three").expect("message");
+        assert_eq!(
+            pending_count(&mut conn, Some(alice.account_id()), carol.account_id()),
+            2
+        );
+        assert_eq!(pending_count(&mut conn, None, carol.account_id()), 3);
+
+        sent(&mut conn, &delivered);
+        assert_eq!(pending_count(&mut conn, None, carol.account_id()), 2);
+        // Bob's receipt is waiting for him, but it doesn't count.
+        assert_eq!(get(&mut conn, &bob).len(), 1);
+        assert_eq!(pending_count(&mut conn, None, bob.account_id()), 0);
+    }
+
+    #[test]
+    fn expired_messages_notify_their_senders() {
+        let mut conn = db::test_connection();
+        let (alice, _) = users::record(&mut conn, "!60000008").expect("alice");
+        let (bob, _) = users::record(&mut conn, "!60000009").expect("bob");
+        post(&mut conn, &alice, &bob, "Are you there?").expect("message");
+
+        assert_eq!(expire(&mut conn, 3_600_000_000), 0);
+        std::thread::sleep(std::time::Duration::from_micros(10));
+        assert_eq!(expire(&mut conn, 1), 1);
+        assert!(get(&mut conn, &bob).is_empty());
+        assert!(received(&mut conn, &bob, 10).is_empty());
+        assert!(sent_by(&mut conn, &alice, 10)[0].is_expired());
+
+        let notices = get(&mut conn, &alice);
+        assert_eq!(notices.len(), 1);
+        assert!(notices[0].body.starts_with("Couldn't deliver your message"));
+        // It's from the BBS, not from Bob, and it isn't a delivery receipt.
+        assert!(notices[0].notice);
+        assert!(!notices[0].receipt);
+        assert_ne!(notices[0].sender_account_id, bob.account_id());
+        assert_eq!(last_sender(&mut conn, &alice), None);
+        assert_eq!(sent_by(&mut conn, &alice, 10).len(), 1);
+        assert_eq!(pending_count(&mut conn, None, alice.account_id()), 0);
+
+        // If the notice itself expires, that's the end of it.
+        std::thread::sleep(std::time::Duration::from_micros(10));
+        assert_eq!(expire(&mut conn, 1), 1);
+        assert!(get(&mut conn, &alice).is_empty());
+        assert!(get(&mut conn, &bob).is_empty());
+    }
 }
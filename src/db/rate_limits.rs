@@ -0,0 +1,53 @@
+use super::models::RateLimit;
+use super::schema::rate_limits::{dsl, table};
+use diesel::prelude::*;
+
+/// Get an account's rate limit state, if it's ever been checked.
+pub fn get(conn: &mut SqliteConnection, account_id: i32) -> Option<RateLimit> {
+    table
+        .select(RateLimit::as_select())
+        .filter(dsl::account_id.eq(account_id))
+        .first(conn)
+        .optional()
+        .expect("should always be possible to load a rate limit")
+}
+
+/// Save an account's rate limit state.
+pub fn save(conn: &mut SqliteConnection, rate_limit: &RateLimit) {
+    diesel::insert_into(table)
+        .values(rate_limit)
+        .on_conflict(dsl::account_id)
+        .do_update()
+        .set(rate_limit)
+        .execute(conn)
+        .expect("should always be possible to save a rate limit");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{self, users};
+
+    #[test]
+    fn save_and_update() {
+        let mut conn = db::test_connection();
+        let (user, _) = users::record(&mut conn, "!70000011").expect("user");
+        assert_eq!(get(&mut conn, user.account_id()), None);
+
+        let mut rate_limit = RateLimit {
+            account_id: user.account_id(),
+            tokens: 2.5,
+            updated_at_us: 1,
+            warned: false,
+            strikes: 0,
+            muted_until_us: Some(5),
+        };
+        save(&mut conn, &rate_limit);
+        assert_eq!(get(&mut conn, user.account_id()), Some(rate_limit.clone()));
+
+        rate_limit.tokens = 0.5;
+        rate_limit.muted_until_us = None;
+        save(&mut conn, &rate_limit);
+        assert_eq!(get(&mut conn, user.account_id()), Some(rate_limit));
+    }
+}
@@ -10,6 +10,37 @@ diesel::table! {
         last_acted_at_us -> Nullable<BigInt>,
         in_board -> Nullable<Integer>,
         invite_allowed -> Bool,
+        banned_until_us -> Nullable<BigInt>,
+        search_post_ids -> Nullable<Text>,
+        digest_enabled -> Bool,
+        dm_receipts -> Bool,
+        skip_ignored_posts -> Bool,
+    }
+}
+
+diesel::table! {
+    airtime (id) {
+        id -> Integer,
+        sent_at_us -> BigInt,
+        airtime_us -> BigInt,
+    }
+}
+
+diesel::table! {
+    announcements (name) {
+        name -> Text,
+        last_sent_at_us -> BigInt,
+    }
+}
+
+diesel::table! {
+    audit_log (id) {
+        id -> Integer,
+        created_at_us -> BigInt,
+        actor -> Text,
+        action -> Text,
+        target -> Nullable<Text>,
+        detail -> Text,
     }
 }
 
@@ -28,6 +59,18 @@ diesel::table! {
         name -> Text,
         description -> Text,
         created_at_us -> BigInt,
+        hidden -> Bool,
+        read_only -> Bool,
+        sysop_post -> Bool,
+    }
+}
+
+diesel::table! {
+    ignores (id) {
+        id -> Integer,
+        account_id -> Integer,
+        ignored_account_id -> Integer,
+        created_at_us -> BigInt,
     }
 }
 
@@ -43,6 +86,15 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    more_pages (id) {
+        id -> Integer,
+        account_id -> Integer,
+        body -> Text,
+        created_at_us -> BigInt,
+    }
+}
+
 diesel::table! {
     nodes (id) {
         id -> Integer,
@@ -62,6 +114,7 @@ diesel::table! {
         account_id -> Integer,
         body -> Text,
         created_at_us -> BigInt,
+        parent_id -> Nullable<Integer>,
     }
 }
 
@@ -73,6 +126,20 @@ diesel::table! {
         body -> Text,
         created_at_us -> BigInt,
         sent_at_us -> Nullable<BigInt>,
+        receipt -> Bool,
+        expired_at_us -> Nullable<BigInt>,
+        notice -> Bool,
+    }
+}
+
+diesel::table! {
+    rate_limits (account_id) {
+        account_id -> Integer,
+        tokens -> Double,
+        updated_at_us -> BigInt,
+        warned -> Bool,
+        strikes -> Integer,
+        muted_until_us -> Nullable<BigInt>,
     }
 }
 
@@ -81,16 +148,24 @@ diesel::joinable!(board_states -> accounts (account_id));
 diesel::joinable!(board_states -> boards (board_id));
 diesel::joinable!(invitations -> accounts (sender_account_id));
 diesel::joinable!(invitations -> nodes (invitee_node_id));
+diesel::joinable!(more_pages -> accounts (account_id));
 diesel::joinable!(nodes -> accounts (account_id));
 diesel::joinable!(posts -> accounts (account_id));
 diesel::joinable!(posts -> boards (board_id));
+diesel::joinable!(rate_limits -> accounts (account_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
     accounts,
+    airtime,
+    announcements,
+    audit_log,
     board_states,
     boards,
+    ignores,
     invitations,
+    more_pages,
     nodes,
     posts,
     queued_messages,
+    rate_limits,
 );
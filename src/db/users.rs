@@ -1,7 +1,7 @@
 use super::models::{Account, AccountNew, AccountUpdate, Node, NodeNew, NodeUpdate, User};
 use super::schema::accounts::{self, dsl as accounts_dsl};
 use super::schema::nodes::{self, dsl as nodes_dsl};
-use super::{now_as_useconds, Result};
+use super::{audit, now_as_useconds, Result};
 use diesel::prelude::*;
 use validator::Validate as _;
 
@@ -220,25 +220,52 @@ pub fn all(conn: &mut SqliteConnection) -> Vec<User> {
         .collect()
 }
 
-pub fn ban(conn: &mut SqliteConnection, user: &User) -> QueryResult<User> {
-    let account: Account = diesel::update(&user.account)
-        .set(accounts_dsl::jackass.eq(true))
-        .returning(Account::as_returning())
-        .get_result(conn)?;
-    Ok(User {
-        account,
-        node: user.node.clone(),
+/// Ban an account on behalf of `actor`. If `until` is given, the ban lifts itself at that
+/// timestamp.
+pub fn ban(
+    conn: &mut SqliteConnection,
+    actor: &str,
+    user: &User,
+    until: Option<i64>,
+) -> QueryResult<User> {
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        let account: Account = diesel::update(&user.account)
+            .set((
+                accounts_dsl::jackass.eq(true),
+                accounts_dsl::banned_until_us.eq(until),
+            ))
+            .returning(Account::as_returning())
+            .get_result(conn)?;
+        let detail = if until.is_some() {
+            format!("Until {}", account.banned_until())
+        } else {
+            "Indefinitely".to_string()
+        };
+        audit::record(conn, actor, "ban", Some(&user.node.node_id), &detail)
+            .expect("should be able to record the ban");
+        Ok(User {
+            account,
+            node: user.node.clone(),
+        })
     })
 }
 
-pub fn unban(conn: &mut SqliteConnection, user: &User) -> QueryResult<User> {
-    let account: Account = diesel::update(&user.account)
-        .set(accounts_dsl::jackass.eq(false))
-        .returning(Account::as_returning())
-        .get_result(conn)?;
-    Ok(User {
-        account,
-        node: user.node.clone(),
+/// Lift an account's ban on behalf of `actor`.
+pub fn unban(conn: &mut SqliteConnection, actor: &str, user: &User) -> QueryResult<User> {
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        let account: Account = diesel::update(&user.account)
+            .set((
+                accounts_dsl::jackass.eq(false),
+                accounts_dsl::banned_until_us.eq(None::<i64>),
+            ))
+            .returning(Account::as_returning())
+            .get_result(conn)?;
+        audit::record(conn, actor, "unban", Some(&user.node.node_id), "")
+            .expect("should be able to record the unban");
+        Ok(User {
+            account,
+            node: user.node.clone(),
+        })
     })
 }
 
@@ -302,6 +329,71 @@ pub fn get_by_short_name(conn: &mut SqliteConnection, short_name: &str) -> Optio
     }
 }
 
+/// Choose whether the user gets unread digests after being away.
+pub fn set_digest_enabled(
+    conn: &mut SqliteConnection,
+    user: &User,
+    digest_enabled: bool,
+) -> QueryResult<User> {
+    let account: Account = diesel::update(&user.account)
+        .set(accounts_dsl::digest_enabled.eq(digest_enabled))
+        .returning(Account::as_returning())
+        .get_result(conn)?;
+    Ok(User {
+        account,
+        node: user.node.clone(),
+    })
+}
+
+/// Choose whether the user gets told when their direct messages are delivered.
+pub fn set_dm_receipts(
+    conn: &mut SqliteConnection,
+    user: &User,
+    dm_receipts: bool,
+) -> QueryResult<User> {
+    let account: Account = diesel::update(&user.account)
+        .set(accounts_dsl::dm_receipts.eq(dm_receipts))
+        .returning(Account::as_returning())
+        .get_result(conn)?;
+    Ok(User {
+        account,
+        node: user.node.clone(),
+    })
+}
+
+/// Choose whether to skip posts by ignored accounts when reading new posts.
+pub fn set_skip_ignored_posts(
+    conn: &mut SqliteConnection,
+    user: &User,
+    skip_ignored_posts: bool,
+) -> QueryResult<User> {
+    let account: Account = diesel::update(&user.account)
+        .set(accounts_dsl::skip_ignored_posts.eq(skip_ignored_posts))
+        .returning(Account::as_returning())
+        .get_result(conn)?;
+    Ok(User {
+        account,
+        node: user.node.clone(),
+    })
+}
+
+/// Remember the post IDs from the user's latest search.
+pub fn set_search_results(
+    conn: &mut SqliteConnection,
+    user: &User,
+    post_ids: &[i32],
+) -> QueryResult<User> {
+    let post_ids: Vec<String> = post_ids.iter().map(ToString::to_string).collect();
+    let account: Account = diesel::update(&user.account)
+        .set(accounts_dsl::search_post_ids.eq(post_ids.join(",")))
+        .returning(Account::as_returning())
+        .get_result(conn)?;
+    Ok(User {
+        account,
+        node: user.node.clone(),
+    })
+}
+
 pub fn enter_board(conn: &mut SqliteConnection, user: &User, board_id: i32) -> QueryResult<User> {
     let account: Account = diesel::update(&user.account)
         .set(accounts_dsl::in_board.eq(board_id))
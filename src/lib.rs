@@ -1,11 +1,17 @@
 pub mod admin;
+pub mod airtime;
 pub mod client;
 pub mod commands;
 pub mod db;
 pub mod paginate;
+pub mod rate_limit;
 pub mod server;
+#[cfg(feature = "simulator")]
+pub mod simulator;
+use airtime::AirtimeConfig;
 use config::{Config, ConfigError, Map};
 use log::info;
+use rate_limit::RateLimitConfig;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
@@ -49,6 +55,26 @@ pub struct BBSConfig {
     #[serde(default)]
     pub menus: Map<String, MenuConfig>,
     pub page_delay_ms: Option<u64>,
+    #[serde(default)]
+    pub bans: BanConfig,
+    /// If set, new accounts start with only this many of the newest posts in each board unread.
+    pub new_account_unread: Option<usize>,
+    /// If set, accounts returning after this many hours away get a digest of what's new.
+    pub digest_idle_hours: Option<u32>,
+    #[serde(default)]
+    pub messages: MessageConfig,
+    /// If set, keep the BBS's transmissions under this duty cycle.
+    pub airtime: Option<AirtimeConfig>,
+    /// If set, limit how fast each account may run commands.
+    pub rate_limit: Option<RateLimitConfig>,
+    /// If set, only send the first page of long replies, and the rest as users ask for them.
+    pub more: Option<MoreConfig>,
+    /// If set, answer some commands sent to public channels.
+    pub public: Option<PublicConfig>,
+    /// If set, broadcast these announcements on a schedule.
+    pub schedule: Option<ScheduleConfig>,
+    /// If set, talk to the mesh through this MQTT broker instead of a radio.
+    pub mqtt: Option<MqttConfig>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -65,6 +91,258 @@ pub struct WeatherConfig {
     pub api_base: Option<String>,
 }
 
+/// What banned accounts may still do.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BanConfig {
+    /// Command functions, like "board::lister", that banned accounts may still run.
+    pub allowed_commands: Vec<String>,
+    /// The reply to a banned account trying to run any other command.
+    pub message: String,
+}
+
+impl Default for BanConfig {
+    fn default() -> Self {
+        Self {
+            allowed_commands: [
+                "board::author",
+                "board::catch_up",
+                "board::catch_up_all",
+                "board::current",
+                "board::enter",
+                "board::goto",
+                "board::headers",
+                "board::latest",
+                "board::lister",
+                "board::next",
+                "board::previous",
+                "board::quick",
+                "board::search",
+                "board::search_result",
+                "board::thread_next",
+                "board::thread_previous",
+                "dm::inbox",
+                "dm::outbox",
+                "dm::read",
+                "ping::ping",
+                "state::describe",
+                "state::more",
+                "user::active",
+                "user::bio_read",
+                "user::digest",
+                "user::ignore_list",
+                "user::name_read",
+                "user::seen",
+                "weather::current",
+            ]
+            .iter()
+            .map(ToString::to_string)
+            .collect(),
+            message: "Your account is not allowed to do that.".to_string(),
+        }
+    }
+}
+
+/// Limits on direct messages waiting to be delivered.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MessageConfig {
+    /// The most undelivered messages one account may have waiting for another.
+    pub max_pending_per_sender: i64,
+    /// The most undelivered messages an account may have waiting for it from everyone.
+    pub max_pending_per_recipient: i64,
+    /// Give up on delivering messages after this many hours.
+    pub expire_hours: u32,
+}
+
+impl Default for MessageConfig {
+    fn default() -> Self {
+        Self {
+            max_pending_per_sender: 10,
+            max_pending_per_recipient: 50,
+            expire_hours: 30 * 24,
+        }
+    }
+}
+
+/// Which commands anyone may run in a public channel, and how they ask.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PublicConfig {
+    /// Messages must start with this, like "!bbs PING", for the BBS to answer them.
+    pub prefix: String,
+    /// The channels to listen for commands on.
+    pub channels: Vec<u32>,
+    /// Command functions, like "ping::ping", that anyone may run this way.
+    pub allowed_commands: Vec<String>,
+}
+
+impl Default for PublicConfig {
+    fn default() -> Self {
+        Self {
+            prefix: "!bbs".to_string(),
+            channels: Vec::new(),
+            allowed_commands: ["board::latest", "ping::ping", "weather::current"]
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+        }
+    }
+}
+
+impl PublicConfig {
+    /// If this message was sent to one of our channels and starts with the prefix, get the
+    /// command after it.
+    pub fn command<'a>(&self, channel: u32, text: &'a str) -> Option<&'a str> {
+        if !self.channels.contains(&channel) {
+            return None;
+        }
+        let text = text.trim_start();
+        let prefix = text.get(..self.prefix.len())?;
+        let rest = &text[self.prefix.len()..];
+        if !prefix.eq_ignore_ascii_case(&self.prefix)
+            || !(rest.is_empty() || rest.starts_with(char::is_whitespace))
+        {
+            return None;
+        }
+        Some(rest.trim())
+    }
+}
+
+/// Announcements to broadcast every so often.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ScheduleConfig {
+    /// Don't broadcast anything from this local hour of the day...
+    pub quiet_start_hour: Option<u32>,
+    /// ...until this one.
+    pub quiet_end_hour: Option<u32>,
+    #[serde(default)]
+    pub announcements: Vec<AnnouncementConfig>,
+}
+
+impl ScheduleConfig {
+    /// Is this local hour of the day during quiet hours? They may wrap around midnight.
+    pub fn is_quiet(&self, hour: u32) -> bool {
+        match (self.quiet_start_hour, self.quiet_end_hour) {
+            (Some(start), Some(end)) if start <= end => (start..end).contains(&hour),
+            (Some(start), Some(end)) => hour >= start || hour < end,
+            _ => false,
+        }
+    }
+
+    /// Make sure the quiet hours are hours of the day, and every announcement can be sent.
+    pub fn check(&self) -> Result<(), String> {
+        for hour in [self.quiet_start_hour, self.quiet_end_hour]
+            .into_iter()
+            .flatten()
+        {
+            if hour > 23 {
+                return Err(format!(
+                    "schedule quiet hours must be from 0 to 23, not {hour}"
+                ));
+            }
+        }
+        self.announcements
+            .iter()
+            .try_for_each(AnnouncementConfig::check)
+    }
+}
+
+/// Something to broadcast every so often.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnnouncementConfig {
+    /// A unique name for remembering when this was last sent.
+    pub name: String,
+    /// What to send. "{ad}" is replaced with the BBS's advertisement, "{bbs_name}" with its name,
+    /// and "{posts_today}" with how many posts were written today.
+    pub text: String,
+    /// How long to wait between broadcasts.
+    pub interval_minutes: u32,
+    /// The channel to broadcast on. Defaults to the public channel.
+    pub channel: Option<u32>,
+}
+
+impl AnnouncementConfig {
+    /// An announcement with no interval would be sent every time the schedule is checked.
+    pub fn check(&self) -> Result<(), String> {
+        if self.interval_minutes == 0 {
+            return Err(format!(
+                "announcement {:?} must have an interval_minutes of at least 1",
+                self.name
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// How to hold the rest of long replies until users ask for more.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MoreConfig {
+    /// Forget pages nobody asked for after this many minutes.
+    pub expire_minutes: u32,
+}
+
+impl Default for MoreConfig {
+    fn default() -> Self {
+        Self { expire_minutes: 30 }
+    }
+}
+
+impl MoreConfig {
+    /// How long to hold pages, in microseconds.
+    pub fn expire_us(&self) -> i64 {
+        i64::from(self.expire_minutes) * 60_000_000
+    }
+}
+
+/// How to reach the mesh through an MQTT broker that gateway nodes relay packets through,
+/// instead of through a radio of our own.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MqttConfig {
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// The topic the gateways publish under, like "msh/US".
+    pub root_topic: String,
+    /// The short name the BBS announces itself to the mesh with.
+    pub short_name: String,
+    /// The mesh's channels, in order, so they have the same numbers they'd have on a radio.
+    pub channels: Vec<MqttChannel>,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            host: "localhost".to_string(),
+            port: 1883,
+            username: None,
+            password: None,
+            root_topic: "msh".to_string(),
+            short_name: "BBS".to_string(),
+            channels: vec![MqttChannel {
+                name: "LongFast".to_string(),
+                key: default_channel_key(),
+            }],
+        }
+    }
+}
+
+/// One of the mesh's channels.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MqttChannel {
+    pub name: String,
+    /// The channel's pre-shared key, in base64 like the Meshtastic apps show it.
+    #[serde(default = "default_channel_key")]
+    pub key: String,
+}
+
+fn default_channel_key() -> String {
+    "AQ==".to_string()
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CommandConfig {
     pub arg: String,
@@ -74,6 +352,51 @@ pub struct CommandConfig {
     pub func: String,
 }
 
+#[cfg(any(test, feature = "simulator"))]
+impl BBSConfig {
+    /// A configuration with none of the optional features turned on. Tests set what they need on
+    /// top of it with struct update syntax.
+    pub fn for_tests() -> Self {
+        Self {
+            bbs_name: "Test BBS".to_string(),
+            my_id: "!00000001".to_string(),
+            db_path: ":memory:".to_string(),
+            serial_device: None,
+            tcp_address: None,
+            sysops: Vec::new(),
+            public_channel: 0,
+            ad_text: String::new(),
+            weather: None,
+            menus: Map::new(),
+            page_delay_ms: None,
+            bans: BanConfig::default(),
+            new_account_unread: None,
+            digest_idle_hours: None,
+            messages: MessageConfig::default(),
+            airtime: None,
+            rate_limit: None,
+            more: None,
+            public: None,
+            schedule: None,
+            mqtt: None,
+        }
+    }
+}
+
+#[cfg(any(test, feature = "simulator"))]
+impl CommandConfig {
+    /// A menu command for tests, without any help text.
+    pub fn for_tests(arg: &str, pattern: &str, available: &str, func: &str) -> Self {
+        Self {
+            arg: arg.to_string(),
+            help: String::new(),
+            pattern: pattern.to_string(),
+            available: available.to_string(),
+            func: func.to_string(),
+        }
+    }
+}
+
 pub fn config_path() -> PathBuf {
     let xdg_dirs = xdg::BaseDirectories::with_prefix(BBS_TAG);
     xdg_dirs
@@ -126,6 +449,14 @@ pub fn config_load() -> Result<BBSConfig, ConfigError> {
         cfg.menus = menus_file.menus;
     }
 
+    if let Some(airtime) = &cfg.airtime {
+        airtime.check().map_err(ConfigError::Message)?;
+    }
+    if let Some(schedule) = &cfg.schedule {
+        schedule.check().map_err(ConfigError::Message)?;
+    }
+    check_mqtt_channels(&cfg).map_err(ConfigError::Message)?;
+
     if cfg.menus.is_empty() {
         eprintln!("Warning: No menus configured. Check config.toml or menus.toml.");
     }
@@ -133,6 +464,37 @@ pub fn config_load() -> Result<BBSConfig, ConfigError> {
     Ok(cfg)
 }
 
+/// Make sure every channel the BBS broadcasts on by itself is one MQTT knows how to reach.
+/// Otherwise, those pages would have nowhere to go.
+fn check_mqtt_channels(cfg: &BBSConfig) -> Result<(), String> {
+    let Some(mqtt) = &cfg.mqtt else {
+        return Ok(());
+    };
+    let mut channels = vec![("public_channel".to_string(), cfg.public_channel)];
+    if let Some(public) = &cfg.public {
+        for &channel in &public.channels {
+            channels.push(("[public] channels".to_string(), channel));
+        }
+    }
+    if let Some(schedule) = &cfg.schedule {
+        for announcement in &schedule.announcements {
+            channels.push((
+                format!("announcement '{}'", announcement.name),
+                announcement.channel.unwrap_or(cfg.public_channel),
+            ));
+        }
+    }
+    for (setting, channel) in channels {
+        if usize::try_from(channel).map_or(true, |index| index >= mqtt.channels.len()) {
+            return Err(format!(
+                "{setting} uses channel {channel}, but [mqtt] only lists {} channels",
+                mqtt.channels.len()
+            ));
+        }
+    }
+    Ok(())
+}
+
 /// Describe this system.
 pub fn system_info(cfg: &BBSConfig) -> String {
     format!(
@@ -145,6 +507,11 @@ pub fn system_info(cfg: &BBSConfig) -> String {
     )
 }
 
+/// Advertise this system.
+pub fn advertisement(cfg: &BBSConfig) -> Vec<String> {
+    vec![cfg.ad_text.clone(), String::new(), system_info(cfg)]
+}
+
 /// Add an empty line to the output.
 #[macro_export]
 macro_rules! linefeed {
@@ -154,6 +521,7 @@ macro_rules! linefeed {
 }
 
 #[cfg(test)]
+#[allow(clippy::unreadable_literal)]
 mod tests {
     use super::*;
 
@@ -164,19 +532,7 @@ mod tests {
 
     #[test]
     fn system_info_includes_bbs_name_and_build_metadata() {
-        let cfg = BBSConfig {
-            bbs_name: "Test BBS".to_string(),
-            my_id: "!00000001".to_string(),
-            db_path: "/tmp/frozenbbs-test.db".to_string(),
-            serial_device: None,
-            tcp_address: None,
-            sysops: Vec::new(),
-            public_channel: 0,
-            ad_text: String::new(),
-            weather: None,
-            menus: Map::new(),
-            page_delay_ms: None,
-        };
+        let cfg = BBSConfig::for_tests();
 
         let info = system_info(&cfg);
 
@@ -185,4 +541,78 @@ mod tests {
         assert!(info.contains(&env!("VERGEN_GIT_SHA")[..7]));
         assert!(info.contains(" built at "));
     }
+
+    #[test]
+    fn mqtt_must_list_every_channel_the_bbs_broadcasts_on() {
+        let mut cfg = BBSConfig {
+            mqtt: Some(MqttConfig::default()),
+            ..BBSConfig::for_tests()
+        };
+        assert!(check_mqtt_channels(&cfg).is_ok());
+        cfg.public = Some(PublicConfig {
+            channels: vec![0, 1],
+            ..PublicConfig::default()
+        });
+        assert!(check_mqtt_channels(&cfg).is_err());
+        cfg.public = None;
+        cfg.public_channel = 2;
+        assert!(check_mqtt_channels(&cfg).is_err());
+        // Without MQTT, the radio knows its own channels.
+        cfg.mqtt = None;
+        assert!(check_mqtt_channels(&cfg).is_ok());
+    }
+
+    #[test]
+    fn partial_ban_config_keeps_the_other_defaults() {
+        let bans: BanConfig = toml::from_str("message = \"Go away.\"").expect("bans");
+        assert_eq!(bans.message, "Go away.");
+        assert_eq!(bans.allowed_commands, BanConfig::default().allowed_commands);
+    }
+
+    #[test]
+    fn public_commands_need_the_prefix_on_a_listed_channel() {
+        let public = PublicConfig {
+            channels: vec![2],
+            ..PublicConfig::default()
+        };
+        assert_eq!(public.command(2, "!BBS ping "), Some("ping"));
+        assert_eq!(public.command(2, "!bbs"), Some(""));
+        assert_eq!(public.command(0, "!bbs ping"), None);
+        assert_eq!(public.command(2, "!bbsping"), None);
+        assert_eq!(public.command(2, "hello ❅"), None);
+    }
+
+    #[test]
+    fn quiet_hours_can_wrap_around_midnight() {
+        let mut schedule = ScheduleConfig {
+            quiet_start_hour: Some(22),
+            quiet_end_hour: Some(7),
+            announcements: Vec::new(),
+        };
+        assert!(schedule.is_quiet(23) && schedule.is_quiet(0) && schedule.is_quiet(6));
+        assert!(!schedule.is_quiet(7) && !schedule.is_quiet(12));
+        schedule.quiet_start_hour = Some(1);
+        schedule.quiet_end_hour = Some(5);
+        assert!(schedule.is_quiet(1) && !schedule.is_quiet(5) && !schedule.is_quiet(23));
+    }
+
+    #[test]
+    fn schedule_check_rejects_impossible_settings() {
+        let mut schedule = ScheduleConfig {
+            quiet_start_hour: Some(22),
+            quiet_end_hour: Some(7),
+            announcements: vec![AnnouncementConfig {
+                name: "ad".to_string(),
+                text: "{ad}".to_string(),
+                interval_minutes: 60,
+                channel: None,
+            }],
+        };
+        assert!(schedule.check().is_ok());
+        schedule.quiet_end_hour = Some(24);
+        assert!(schedule.check().is_err());
+        schedule.quiet_end_hour = Some(7);
+        schedule.announcements[0].interval_minutes = 0;
+        assert!(schedule.check().is_err());
+    }
 }
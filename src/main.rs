@@ -1,3 +1,4 @@
+use chrono::{Local, NaiveDate, NaiveDateTime, TimeZone};
 use clap::{ArgAction, Parser, Subcommand};
 use frozenbbs::{
     admin, canonical_node_id, client, config_load, config_path, db, default_db_path, menus_path,
@@ -55,6 +56,12 @@ enum Subsystems {
         #[command(subcommand)]
         user_command: Option<UserCommands>,
     },
+    /// Audit log commands
+    #[command(arg_required_else_help = true)]
+    Audit {
+        #[command(subcommand)]
+        audit_command: Option<AuditCommands>,
+    },
 }
 
 #[derive(Debug, Subcommand)]
@@ -99,6 +106,51 @@ enum BoardCommands {
         /// Description of the new board.
         #[arg(short, long)]
         description: String,
+        /// Only sysops can see the board.
+        #[arg(long)]
+        hidden: bool,
+        /// Nobody can post to the board over the radio.
+        #[arg(long)]
+        read_only: bool,
+        /// Only sysops can post to the board.
+        #[arg(long)]
+        sysop_post: bool,
+    },
+
+    /// Rename a board or change its description.
+    Edit {
+        /// Number of the board to edit.
+        #[arg(short, long)]
+        board_id: i32,
+        /// New name of the board.
+        #[arg(short, long)]
+        name: Option<String>,
+        /// New description of the board.
+        #[arg(short, long)]
+        description: Option<String>,
+    },
+
+    /// Delete a board and all of its posts.
+    Delete {
+        /// Number of the board to delete.
+        #[arg(short, long)]
+        board_id: i32,
+    },
+
+    /// Change who can read and write a board.
+    Permissions {
+        /// Number of the board to change.
+        #[arg(short, long)]
+        board_id: i32,
+        /// Only sysops can see the board.
+        #[arg(long)]
+        hidden: Option<bool>,
+        /// Nobody can post to the board over the radio.
+        #[arg(long)]
+        read_only: Option<bool>,
+        /// Only sysops can post to the board.
+        #[arg(long)]
+        sysop_post: Option<bool>,
     },
 }
 
@@ -123,6 +175,33 @@ enum PostCommands {
         #[arg(short, long)]
         content: String,
     },
+
+    /// Change a post's body.
+    Edit {
+        /// Number of the post to edit.
+        #[arg(short, long)]
+        post_id: i32,
+        /// New body of the post.
+        #[arg(short, long)]
+        content: String,
+    },
+
+    /// Move a post to another board.
+    Move {
+        /// Number of the post to move.
+        #[arg(short, long)]
+        post_id: i32,
+        /// Number of the board to move it to.
+        #[arg(short, long)]
+        to_board: i32,
+    },
+
+    /// Delete a post.
+    Delete {
+        /// Number of the post to delete.
+        #[arg(short, long)]
+        post_id: i32,
+    },
 }
 
 #[derive(Debug, Subcommand)]
@@ -146,6 +225,9 @@ enum UserCommands {
         /// User's node ID in !hex format
         #[arg(short, long)]
         node_id: String,
+        /// Lift the ban automatically after this many hours.
+        #[arg(long)]
+        hours: Option<u32>,
     },
     /// Unset the user's jackass bit.
     Unban {
@@ -155,7 +237,70 @@ enum UserCommands {
     },
 }
 
+#[derive(Debug, Subcommand)]
+enum AuditCommands {
+    /// List moderation and account actions.
+    List {
+        /// Only show actions by this node ID, or "admin" for the command line.
+        #[arg(short, long)]
+        actor: Option<String>,
+        /// Only show actions affecting this node ID.
+        #[arg(short, long)]
+        target: Option<String>,
+        /// Only show actions at or after this local time, like 2024-11-25 or 2024-11-25T04:34:47.
+        #[arg(short, long, value_parser = parse_local_time)]
+        since: Option<i64>,
+        /// Only show actions at or before this local time, like 2024-11-25 (through the end of that
+        /// day) or 2024-11-25T04:34:47.
+        #[arg(short, long, value_parser = parse_local_end_time)]
+        until: Option<i64>,
+    },
+}
+
+/// Convert a local date, or date and time, to microseconds since the Unix epoch. A date alone
+/// means the start of that day.
+fn parse_local_time(value: &str) -> Result<i64, String> {
+    local_time(value, false)
+}
+
+/// Like `parse_local_time`, but a date alone means the end of that day, so that it's included
+/// when it's the last day of a range.
+fn parse_local_end_time(value: &str) -> Result<i64, String> {
+    local_time(value, true)
+}
+
+/// The parser behind `parse_local_time` and `parse_local_end_time`.
+fn local_time(value: &str, end_of_day: bool) -> Result<i64, String> {
+    let invalid = || format!("'{value}' should look like 2024-11-25 or 2024-11-25T04:34:47");
+    let (naive, day_end) = match NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S") {
+        Ok(naive) => (naive, false),
+        Err(_) => {
+            let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| invalid())?;
+            // The day ends just before the next one starts.
+            let date = if end_of_day {
+                date.succ_opt().ok_or_else(invalid)?
+            } else {
+                date
+            };
+            let midnight = date.and_hms_opt(0, 0, 0).expect("midnight should exist");
+            (midnight, end_of_day)
+        }
+    };
+    let micros = Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|t| t.timestamp_micros())
+        .ok_or_else(|| format!("'{value}' isn't a valid local time"))?;
+    Ok(if day_end { micros - 1 } else { micros })
+}
+
+/// Node IDs are stored in canonical form, but other actors like "admin" are stored as-is.
+fn audit_party(party: Option<&String>) -> Option<String> {
+    party.map(|party| canonical_node_id(party).unwrap_or_else(|| party.clone()))
+}
+
 #[cfg(test)]
+#[allow(clippy::items_after_test_module)]
 mod tests {
     use super::*;
 
@@ -173,6 +318,117 @@ mod tests {
         assert_eq!(cli.verbose, 1);
         assert!(cli.whoami);
     }
+
+    #[test]
+    fn board_permissions_accepts_explicit_values() {
+        let cli = Cli::try_parse_from([
+            "frozenbbs",
+            "board",
+            "permissions",
+            "-b",
+            "2",
+            "--hidden",
+            "true",
+            "--sysop-post",
+            "false",
+        ])
+        .unwrap();
+        let Some(Subsystems::Board {
+            board_command:
+                Some(BoardCommands::Permissions {
+                    board_id,
+                    hidden,
+                    read_only,
+                    sysop_post,
+                }),
+        }) = cli.command
+        else {
+            panic!("should parse as a board permissions command");
+        };
+        assert_eq!(board_id, 2);
+        assert_eq!(hidden, Some(true));
+        assert_eq!(read_only, None);
+        assert_eq!(sysop_post, Some(false));
+    }
+
+    #[test]
+    fn audit_list_parses_time_filters() {
+        let cli = Cli::try_parse_from([
+            "frozenbbs",
+            "audit",
+            "list",
+            "--actor",
+            "admin",
+            "--since",
+            "2024-11-25",
+            "--until",
+            "2024-11-25T04:34:47",
+        ])
+        .unwrap();
+        let Some(Subsystems::Audit {
+            audit_command:
+                Some(AuditCommands::List {
+                    actor,
+                    target,
+                    since,
+                    until,
+                }),
+        }) = cli.command
+        else {
+            panic!("should parse as an audit list command");
+        };
+        assert_eq!(actor.as_deref(), Some("admin"));
+        assert_eq!(target, None);
+        assert_eq!(
+            until.unwrap() - since.unwrap(),
+            (4 * 3600 + 34 * 60 + 47) * 1_000_000
+        );
+        assert!(
+            Cli::try_parse_from(["frozenbbs", "audit", "list", "--since", "last week"]).is_err()
+        );
+    }
+
+    #[test]
+    fn audit_list_until_a_date_includes_that_day() {
+        let since = parse_local_time("2024-11-25").unwrap();
+        let until = parse_local_end_time("2024-11-25").unwrap();
+        assert_eq!(until - since, 24 * 3600 * 1_000_000 - 1);
+        // A time is used as-is.
+        assert_eq!(
+            parse_local_end_time("2024-11-25T04:34:47").unwrap(),
+            parse_local_time("2024-11-25T04:34:47").unwrap()
+        );
+    }
+
+    #[test]
+    fn post_move_takes_a_destination_board() {
+        let cli =
+            Cli::try_parse_from(["frozenbbs", "post", "move", "-p", "7", "--to-board", "3"])
+                .unwrap();
+        let Some(Subsystems::Post {
+            post_command: Some(PostCommands::Move { post_id, to_board }),
+        }) = cli.command
+        else {
+            panic!("should parse as a post move command");
+        };
+        assert_eq!(post_id, 7);
+        assert_eq!(to_board, 3);
+    }
+
+    #[test]
+    fn user_ban_accepts_optional_hours() {
+        let cli =
+            Cli::try_parse_from(["frozenbbs", "user", "ban", "-n", "!12345678", "--hours", "24"])
+                .unwrap();
+        let Some(Subsystems::User {
+            user_command: Some(UserCommands::Ban { node_id, hours }),
+        }) = cli.command
+        else {
+            panic!("should parse as a ban command");
+        };
+        assert_eq!(node_id, "!12345678");
+        assert_eq!(hours, Some(24));
+    }
 }
 
 /// The main command line handler.
@@ -270,9 +526,27 @@ Edit \"{}\" to set it to your radio's node ID.
         Some(Subsystems::Server {}) => server::event_loop(conn, &cfg).await.unwrap(),
         Some(Subsystems::Board { board_command }) => match board_command {
             Some(BoardCommands::List {}) => admin::board_list(conn),
-            Some(BoardCommands::Add { name, description }) => {
-                admin::board_add(conn, name, description);
+            Some(BoardCommands::Add {
+                name,
+                description,
+                hidden,
+                read_only,
+                sysop_post,
+            }) => {
+                admin::board_add(conn, name, description, *hidden, *read_only, *sysop_post);
             }
+            Some(BoardCommands::Edit {
+                board_id,
+                name,
+                description,
+            }) => admin::board_edit(conn, *board_id, name.as_deref(), description.as_deref()),
+            Some(BoardCommands::Delete { board_id }) => admin::board_delete(conn, *board_id),
+            Some(BoardCommands::Permissions {
+                board_id,
+                hidden,
+                read_only,
+                sysop_post,
+            }) => admin::board_permissions(conn, *board_id, *hidden, *read_only, *sysop_post),
             None => {}
         },
         Some(Subsystems::Config { .. }) => {} // Already handled this arm earlier.
@@ -283,6 +557,13 @@ Edit \"{}\" to set it to your radio's node ID.
                 node_id,
                 content,
             }) => admin::post_add(conn, *board_id, &default_or(node_id), content),
+            Some(PostCommands::Edit { post_id, content }) => {
+                admin::post_edit(conn, *post_id, content);
+            }
+            Some(PostCommands::Move { post_id, to_board }) => {
+                admin::post_move(conn, *post_id, *to_board);
+            }
+            Some(PostCommands::Delete { post_id }) => admin::post_delete(conn, *post_id),
             None => {}
         },
         Some(Subsystems::User { user_command }) => match user_command {
@@ -297,14 +578,29 @@ Edit \"{}\" to set it to your radio's node ID.
                 short_name.as_deref(),
                 long_name.as_deref(),
             ),
-            Some(UserCommands::Ban { node_id }) => {
-                admin::user_ban(conn, &canonical_node_id(node_id).unwrap());
+            Some(UserCommands::Ban { node_id, hours }) => {
+                admin::user_ban(conn, &canonical_node_id(node_id).unwrap(), *hours);
             }
             Some(UserCommands::Unban { node_id }) => {
                 admin::user_unban(conn, &canonical_node_id(node_id).unwrap());
             }
             None => {}
         },
+        Some(Subsystems::Audit { audit_command }) => match audit_command {
+            Some(AuditCommands::List {
+                actor,
+                target,
+                since,
+                until,
+            }) => admin::audit_list(
+                conn,
+                audit_party(actor.as_ref()).as_deref(),
+                audit_party(target.as_ref()).as_deref(),
+                *since,
+                *until,
+            ),
+            None => {}
+        },
         None => {}
     }
 }
@@ -4,6 +4,15 @@ fn footer(m: usize, n: usize) -> String {
     format!("\n\nPage {m}/{n}")
 }
 
+/// Like `footer`, but tell users how to get the next page when it isn't coming on its own.
+fn more_footer(m: usize, n: usize) -> String {
+    if m < n {
+        format!("\n\nPage {m}/{n}. Send MORE")
+    } else {
+        footer(m, n)
+    }
+}
+
 /// Remove excessive internal and trailing newlines from a string.
 ///
 /// No, this isn't super algorithmically efficient. That's A-OK here. First, it's unlikely that
@@ -31,13 +40,15 @@ fn shrink(text: &str) -> String {
     text
 }
 
-/// If some smartass makes a post that's longer than the maximum page size, carelessly slice that
-/// sucker up into ugly chunks.
+/// If some smartass makes a post that's longer than the maximum page size, slice that sucker up
+/// into chunks of at most `max_length` bytes. Break between words where we can, and between
+/// characters where we can't. Never break inside a character, though: Rust would rightly panic,
+/// and the radio would garble it anyway.
 pub fn splitted(text: &str, max_length: usize) -> Vec<String> {
     let mut out: Vec<String> = Vec::new();
-    // Text will already be shrunk and trimmed before it gets here, so don't bother with doing it
-    // again.
-    let mut text = text;
+    // Text will usually already be shrunk and trimmed before it gets here, but it's cheap to make
+    // sure no chunk starts with whitespace.
+    let mut text = text.trim_start();
     while !text.is_empty() {
         if text.len() <= max_length {
             // If we're lucky enough to coincidentally split between words, trim the space from
@@ -45,29 +56,98 @@ pub fn splitted(text: &str, max_length: usize) -> Vec<String> {
             out.push(text.trim_end().to_string());
             break;
         }
-        out.push(text[0..max_length].trim_end().to_string());
-        text = text[max_length..].trim_start();
+        let end = split_point(text, max_length);
+        out.push(text[..end].trim_end().to_string());
+        text = text[end..].trim_start();
     }
     out
 }
 
+/// Find the byte index to end a chunk of `text` at, so that the chunk is no longer than
+/// `max_length` bytes. `text` must be longer than that, and mustn't start with whitespace.
+fn split_point(text: &str, max_length: usize) -> usize {
+    let mut end = max_length;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    if end == 0 {
+        // Not even one character fits. Send it anyway rather than looping forever.
+        return text.chars().next().map_or(text.len(), char::len_utf8);
+    }
+    // The chunk ends right before a space, so it's already between words.
+    if text[end..].starts_with(char::is_whitespace) {
+        return end;
+    }
+    // Otherwise end it at the last space that fits, if there's one.
+    match text[..end].rfind(char::is_whitespace) {
+        Some(space) if space > 0 => space,
+        _ => end,
+    }
+}
+
 /// Do whatever we reasonably can to batch a series of lines into pages not longer than the
 /// maximum Meshtastic message size. If we generate multiple pages, number them so users can
 /// reorder them correctly.
+#[allow(clippy::needless_pass_by_value)]
 pub fn paginate(lines: Vec<String>, max_length: usize) -> Vec<String> {
-    let one_page = lines.join("\n");
+    paginate_with(&lines, max_length, footer)
+}
+
+/// Paginate lines for a reply where only the first page gets sent right away. Every page but the
+/// last tells the user to send MORE for the next one.
+#[allow(clippy::needless_pass_by_value)]
+pub fn paginate_more(lines: Vec<String>, max_length: usize) -> Vec<String> {
+    paginate_with(&lines, max_length, more_footer)
+}
+
+fn paginate_with(
+    lines: &[String],
+    max_length: usize,
+    footer: fn(usize, usize) -> String,
+) -> Vec<String> {
+    // Squeezing out extra whitespace might be enough to make it all fit on one page.
+    let one_page = shrink(&lines.join("\n"));
+    let one_page = one_page.trim_end();
     if one_page.len() <= max_length {
-        return vec![shrink(&one_page)];
+        return vec![one_page.to_string()];
+    }
+
+    // Leave room for the longest footer we'll need. That depends on how many pages there are,
+    // which we don't know until we're done, so guess there are fewer than 10 and try again with
+    // room for more digits if that was wrong.
+    let mut most_pages = 9;
+    loop {
+        let footer_length = footer(most_pages - 1, most_pages).len();
+        // If the footer won't even fit, there's no honoring the limit anyway. At least keep the
+        // text readable.
+        let page_length = match max_length.checked_sub(footer_length) {
+            Some(page_length) if page_length > 0 => page_length,
+            _ => max_length,
+        };
+        let mut pages = pages_of(lines, page_length);
+        let page_count = pages.len();
+        if page_count > most_pages {
+            most_pages = most_pages * 10 + 9;
+            continue;
+        }
+        // If we ended up with more than 1 page, add footers to the end of each.
+        if page_count > 1 {
+            for (i, buf) in pages.iter_mut().enumerate() {
+                buf.push_str(&footer(i + 1, page_count));
+            }
+        }
+        return pages;
     }
+}
 
+/// Batch lines into pages of at most `page_length` bytes, without footers.
+fn pages_of(lines: &[String], page_length: usize) -> Vec<String> {
     let mut buf = String::new();
     let mut pages = Vec::new();
 
-    let page_length = max_length - footer(9, 9).len();
-
     for line in lines {
         if buf.is_empty() {
-            buf.clone_from(&line);
+            buf.clone_from(line);
             continue;
         }
         // Before we consider creating a new page, ensure the current one isn't stuffed or tailed
@@ -76,28 +156,20 @@ pub fn paginate(lines: Vec<String>, max_length: usize) -> Vec<String> {
         if buf.len() + 1 + line.len() > page_length {
             // Pages never need to be trailed by whitespace.
             buf = buf.trim_end().to_string();
-            pages.extend(splitted(&buf, max_length));
+            pages.extend(splitted(&buf, page_length));
             // Start a new page with the incoming line.
-            buf.clone_from(&line);
+            buf.clone_from(line);
             continue;
         }
         // Add a new line to the current page.
         buf.push('\n');
-        buf.push_str(&line);
+        buf.push_str(line);
     }
 
     // Does the remaining buffer have anything other than whitespace? Add it as another page.
     buf = shrink(&buf).trim_end().to_string();
     if !buf.is_empty() {
-        pages.extend(splitted(&buf, max_length));
-    }
-
-    let page_count = pages.len();
-    // If we ended up with more than 1 page, add footers to the end of each.
-    if page_count > 1 {
-        for (i, buf) in pages.iter_mut().enumerate() {
-            buf.push_str(&footer(i + 1, page_count));
-        }
+        pages.extend(splitted(&buf, page_length));
     }
     pages
 }
@@ -105,6 +177,7 @@ pub fn paginate(lines: Vec<String>, max_length: usize) -> Vec<String> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
 
     #[test]
     fn single_page() {
@@ -120,6 +193,18 @@ mod tests {
         assert_eq!(pages, vec!["line1\n\nPage 1/2", "line2\n\nPage 2/2"]);
     }
 
+    #[test]
+    fn multi_page_more() {
+        let lines = vec!["a".repeat(150), "b".repeat(150)];
+        let pages = paginate_more(lines, MAX_LENGTH);
+        assert_eq!(pages.len(), 2);
+        assert!(pages[0].ends_with("\n\nPage 1/2. Send MORE"));
+        assert!(pages[1].ends_with("b\n\nPage 2/2"));
+        assert!(pages.iter().all(|page| page.len() <= MAX_LENGTH));
+        // Short replies don't need it.
+        assert_eq!(paginate_more(vec!["line1".to_string()], 30), vec!["line1"]);
+    }
+
     #[test]
     fn multi_page_trimmed() {
         let lines = vec![
@@ -164,4 +249,52 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn shard_between_words() {
+        let shards = splitted("the quick brown fox jumps", 10);
+        assert_eq!(shards, vec!["the quick", "brown fox", "jumps"]);
+    }
+
+    #[test]
+    fn shard_between_characters() {
+        // Each snowflake is 3 bytes, so a 10 byte limit falls in the middle of the fourth one.
+        let shards = splitted(&"❅".repeat(5), 10);
+        assert_eq!(shards, vec!["❅❅❅", "❅❅"]);
+    }
+
+    #[test]
+    fn many_pages_fit_their_footers() {
+        let lines: Vec<String> = (0..40)
+            .map(|i| format!("{i:02} {}", "x".repeat(60)))
+            .collect();
+        let pages = paginate(lines, MAX_LENGTH);
+        assert!(pages.len() >= 10);
+        assert!(pages.iter().all(|page| page.len() <= MAX_LENGTH));
+        assert!(pages[9].ends_with(&format!("Page 10/{}", pages.len())));
+    }
+
+    proptest! {
+        #[test]
+        fn shards_fit_and_keep_everything(text in "\\PC*", max_length in 4_usize..64) {
+            let shards = splitted(&text, max_length);
+            for shard in &shards {
+                prop_assert!(shard.len() <= max_length);
+                prop_assert!(!shard.is_empty());
+            }
+            let kept: String = shards.concat().split_whitespace().collect();
+            let given: String = text.split_whitespace().collect();
+            prop_assert_eq!(kept, given);
+        }
+
+        #[test]
+        fn pages_fit(lines in prop::collection::vec("\\PC{0,300}", 0..20)) {
+            for page in paginate(lines.clone(), MAX_LENGTH) {
+                prop_assert!(page.len() <= MAX_LENGTH, "{page:?}");
+            }
+            for page in paginate_more(lines, MAX_LENGTH) {
+                prop_assert!(page.len() <= MAX_LENGTH, "{page:?}");
+            }
+        }
+    }
 }
@@ -0,0 +1,202 @@
+use crate::db::{rate_limits, RateLimit};
+use diesel::SqliteConnection;
+use serde::{Deserialize, Serialize};
+
+/// How fast one kind of account may run commands.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct Limit {
+    /// How many commands an account may run in a quick burst.
+    pub burst: u32,
+    /// How many more commands an account earns each minute, up to `burst`.
+    pub per_minute: f64,
+}
+
+/// How fast accounts may run commands, and what happens when they keep going faster than that.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    pub users: Limit,
+    pub sysops: Limit,
+    /// Mute accounts that run out of commands this many times without letting up.
+    pub strikes_to_mute: i32,
+    /// How long muted accounts are ignored.
+    pub mute_minutes: u32,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            users: Limit {
+                burst: 5,
+                per_minute: 6.0,
+            },
+            sysops: Limit {
+                burst: 20,
+                per_minute: 60.0,
+            },
+            strikes_to_mute: 3,
+            mute_minutes: 15,
+        }
+    }
+}
+
+/// What to do with a command from an account.
+#[derive(Debug, PartialEq)]
+pub enum Verdict {
+    /// Run it.
+    Allow,
+    /// Don't run it, and tell them why.
+    Notify(String),
+    /// Don't run it, and don't say anything. They've already been told.
+    Ignore,
+}
+
+/// Spend one of the account's commands, if it has any left, and decide what to do with the
+/// command it just sent.
+#[allow(clippy::cast_precision_loss)]
+pub fn check(
+    conn: &mut SqliteConnection,
+    cfg: &RateLimitConfig,
+    account_id: i32,
+    sysop: bool,
+    now_us: i64,
+) -> Verdict {
+    let limit = if sysop { cfg.sysops } else { cfg.users };
+    let burst = f64::from(limit.burst);
+    let mut state = rate_limits::get(conn, account_id).unwrap_or(RateLimit {
+        account_id,
+        tokens: burst,
+        updated_at_us: now_us,
+        warned: false,
+        strikes: 0,
+        muted_until_us: None,
+    });
+    if state.muted_until_us.is_some_and(|until| until > now_us) {
+        return Verdict::Ignore;
+    }
+    state.muted_until_us = None;
+
+    let minutes = (now_us - state.updated_at_us).max(0) as f64 / 60_000_000.0;
+    state.tokens = (state.tokens + minutes * limit.per_minute).min(burst);
+    state.updated_at_us = now_us;
+    // Once they've let up long enough to get all their commands back, forgive them.
+    if state.tokens >= burst {
+        state.strikes = 0;
+    }
+
+    let verdict = if state.tokens >= 1.0 {
+        state.tokens -= 1.0;
+        state.warned = false;
+        Verdict::Allow
+    } else if state.warned {
+        Verdict::Ignore
+    } else {
+        state.warned = true;
+        state.strikes += 1;
+        if state.strikes >= cfg.strikes_to_mute {
+            state.strikes = 0;
+            state.muted_until_us = Some(now_us + i64::from(cfg.mute_minutes) * 60_000_000);
+            Verdict::Notify(format!(
+                "You're sending commands too fast. Ignoring you for {} minutes.",
+                cfg.mute_minutes
+            ))
+        } else {
+            Verdict::Notify("Slow down! You're sending commands too fast.".to_string())
+        }
+    };
+    rate_limits::save(conn, &state);
+    verdict
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{self, users};
+
+    const MINUTE: i64 = 60_000_000;
+
+    fn config() -> RateLimitConfig {
+        RateLimitConfig {
+            users: Limit {
+                burst: 2,
+                per_minute: 1.0,
+            },
+            sysops: Limit {
+                burst: 4,
+                per_minute: 1.0,
+            },
+            strikes_to_mute: 2,
+            mute_minutes: 10,
+        }
+    }
+
+    #[test]
+    fn bursts_are_allowed_then_refill() {
+        let mut conn = db::test_connection();
+        let (user, _) = users::record(&mut conn, "!70000021").expect("user");
+        let id = user.account_id();
+        let cfg = config();
+
+        assert_eq!(check(&mut conn, &cfg, id, false, 0), Verdict::Allow);
+        assert_eq!(check(&mut conn, &cfg, id, false, 0), Verdict::Allow);
+        assert!(matches!(
+            check(&mut conn, &cfg, id, false, 0),
+            Verdict::Notify(_)
+        ));
+        // They only get told once.
+        assert_eq!(check(&mut conn, &cfg, id, false, 0), Verdict::Ignore);
+        assert_eq!(check(&mut conn, &cfg, id, false, MINUTE), Verdict::Allow);
+        assert!(matches!(
+            check(&mut conn, &cfg, id, false, MINUTE),
+            Verdict::Notify(_)
+        ));
+    }
+
+    #[test]
+    fn sysops_get_more_room() {
+        let mut conn = db::test_connection();
+        let (user, _) = users::record(&mut conn, "!70000022").expect("user");
+        let cfg = config();
+        for _ in 0..4 {
+            assert_eq!(
+                check(&mut conn, &cfg, user.account_id(), true, 0),
+                Verdict::Allow
+            );
+        }
+        assert_ne!(
+            check(&mut conn, &cfg, user.account_id(), true, 0),
+            Verdict::Allow
+        );
+    }
+
+    #[test]
+    fn repeat_offenders_are_muted() {
+        let mut conn = db::test_connection();
+        let (user, _) = users::record(&mut conn, "!70000023").expect("user");
+        let id = user.account_id();
+        let cfg = config();
+
+        check(&mut conn, &cfg, id, false, 0);
+        check(&mut conn, &cfg, id, false, 0);
+        let Verdict::Notify(first) = check(&mut conn, &cfg, id, false, 0) else {
+            panic!("should be told to slow down");
+        };
+        assert!(first.starts_with("Slow down!"));
+        // A token trickles back in and they spend it right away.
+        assert_eq!(check(&mut conn, &cfg, id, false, MINUTE), Verdict::Allow);
+        let Verdict::Notify(second) = check(&mut conn, &cfg, id, false, MINUTE) else {
+            panic!("should be muted");
+        };
+        assert!(second.contains("10 minutes"), "{second}");
+
+        // They're ignored even after they'd have earned commands back, until the mute ends.
+        assert_eq!(
+            check(&mut conn, &cfg, id, false, 5 * MINUTE),
+            Verdict::Ignore
+        );
+        assert_eq!(
+            check(&mut conn, &cfg, id, false, 12 * MINUTE),
+            Verdict::Allow
+        );
+    }
+}
@@ -1,22 +1,38 @@
 use crate::{
-    client::dispatch,
+    airtime,
+    client::{dispatch, dispatch_public},
     commands::{self, Replies, ReplyDestination},
-    db::{queued_messages, stats, users},
+    db::{self, more_pages, queued_messages, stats, users},
     hex_id_to_num, num_id_to_hex,
-    paginate::{paginate, MAX_LENGTH},
+    paginate::{paginate, paginate_more, MAX_LENGTH},
     system_info, BBSConfig,
 };
+use chrono::Local;
 use diesel::SqliteConnection;
 use meshtastic::{
     self,
-    api::StreamApi,
+    api::{ConnectedStreamApi, StreamApi, StreamHandle},
     packet::{PacketDestination, PacketRouter},
-    protobufs::{from_radio, mesh_packet, FromRadio, MapReport, MeshPacket, PortNum, User},
+    protobufs::{
+        from_radio, mesh_packet, routing, FromRadio, MapReport, MeshPacket, PortNum, Routing, User,
+    },
     types::NodeId,
     utils, Message,
 };
 use std::error::Error;
 use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::mpsc::UnboundedReceiver;
+
+mod acks;
+mod mqtt;
+mod outbox;
+mod reconnect;
+mod schedule;
+use acks::{Page, Tracker};
+use outbox::{Outbox, Outgoing};
+use reconnect::Backoff;
 
 // A simple error type
 #[derive(Debug)]
@@ -36,6 +52,8 @@ pub struct HandlerMetadata {}
 // Your packet router implementation
 pub struct TestPacketRouter {
     pub my_id: NodeId,
+    /// The ID of the last packet we sent, which the stream API echoes back to us here.
+    pub last_sent_id: Option<u32>,
 }
 
 impl PacketRouter<HandlerMetadata, TestRouterError> for TestPacketRouter {
@@ -59,6 +77,7 @@ impl PacketRouter<HandlerMetadata, TestRouterError> for TestPacketRouter {
         if self.my_id == packet.to {
             panic!("I got tricked into messaging myself. I'd rather panic than blue up the radio.");
         }
+        self.last_sent_id = Some(packet.id);
 
         Ok(HandlerMetadata {})
     }
@@ -74,18 +93,39 @@ impl PacketRouter<HandlerMetadata, TestRouterError> for TestPacketRouter {
 #[derive(Debug)]
 struct Response {
     sender: u32,
+    /// The channel the packet came in on, which replies go back out on.
+    channel: u32,
+    /// Whether the command was sent to a public channel, so everyone there should see the reply.
+    public: bool,
     replies: Option<Replies>,
+    routed: Option<Routed>,
 }
 
+/// What the mesh told us about a packet we sent.
+#[derive(Debug)]
+struct Routed {
+    request_id: u32,
+    delivered: bool,
+}
+
+/// The node ID packets to everyone are sent to.
+const BROADCAST_ID: u32 = 0xffff_ffff;
+/// How often to check for scheduled announcements that are due.
+const SCHEDULE_CHECK: Duration = Duration::from_secs(60);
+/// How often to give up on undelivered messages and unrequested pages that have waited too long.
+const EXPIRY_CHECK: Duration = Duration::from_secs(60);
+/// How often to check for pages that need to be sent again.
+const RETRY_CHECK: Duration = Duration::from_secs(5);
+/// How long to wait before checking again whether there's airtime to send a held page.
+const BUDGET_RECHECK: Duration = Duration::from_secs(10);
+/// Keep at least an hour of airtime history for the stats.
+const AIRTIME_HISTORY_US: i64 = 3_600_000_000;
+
 pub async fn event_loop(
     conn: &mut SqliteConnection,
     cfg: &BBSConfig,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let commands = commands::command_structure(cfg);
-    let stream_api = StreamApi::new();
-
-    let connected_stream_api;
-    let mut decoded_listener;
 
     eprintln!(
         "\
@@ -99,109 +139,492 @@ Startup stats:
         stats(conn)
     );
 
-    if let Some(tcp_address) = &cfg.tcp_address {
-        log::info!("Connecting to {tcp_address}");
-        let stream = utils::stream::build_tcp_stream(tcp_address.clone()).await?;
-        (decoded_listener, connected_stream_api) = stream_api.connect(stream).await;
+    // Pages waiting to go out, or to be acknowledged, survive losing the radio for a while.
+    let mut tracker = Tracker::default();
+    let mut outbox = Outbox::default();
+    let mut backoff = Backoff::default();
+
+    // Keep the radio connected for as long as we're running. USB cables wiggle loose and radios
+    // reboot, and the BBS shouldn't stay offline until someone notices.
+    loop {
+        let (mut decoded_listener, link) = match connect(cfg).await {
+            Ok(connection) => connection,
+            Err(err) => {
+                let delay = backoff.next_delay();
+                log::error!(
+                    "Radio link: couldn't connect ({err}). Retrying in {}s",
+                    delay.as_secs()
+                );
+                tokio::time::sleep(delay).await;
+                continue;
+            }
+        };
+        log::info!("Radio link: connected");
+        let connected_at = Instant::now();
+
+        (tracker, outbox) = session(
+            conn,
+            cfg,
+            &commands,
+            link,
+            &mut decoded_listener,
+            (tracker, outbox),
+        )
+        .await;
+
+        backoff.connected_for(connected_at.elapsed());
+        let delay = backoff.next_delay();
+        log::info!("Radio link: reconnecting in {}s", delay.as_secs());
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Open the link to the radio, or the MQTT broker standing in for one, and ask it to start
+/// sending us packets.
+async fn connect(
+    cfg: &BBSConfig,
+) -> Result<(UnboundedReceiver<FromRadio>, Link), Box<dyn std::error::Error>> {
+    if let Some(mqtt) = &cfg.mqtt {
+        log::info!(
+            "Radio link: connecting to MQTT broker {}:{}",
+            mqtt.host,
+            mqtt.port
+        );
+        let my_id = hex_id_to_num(&cfg.my_id).unwrap();
+        let (decoded_listener, link) = mqtt::connect(mqtt, my_id, &cfg.bbs_name).await?;
+        return Ok((decoded_listener, Link::Mqtt(link)));
+    }
+    let (decoded_listener, stream_api) = if let Some(tcp_address) = &cfg.tcp_address {
+        log::info!("Radio link: connecting to {tcp_address}");
+        open(utils::stream::build_tcp_stream(tcp_address.clone()).await?).await?
     } else if let Some(serial_device) = &cfg.serial_device {
-        log::info!("Connecting to {serial_device}");
-        let stream = utils::stream::build_serial_stream(serial_device.clone(), None, None, None)?;
-        (decoded_listener, connected_stream_api) = stream_api.connect(stream).await;
+        log::info!("Radio link: connecting to {serial_device}");
+        open(utils::stream::build_serial_stream(
+            serial_device.clone(),
+            None,
+            None,
+            None,
+        )?)
+        .await?
     } else {
-        panic!("At least one of tcp_address and serial_device must be configured.");
-    }
+        panic!("At least one of mqtt, tcp_address, and serial_device must be configured.");
+    };
+    Ok((decoded_listener, Link::Radio(stream_api)))
+}
 
+/// Start talking to whatever radio is on the other end of this stream.
+async fn open<S>(
+    stream: StreamHandle<S>,
+) -> Result<(UnboundedReceiver<FromRadio>, ConnectedStreamApi), Box<dyn std::error::Error>>
+where
+    S: AsyncReadExt + AsyncWriteExt + Send + 'static,
+{
+    let (decoded_listener, connected_stream_api) = StreamApi::new().connect(stream).await;
     let config_id = utils::generate_rand_id();
-    let mut stream_api = connected_stream_api.configure(config_id).await?;
+    let stream_api = connected_stream_api.configure(config_id).await?;
+    Ok((decoded_listener, stream_api))
+}
 
+/// Run the BBS over a single radio link, like the one from a simulator, until it drops. Unlike
+/// `event_loop`, this doesn't try to reconnect afterward.
+#[cfg(feature = "simulator")]
+pub async fn serve_stream<S>(
+    conn: &mut SqliteConnection,
+    cfg: &BBSConfig,
+    stream: StreamHandle<S>,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    S: AsyncReadExt + AsyncWriteExt + Send + 'static,
+{
+    let commands = commands::command_structure(cfg);
+    let (mut decoded_listener, stream_api) = open(stream).await?;
+    session(
+        conn,
+        cfg,
+        &commands,
+        Link::Radio(stream_api),
+        &mut decoded_listener,
+        (Tracker::default(), Outbox::default()),
+    )
+    .await;
+    Ok(())
+}
+
+/// Serve one connection to the radio until it drops, then hand back the pages still waiting to
+/// be sent or acknowledged so the next connection can pick them up.
+async fn session(
+    conn: &mut SqliteConnection,
+    cfg: &BBSConfig,
+    commands: &commands::Menus,
+    link: Link,
+    decoded_listener: &mut UnboundedReceiver<FromRadio>,
+    (tracker, outbox): (Tracker, Outbox),
+) -> (Tracker, Outbox) {
     let my_id = hex_id_to_num(&cfg.my_id).unwrap();
-    let mut router = TestPacketRouter {
-        my_id: my_id.into(),
+    let mut radio = Radio {
+        link,
+        router: TestPacketRouter {
+            my_id: my_id.into(),
+            last_sent_id: None,
+        },
+        tracker,
+        outbox,
+        next_send_at: Instant::now(),
     };
+    match serve(conn, cfg, commands, &mut radio, decoded_listener, my_id).await {
+        Ok(()) => log::warn!("Radio link: lost"),
+        Err(err) => log::warn!("Radio link: lost ({err})"),
+    }
+    let Radio {
+        link,
+        tracker,
+        outbox,
+        ..
+    } = radio;
+    link.disconnect().await;
+    (tracker, outbox)
+}
 
-    while let Some(decoded) = decoded_listener.recv().await {
-        let Some(response) = handle_packet(conn, cfg, &commands, decoded, my_id) else {
-            continue;
-        };
-
-        // Send any replies from the commands the user executed.
-        if let Some(replies) = response.replies {
-            for reply in replies.0 {
-                let (channel, destination, recipient) = match reply.destination {
-                    ReplyDestination::Sender => (
-                        0,
-                        PacketDestination::Node(NodeId::new(response.sender)),
-                        num_id_to_hex(response.sender),
-                    ),
-                    ReplyDestination::Broadcast => (
-                        cfg.public_channel,
-                        PacketDestination::Broadcast,
-                        "<broadcast>".to_string(),
-                    ),
+/// Handle packets from the radio until the link drops.
+async fn serve(
+    conn: &mut SqliteConnection,
+    cfg: &BBSConfig,
+    commands: &commands::Menus,
+    radio: &mut Radio,
+    decoded_listener: &mut UnboundedReceiver<FromRadio>,
+    my_id: u32,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut retry_timer = tokio::time::interval(RETRY_CHECK);
+    let mut schedule_timer = tokio::time::interval(SCHEDULE_CHECK);
+    let mut expiry_timer = tokio::time::interval(EXPIRY_CHECK);
+
+    // Handle incoming packets as they arrive, and send replies out one page at a time in between.
+    // That way, nobody waits for the whole of someone else's long reply to go out before we'll
+    // even read their command.
+    loop {
+        let send_at = tokio::time::Instant::from_std(radio.next_send_at);
+        tokio::select! {
+            decoded = decoded_listener.recv() => {
+                let Some(decoded) = decoded else {
+                    break;
+                };
+                let Some(response) = handle_packet(conn, cfg, commands, decoded, my_id) else {
+                    continue;
                 };
-                for page in paginate(reply.out, MAX_LENGTH) {
-                    log::info!("Replying to {recipient} at {channel}:\n\n\"\"\"\n{page}\n\"\"\"");
-                    stream_api
-                        .send_text(&mut router, page, destination, true, channel.into())
-                        .await?;
-                    if let Some(delay_ms) = cfg.page_delay_ms {
-                        if delay_ms > 0 {
-                            tokio::time::sleep(std::time::Duration::from_millis(delay_ms)).await;
-                        }
+                respond(conn, cfg, radio, response);
+            }
+            _ = retry_timer.tick() => {
+                let (retries, abandoned) = radio.tracker.due(Instant::now());
+                for (page, attempts) in retries {
+                    log::info!(
+                        "Resending to {} (attempt {})",
+                        num_id_to_hex(page.destination),
+                        attempts + 1
+                    );
+                    radio.outbox.push(Outgoing::Direct { page, attempts });
+                }
+                for message_id in abandoned {
+                    radio.outbox.drop_message(message_id);
+                }
+            }
+            _ = schedule_timer.tick() => {
+                for (channel, out) in schedule::due(conn, cfg, Local::now()) {
+                    for text in paginate(out, MAX_LENGTH) {
+                        log::info!("Announcing at {channel}:\n\n\"\"\"\n{text}\n\"\"\"");
+                        radio.outbox.push(Outgoing::Broadcast { channel, text });
                     }
                 }
             }
+            _ = expiry_timer.tick() => {
+                expire(conn, cfg);
+            }
+            () = tokio::time::sleep_until(send_at), if !radio.outbox.is_empty() => {
+                radio.send_next(conn, cfg).await?;
+            }
         }
+    }
 
-        // Next, send any queued messages to the user.
-        // We send to the specific node that just connected (response.sender), not necessarily
-        // the "primary" node for the account. This way, if an account has multiple nodes,
-        // messages are delivered to whichever node is currently active.
+    Ok(())
+}
 
-        let node_id = num_id_to_hex(response.sender);
-        let Ok(user) = users::get(conn, &node_id) else {
-            // This should never happen because we should've upserted the user before calling this.
-            log::debug!("No user matching {node_id}");
-            continue;
-        };
+/// Give up on messages and pages that have waited too long for anyone to want them.
+fn expire(conn: &mut SqliteConnection, cfg: &BBSConfig) {
+    let expired =
+        queued_messages::expire(conn, i64::from(cfg.messages.expire_hours) * 3_600_000_000);
+    if expired > 0 {
+        log::info!("Expired {expired} undelivered messages");
+    }
+    if let Some(more) = &cfg.more {
+        let expired = more_pages::expire(conn, more.expire_us());
+        if expired > 0 {
+            log::info!("Forgot {expired} pages nobody asked for");
+        }
+    }
+}
 
-        let queue = queued_messages::get(conn, &user);
-        if queue.is_empty() {
-            log::debug!("No unsent messages for {}", user.account_id());
+/// How we reach the mesh.
+enum Link {
+    /// A radio of our own, over serial or TCP.
+    Radio(ConnectedStreamApi),
+    /// An MQTT broker that gateway nodes relay packets through.
+    Mqtt(mqtt::Link),
+}
+
+impl Link {
+    async fn disconnect(self) {
+        match self {
+            Link::Radio(stream_api) => {
+                if let Err(err) = stream_api.disconnect().await {
+                    log::debug!("Radio link: error while closing it: {err}");
+                }
+            }
+            Link::Mqtt(link) => link.disconnect().await,
         }
+    }
+}
 
-        for message in queue {
-            // Look up the sender's account to display their name. Note: if the sender has
-            // multiple nodes, we show their username (or first node's long_name as fallback).
-            let Ok(sender) = users::get_by_account_id(conn, message.sender_account_id) else {
-                log::error!("Unknown sender account_id: {}", message.sender_account_id);
-                continue;
-            };
-            log::info!("Sending a queued message from {sender} to {user}");
-            // Construct the message body.
-            let out = vec![
-                format!("Message from {} at {}:", sender, message.created_at(),),
-                String::new(),
-                message.body.to_string(),
-            ];
-            // Send to the node that just connected, not user.node_id_numeric() which might
-            // be a different node if the account has multiple.
-            let destination = PacketDestination::Node(NodeId::new(response.sender));
-            for page in paginate(out, MAX_LENGTH) {
+/// The connection to the radio, and what we remember about what we've sent through it.
+struct Radio {
+    link: Link,
+    router: TestPacketRouter,
+    tracker: Tracker,
+    outbox: Outbox,
+    /// Don't send the next page before this.
+    next_send_at: Instant,
+}
+
+impl Radio {
+    /// Send a text packet, and return its ID if we know it.
+    async fn send_text(
+        &mut self,
+        destination: u32,
+        channel: u32,
+        text: String,
+    ) -> Result<Option<u32>, Box<dyn std::error::Error>> {
+        match &mut self.link {
+            Link::Radio(stream_api) => {
+                let destination = if destination == BROADCAST_ID {
+                    PacketDestination::Broadcast
+                } else {
+                    PacketDestination::Node(NodeId::new(destination))
+                };
                 stream_api
-                    .send_text(&mut router, page, destination, true, 0.into())
+                    .send_text(&mut self.router, text, destination, true, channel.into())
                     .await?;
-                if let Some(delay_ms) = cfg.page_delay_ms {
-                    if delay_ms > 0 {
-                        tokio::time::sleep(std::time::Duration::from_millis(delay_ms)).await;
+                Ok(self.router.last_sent_id.take())
+            }
+            Link::Mqtt(link) => Ok(Some(link.send_text(destination, channel, text).await?)),
+        }
+    }
+
+    /// Send the next page waiting in the outbox, unless that would put us over the airtime
+    /// budget. Pages to one node are tracked until they're acknowledged.
+    async fn send_next(
+        &mut self,
+        conn: &mut SqliteConnection,
+        cfg: &BBSConfig,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(outgoing) = self.outbox.peek() else {
+            return Ok(());
+        };
+        let preset = cfg
+            .airtime
+            .as_ref()
+            .map(|budget| budget.modem_preset)
+            .unwrap_or_default();
+        let airtime_us = airtime::estimate_us(preset, outgoing.text());
+        if let Some(budget) = &cfg.airtime {
+            let used_us = db::airtime::used(conn, budget.window_us());
+            if used_us + airtime_us > budget.allowed_us() {
+                log::info!(
+                    "Holding replies: {:.1}s of the {:.1}s airtime budget is used",
+                    used_us as f64 / 1_000_000.0,
+                    budget.allowed_us() as f64 / 1_000_000.0
+                );
+                self.next_send_at = Instant::now() + BUDGET_RECHECK;
+                return Ok(());
+            }
+        }
+        let Some(outgoing) = self.outbox.pop() else {
+            return Ok(());
+        };
+        let sent = match outgoing {
+            Outgoing::Direct { page, attempts } => self
+                .send_text(page.destination, page.channel, page.text.clone())
+                .await
+                .map(|packet_id| match packet_id {
+                    Some(packet_id) => {
+                        self.tracker
+                            .sent(packet_id, page, attempts + 1, Instant::now());
                     }
+                    None => log::warn!("Didn't see the ID of the packet we just sent"),
+                }),
+            Outgoing::Broadcast { channel, text } => self
+                .send_text(BROADCAST_ID, channel, text)
+                .await
+                .map(|_| ()),
+        };
+        if let Err(err) = sent {
+            // A page we can't send shouldn't take the whole link down with it.
+            if err.is::<mqtt::UnknownChannel>() {
+                log::error!("Dropping a page: {err}");
+                return Ok(());
+            }
+            return Err(err);
+        }
+        db::airtime::record(conn, airtime_us);
+        db::airtime::prune(
+            conn,
+            cfg.airtime
+                .as_ref()
+                .map_or(0, airtime::AirtimeConfig::window_us)
+                .max(AIRTIME_HISTORY_US),
+        );
+        self.next_send_at = Instant::now() + page_delay(cfg);
+        Ok(())
+    }
+}
+
+/// How long to give the mesh between pages.
+fn page_delay(cfg: &BBSConfig) -> Duration {
+    Duration::from_millis(cfg.page_delay_ms.unwrap_or(0))
+}
+
+/// Act on a packet we received: note any acknowledgements, and queue up the replies to any
+/// commands and any messages waiting for the sender.
+fn respond(conn: &mut SqliteConnection, cfg: &BBSConfig, radio: &mut Radio, response: Response) {
+    // First, see if this is news about something we sent.
+    if let Some(routed) = response.routed {
+        if routed.delivered {
+            // It's only delivered once every page has been sent and acknowledged.
+            if let Some(message_id) = radio
+                .tracker
+                .acked(routed.request_id, response.sender)
+                .filter(|&message_id| !radio.outbox.has_message(message_id))
+            {
+                log::info!("Queued message {message_id} was delivered");
+                if let Ok(message) = queued_messages::find(conn, message_id) {
+                    queued_messages::sent(conn, &message);
                 }
             }
-            queued_messages::sent(conn, &message);
+        } else {
+            log::info!("Packet {} wasn't delivered", routed.request_id);
+            radio.tracker.nacked(routed.request_id, Instant::now());
         }
     }
 
-    Ok(())
+    // Send any replies from the commands the user executed.
+    if let Some(replies) = response.replies {
+        // The pages the user has to ask for, if we're only sending the first page of each reply.
+        let mut later = Vec::new();
+        for reply in replies.0 {
+            match reply.destination {
+                // Replies to commands in a public channel go back to everyone there.
+                ReplyDestination::Sender if response.public => {
+                    for text in paginate(reply.out, MAX_LENGTH) {
+                        log::info!(
+                            "Replying to <broadcast> at {}:\n\n\"\"\"\n{text}\n\"\"\"",
+                            response.channel
+                        );
+                        radio.outbox.push(Outgoing::Broadcast {
+                            channel: response.channel,
+                            text,
+                        });
+                    }
+                }
+                ReplyDestination::Sender | ReplyDestination::Private => {
+                    let recipient = num_id_to_hex(response.sender);
+                    let pages = if cfg.more.is_some() {
+                        let mut pages = paginate_more(reply.out, MAX_LENGTH);
+                        later.extend(pages.split_off(1.min(pages.len())));
+                        pages
+                    } else {
+                        paginate(reply.out, MAX_LENGTH)
+                    };
+                    for text in pages {
+                        log::info!("Replying to {recipient}:\n\n\"\"\"\n{text}\n\"\"\"");
+                        let page = Page {
+                            destination: response.sender,
+                            channel: response.channel,
+                            text,
+                            message_id: None,
+                        };
+                        radio.outbox.push(Outgoing::Direct { page, attempts: 0 });
+                    }
+                }
+                ReplyDestination::Broadcast => {
+                    for text in paginate(reply.out, MAX_LENGTH) {
+                        log::info!(
+                            "Replying to <broadcast> at {}:\n\n\"\"\"\n{text}\n\"\"\"",
+                            cfg.public_channel
+                        );
+                        radio.outbox.push(Outgoing::Broadcast {
+                            channel: cfg.public_channel,
+                            text,
+                        });
+                    }
+                }
+            }
+        }
+        if !later.is_empty() {
+            if let Ok(user) = users::get(conn, &num_id_to_hex(response.sender)) {
+                log::info!("Holding {} pages for {user}", later.len());
+                more_pages::replace(conn, user.account_id(), &later);
+            }
+        }
+    }
+
+    // Next, send any queued messages to the user.
+    // We send to the specific node that just connected (response.sender), not necessarily
+    // the "primary" node for the account. This way, if an account has multiple nodes,
+    // messages are delivered to whichever node is currently active.
+
+    let node_id = num_id_to_hex(response.sender);
+    let Ok(user) = users::get(conn, &node_id) else {
+        // This should never happen because we should've upserted the user before calling this.
+        log::debug!("No user matching {node_id}");
+        return;
+    };
+
+    let queue = queued_messages::get(conn, &user);
+    if queue.is_empty() {
+        log::debug!("No unsent messages for {}", user.account_id());
+    }
+
+    for message in queue {
+        // It's already on its way. Don't pile on another copy.
+        if radio.tracker.in_flight(message.id) || radio.outbox.has_message(message.id) {
+            continue;
+        }
+        // Notices come from the BBS itself. Otherwise, look up the sender's account to display
+        // their name. Note: if the sender has multiple nodes, we show their username (or first
+        // node's long_name as fallback).
+        let headline = if message.notice {
+            log::info!("Sending a notice to {user}");
+            format!("Notice from {} at {}:", cfg.bbs_name, message.created_at())
+        } else {
+            let Ok(sender) = users::get_by_account_id(conn, message.sender_account_id) else {
+                log::error!("Unknown sender account_id: {}", message.sender_account_id);
+                continue;
+            };
+            log::info!("Sending a queued message from {sender} to {user}");
+            format!("Message from {} at {}:", sender, message.created_at())
+        };
+        // Construct the message body.
+        let out = vec![headline, String::new(), message.body.to_string()];
+        // Send to the node that just connected, not user.node_id_numeric() which might
+        // be a different node if the account has multiple. It's marked sent once the node
+        // acknowledges every page.
+        for text in paginate(out, MAX_LENGTH) {
+            let page = Page {
+                destination: response.sender,
+                channel: response.channel,
+                text,
+                message_id: Some(message.id),
+            };
+            radio.outbox.push(Outgoing::Direct { page, attempts: 0 });
+        }
+    }
 }
 
 fn handle_packet(
@@ -235,10 +658,39 @@ fn handle_packet(
         log::debug!("Result: {:?}", &replies);
         return Some(Response {
             sender: meshpacket.from,
+            channel: meshpacket.channel,
+            public: false,
             replies: Some(replies),
+            routed: None,
         });
     }
 
+    // Someone may be asking the BBS for something in a public channel.
+    if decoded.portnum == PortNum::TextMessageApp as i32 && meshpacket.to == BROADCAST_ID {
+        if let Some(command) = cfg
+            .public
+            .as_ref()
+            .zip(std::str::from_utf8(&decoded.payload).ok())
+            .and_then(|(public, text)| public.command(meshpacket.channel, text))
+        {
+            log::debug!(
+                "Received public command from {user_id} on channel {}: <{command}>",
+                meshpacket.channel
+            );
+            let replies = dispatch_public(conn, cfg, &user_id, menus, command);
+            log::debug!("Result: {:?}", &replies);
+            return Some(Response {
+                sender: meshpacket.from,
+                channel: meshpacket.channel,
+                public: true,
+                replies: Some(replies),
+                routed: None,
+            });
+        }
+    }
+
+    let mut routed = None;
+
     let mut short_name: Option<String> = None;
     let mut long_name: Option<String> = None;
 
@@ -266,6 +718,20 @@ fn handle_packet(
         user_id = user.id;
         short_name = Some(user.short_name);
         long_name = Some(user.long_name);
+    } else if decoded.portnum == PortNum::RoutingApp as i32 && decoded.request_id != 0 {
+        // This is an ACK or NAK for a packet we sent.
+        match Routing::decode(&decoded.payload[..]) {
+            Ok(Routing {
+                variant: Some(routing::Variant::ErrorReason(reason)),
+            }) => {
+                routed = Some(Routed {
+                    request_id: decoded.request_id,
+                    delivered: reason == routing::Error::None as i32,
+                });
+            }
+            Ok(_) => {}
+            Err(err) => log::error!("Unable to decode the routing {:?}: {err}", decoded.payload),
+        }
     }
     observe(
         conn,
@@ -278,7 +744,10 @@ fn handle_packet(
 
     Some(Response {
         sender: meshpacket.from,
+        channel: meshpacket.channel,
+        public: false,
         replies: None,
+        routed,
     })
 }
 
@@ -310,3 +779,134 @@ fn observe(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CommandConfig, MenuConfig, PublicConfig};
+    use config::Map;
+    use meshtastic::protobufs::Data;
+
+    const MY_ID: u32 = 0x0000_0001;
+
+    fn test_config() -> BBSConfig {
+        let mut menus = Map::new();
+        menus.insert(
+            "General".to_string(),
+            MenuConfig {
+                help_suffix: "G".to_string(),
+                commands: vec![
+                    CommandConfig::for_tests("PING", "ping", "always", "ping::ping"),
+                    CommandConfig::for_tests("B", "b", "always", "board::lister"),
+                ],
+            },
+        );
+        BBSConfig {
+            menus,
+            public: Some(PublicConfig {
+                channels: vec![2],
+                ..PublicConfig::default()
+            }),
+            ..BBSConfig::for_tests()
+        }
+    }
+
+    fn text_packet(from: u32, to: u32, channel: u32, text: &str) -> FromRadio {
+        FromRadio {
+            payload_variant: Some(from_radio::PayloadVariant::Packet(MeshPacket {
+                from,
+                to,
+                channel,
+                payload_variant: Some(mesh_packet::PayloadVariant::Decoded(Data {
+                    portnum: PortNum::TextMessageApp as i32,
+                    payload: text.as_bytes().to_vec(),
+                    ..Data::default()
+                })),
+                ..MeshPacket::default()
+            })),
+            ..FromRadio::default()
+        }
+    }
+
+    fn reply_text(response: &Response) -> String {
+        response.replies.as_ref().expect("replies").0[0]
+            .out
+            .join("\n")
+    }
+
+    #[test]
+    fn direct_commands_reply_on_their_channel() {
+        let mut conn = db::test_connection();
+        let cfg = test_config();
+        let menus = commands::command_structure(&cfg);
+        // Get past the new user welcome.
+        handle_packet(
+            &mut conn,
+            &cfg,
+            &menus,
+            text_packet(5, MY_ID, 1, "ping"),
+            MY_ID,
+        );
+
+        let response = handle_packet(
+            &mut conn,
+            &cfg,
+            &menus,
+            text_packet(5, MY_ID, 1, "ping"),
+            MY_ID,
+        )
+        .expect("response");
+        assert_eq!(response.channel, 1);
+        assert!(!response.public);
+        assert_eq!(reply_text(&response), "pong");
+    }
+
+    #[test]
+    fn public_commands_need_the_prefix_and_an_allowed_command() {
+        let mut conn = db::test_connection();
+        let cfg = test_config();
+        let menus = commands::command_structure(&cfg);
+
+        let response = handle_packet(
+            &mut conn,
+            &cfg,
+            &menus,
+            text_packet(6, BROADCAST_ID, 2, "!bbs PING"),
+            MY_ID,
+        )
+        .expect("response");
+        assert_eq!(response.channel, 2);
+        assert!(response.public);
+        assert_eq!(reply_text(&response), "PONG");
+
+        // Commands that aren't allowed in public get a list of the ones that are, but only the
+        // sender needs to see it.
+        let response = handle_packet(
+            &mut conn,
+            &cfg,
+            &menus,
+            text_packet(6, BROADCAST_ID, 2, "!bbs b"),
+            MY_ID,
+        )
+        .expect("response");
+        assert!(matches!(
+            response.replies.as_ref().expect("replies").0[0].destination,
+            ReplyDestination::Private
+        ));
+        assert!(reply_text(&response).contains("PING"));
+        assert!(!reply_text(&response).contains("B :"));
+
+        // Chatter without the prefix, or on other channels, isn't for us.
+        for (channel, text) in [(2, "ping"), (0, "!bbs ping")] {
+            let response = handle_packet(
+                &mut conn,
+                &cfg,
+                &menus,
+                text_packet(6, BROADCAST_ID, channel, text),
+                MY_ID,
+            )
+            .expect("response");
+            assert!(response.replies.is_none());
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// How many times to send a page before giving up on it.
+const MAX_ATTEMPTS: u32 = 3;
+/// How long to wait for the first attempt to be acknowledged. Each retry waits twice as long.
+const ACK_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long to wait before retrying the first time the mesh says it couldn't deliver a page. Each
+/// later retry waits twice as long.
+const NAK_DELAY: Duration = Duration::from_secs(10);
+
+/// A page of text sent to one node, which we'd like to hear was received.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Page {
+    pub destination: u32,
+    pub channel: u32,
+    pub text: String,
+    /// The queued message this page is part of, if any.
+    pub message_id: Option<i32>,
+}
+
+#[derive(Debug)]
+struct Pending {
+    page: Page,
+    attempts: u32,
+    retry_at: Instant,
+}
+
+/// Double the base delay for each attempt after the first.
+fn backoff(base: Duration, attempts: u32) -> Duration {
+    base * 2_u32.pow(attempts.saturating_sub(1))
+}
+
+/// The pages we've sent and haven't heard back about yet, by packet ID.
+#[derive(Debug, Default)]
+pub struct Tracker {
+    pending: HashMap<u32, Pending>,
+}
+
+impl Tracker {
+    /// Remember that we sent this page as packet `packet_id`, for the `attempts`th time.
+    pub fn sent(&mut self, packet_id: u32, page: Page, attempts: u32, now: Instant) {
+        self.pending.insert(
+            packet_id,
+            Pending {
+                page,
+                attempts,
+                retry_at: now + backoff(ACK_TIMEOUT, attempts),
+            },
+        );
+    }
+
+    /// Are any pages of this queued message still waiting to be acknowledged?
+    pub fn in_flight(&self, message_id: i32) -> bool {
+        self.pending
+            .values()
+            .any(|pending| pending.page.message_id == Some(message_id))
+    }
+
+    /// Node `from` acknowledged this packet. If it was the last page of a queued message we were
+    /// waiting on, return that message's ID so it can be marked delivered.
+    ///
+    /// Only the page's destination can say it got the page. Anyone else, like our own radio
+    /// hearing a neighbor relay it, only tells us it's on its way, so we keep waiting.
+    pub fn acked(&mut self, packet_id: u32, from: u32) -> Option<i32> {
+        if self.pending.get(&packet_id)?.page.destination != from {
+            return None;
+        }
+        let message_id = self.pending.remove(&packet_id)?.page.message_id?;
+        if self.in_flight(message_id) {
+            None
+        } else {
+            Some(message_id)
+        }
+    }
+
+    /// The mesh couldn't deliver this packet. Try it again after a while.
+    pub fn nacked(&mut self, packet_id: u32, now: Instant) {
+        if let Some(pending) = self.pending.get_mut(&packet_id) {
+            pending.retry_at = now + backoff(NAK_DELAY, pending.attempts);
+        }
+    }
+
+    /// Take the pages that are due to be retried, along with how many times each has been sent.
+    /// Pages that have used up their attempts are dropped and logged instead. When that happens to
+    /// a page of a queued message, the rest of the message is dropped, too, and it stays queued to
+    /// be sent again the next time we hear from the recipient. Those messages' IDs are returned
+    /// as well.
+    pub fn due(&mut self, now: Instant) -> (Vec<(Page, u32)>, Vec<i32>) {
+        let due: Vec<u32> = self
+            .pending
+            .iter()
+            .filter(|(_, pending)| pending.retry_at <= now)
+            .map(|(&packet_id, _)| packet_id)
+            .collect();
+        let mut retries = Vec::new();
+        let mut abandoned = Vec::new();
+        for packet_id in due {
+            let Some(pending) = self.pending.remove(&packet_id) else {
+                continue;
+            };
+            if pending.attempts < MAX_ATTEMPTS {
+                retries.push((pending.page, pending.attempts));
+                continue;
+            }
+            log::warn!(
+                "Giving up on sending to !{:08x} after {} attempts: {:?}",
+                pending.page.destination,
+                pending.attempts,
+                pending.page.text
+            );
+            if let Some(message_id) = pending.page.message_id {
+                self.pending
+                    .retain(|_, pending| pending.page.message_id != Some(message_id));
+                retries.retain(|(page, _)| page.message_id != Some(message_id));
+                abandoned.push(message_id);
+            }
+        }
+        (retries, abandoned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(text: &str, message_id: Option<i32>) -> Page {
+        Page {
+            destination: 0x1234_5678,
+            channel: 0,
+            text: text.to_string(),
+            message_id,
+        }
+    }
+
+    #[test]
+    fn messages_are_delivered_when_every_page_is_acked() {
+        let now = Instant::now();
+        let mut tracker = Tracker::default();
+        tracker.sent(1, page("one", Some(7)), 1, now);
+        tracker.sent(2, page("two", Some(7)), 1, now);
+        assert!(tracker.in_flight(7));
+        assert_eq!(tracker.acked(2, 0x1234_5678), None);
+        assert_eq!(tracker.acked(99, 0x1234_5678), None);
+        assert_eq!(tracker.acked(1, 0x1234_5678), Some(7));
+        assert!(!tracker.in_flight(7));
+    }
+
+    #[test]
+    fn only_the_destination_can_ack_a_page() {
+        let now = Instant::now();
+        let mut tracker = Tracker::default();
+        tracker.sent(1, page("hello", Some(7)), 1, now);
+        // Our own radio heard a neighbor relay it. That's not delivery.
+        assert_eq!(tracker.acked(1, 0xcafe_b33d), None);
+        assert!(tracker.in_flight(7));
+        assert_eq!(tracker.acked(1, 0x1234_5678), Some(7));
+    }
+
+    #[test]
+    fn unacked_pages_retry_with_backoff() {
+        let now = Instant::now();
+        let mut tracker = Tracker::default();
+        tracker.sent(1, page("hello", None), 1, now);
+        assert!(tracker.due(now + ACK_TIMEOUT / 2).0.is_empty());
+        let (retries, _) = tracker.due(now + ACK_TIMEOUT);
+        assert_eq!(retries, vec![(page("hello", None), 1)]);
+
+        // The second attempt waits twice as long.
+        tracker.sent(2, page("hello", None), 2, now);
+        assert!(tracker.due(now + ACK_TIMEOUT).0.is_empty());
+        assert_eq!(tracker.due(now + ACK_TIMEOUT * 2).0.len(), 1);
+    }
+
+    #[test]
+    fn naks_retry_sooner() {
+        let now = Instant::now();
+        let mut tracker = Tracker::default();
+        tracker.sent(1, page("hello", None), 1, now);
+        tracker.nacked(1, now);
+        assert_eq!(tracker.due(now + NAK_DELAY).0.len(), 1);
+    }
+
+    #[test]
+    fn messages_are_dropped_after_the_last_attempt() {
+        let now = Instant::now();
+        let mut tracker = Tracker::default();
+        tracker.sent(1, page("one", Some(7)), MAX_ATTEMPTS, now);
+        tracker.sent(2, page("two", Some(7)), 1, now + ACK_TIMEOUT * 10);
+        let later = now + backoff(ACK_TIMEOUT, MAX_ATTEMPTS);
+        assert_eq!(tracker.due(later), (vec![], vec![7]));
+        assert!(!tracker.in_flight(7));
+    }
+}
@@ -0,0 +1,493 @@
+use super::BROADCAST_ID;
+use crate::{num_id_to_hex, MqttChannel, MqttConfig};
+use aes::{Aes128, Aes256};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ctr::{
+    cipher::{KeyIvInit, StreamCipher},
+    Ctr128BE,
+};
+use meshtastic::{
+    protobufs::{
+        from_radio, mesh_packet, Data, FromRadio, MeshPacket, PortNum, ServiceEnvelope, User,
+    },
+    utils, Message,
+};
+use rumqttc::{AsyncClient, Event, EventLoop, MqttOptions, Packet, QoS};
+use std::collections::{HashSet, VecDeque};
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::time::Duration;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::task::JoinHandle;
+
+/// The key behind the one byte "AQ==" key most channels use.
+const DEFAULT_KEY: [u8; 16] = [
+    0xd4, 0xf1, 0xbb, 0x3a, 0x20, 0x29, 0x07, 0x59, 0xf0, 0xbc, 0xff, 0xab, 0xcf, 0x4e, 0x69, 0x01,
+];
+/// How many hops our packets may take after the gateway sends them.
+const HOP_LIMIT: u32 = 3;
+/// How often to tell the mesh who we are, so people can find us in their node lists.
+const NODE_INFO_INTERVAL: Duration = Duration::from_secs(3 * 60 * 60);
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+/// How many packets to remember, so copies uploaded by several gateways are only handled once.
+const SEEN_PACKETS: usize = 1000;
+/// How many requests to the broker may wait to be sent.
+const REQUEST_CAPACITY: usize = 100;
+
+/// One of the mesh's channels, ready to encrypt and decrypt packets with.
+#[derive(Clone, Debug)]
+struct Channel {
+    name: String,
+    /// The AES key, or nothing if the channel isn't encrypted.
+    key: Vec<u8>,
+    /// Packets on the air carry this hash of the channel's name and key instead of its number.
+    hash: u32,
+}
+
+impl Channel {
+    fn new(config: &MqttChannel) -> Result<Self, String> {
+        let psk = STANDARD
+            .decode(&config.key)
+            .map_err(|err| format!("The key for channel {} isn't base64: {err}", config.name))?;
+        let key = match psk.len() {
+            0 => Vec::new(),
+            // A one byte key picks one of the well-known keys. Zero means no encryption.
+            1 if psk[0] == 0 => Vec::new(),
+            1 => {
+                let mut key = DEFAULT_KEY;
+                key[15] = key[15].wrapping_add(psk[0] - 1);
+                key.to_vec()
+            }
+            16 | 32 => psk,
+            len => {
+                return Err(format!(
+                    "The key for channel {} is {len} bytes, but must be 0, 1, 16, or 32",
+                    config.name
+                ))
+            }
+        };
+        let hash = config
+            .name
+            .bytes()
+            .chain(key.iter().copied())
+            .fold(0, |hash, byte| hash ^ byte);
+        Ok(Self {
+            name: config.name.clone(),
+            key,
+            hash: u32::from(hash),
+        })
+    }
+
+    /// Encrypt or decrypt a packet's payload. Meshtastic uses AES-CTR, with the packet ID and
+    /// sender as the nonce, so both directions are the same.
+    fn crypt(&self, packet_id: u32, from: u32, data: &mut [u8]) {
+        let mut nonce = [0u8; 16];
+        nonce[..8].copy_from_slice(&u64::from(packet_id).to_le_bytes());
+        nonce[8..12].copy_from_slice(&from.to_le_bytes());
+        match self.key.len() {
+            16 => Ctr128BE::<Aes128>::new(self.key[..].into(), &nonce.into()).apply_keystream(data),
+            32 => Ctr128BE::<Aes256>::new(self.key[..].into(), &nonce.into()).apply_keystream(data),
+            _ => {}
+        }
+    }
+}
+
+/// The packets we've handled lately, by sender and packet ID.
+#[derive(Debug, Default)]
+struct Seen {
+    order: VecDeque<(u32, u32)>,
+    packets: HashSet<(u32, u32)>,
+}
+
+impl Seen {
+    /// Remember this packet. Returns whether it's the first time we've seen it.
+    fn first_time(&mut self, from: u32, id: u32) -> bool {
+        if !self.packets.insert((from, id)) {
+            return false;
+        }
+        self.order.push_back((from, id));
+        if self.order.len() > SEEN_PACKETS {
+            if let Some(oldest) = self.order.pop_front() {
+                self.packets.remove(&oldest);
+            }
+        }
+        true
+    }
+}
+
+/// Turn a packet a gateway published into one like a radio would hand us: decrypted, and on
+/// the channel's number.
+fn unwrap(channels: &[Channel], my_id: u32, payload: &[u8]) -> Option<FromRadio> {
+    let envelope = match ServiceEnvelope::decode(payload) {
+        Ok(x) => x,
+        Err(err) => {
+            log::debug!("Unable to decode the service envelope: {err}");
+            return None;
+        }
+    };
+    let mut packet = envelope.packet?;
+    // Our own packets come back to us from the broker.
+    if packet.from == my_id {
+        return None;
+    }
+    let Some((index, channel)) = channels
+        .iter()
+        .enumerate()
+        .find(|(_, channel)| channel.name == envelope.channel_id)
+    else {
+        log::debug!(
+            "Ignoring a packet on unknown channel {}",
+            envelope.channel_id
+        );
+        return None;
+    };
+    if let Some(mesh_packet::PayloadVariant::Encrypted(mut data)) = packet.payload_variant {
+        // Someone else's channel that happens to have the same name.
+        if packet.channel != channel.hash {
+            return None;
+        }
+        channel.crypt(packet.id, packet.from, &mut data);
+        let Ok(decoded) = Data::decode(&data[..]) else {
+            log::debug!(
+                "Unable to decrypt packet {} on channel {}",
+                packet.id,
+                channel.name
+            );
+            return None;
+        };
+        packet.payload_variant = Some(mesh_packet::PayloadVariant::Decoded(decoded));
+    }
+    packet.channel = u32::try_from(index).ok()?;
+    Some(FromRadio {
+        payload_variant: Some(from_radio::PayloadVariant::Packet(packet)),
+        ..FromRadio::default()
+    })
+}
+
+/// Wrap a packet from us the way gateways expect to find it on the broker.
+fn wrap(channel: &Channel, my_id: u32, id: u32, to: u32, data: &Data) -> ServiceEnvelope {
+    let payload_variant = if channel.key.is_empty() {
+        mesh_packet::PayloadVariant::Decoded(data.clone())
+    } else {
+        let mut encrypted = data.encode_to_vec();
+        channel.crypt(id, my_id, &mut encrypted);
+        mesh_packet::PayloadVariant::Encrypted(encrypted)
+    };
+    ServiceEnvelope {
+        packet: Some(MeshPacket {
+            from: my_id,
+            to,
+            channel: channel.hash,
+            id,
+            hop_limit: HOP_LIMIT,
+            hop_start: HOP_LIMIT,
+            want_ack: to != BROADCAST_ID,
+            payload_variant: Some(payload_variant),
+            ..MeshPacket::default()
+        }),
+        channel_id: channel.name.clone(),
+        gateway_id: num_id_to_hex(my_id),
+    }
+}
+
+/// We were asked to send on a channel that isn't in the MQTT config. Only that page is lost. The
+/// link itself is fine.
+#[derive(Debug)]
+pub struct UnknownChannel(pub u32);
+
+impl Display for UnknownChannel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "There's no MQTT channel number {}", self.0)
+    }
+}
+
+impl Error for UnknownChannel {}
+
+/// Our connection to the broker.
+pub struct Link {
+    client: AsyncClient,
+    relay: JoinHandle<()>,
+    announcer: JoinHandle<()>,
+    channels: Vec<Channel>,
+    root_topic: String,
+    my_id: u32,
+}
+
+/// Connect to the broker and start relaying the mesh's packets to us, like a radio would. We
+/// tell the mesh we're node `my_id`, named `long_name`.
+pub async fn connect(
+    mqtt: &MqttConfig,
+    my_id: u32,
+    long_name: &str,
+) -> Result<(UnboundedReceiver<FromRadio>, Link), Box<dyn Error>> {
+    let channels = mqtt
+        .channels
+        .iter()
+        .map(Channel::new)
+        .collect::<Result<Vec<_>, _>>()?;
+    let Some(primary) = channels.first().cloned() else {
+        return Err("At least one MQTT channel must be configured.".into());
+    };
+
+    let mut options = MqttOptions::new(
+        format!("frozenbbs-{}", num_id_to_hex(my_id)),
+        &mqtt.host,
+        mqtt.port,
+    );
+    options.set_keep_alive(KEEP_ALIVE);
+    if let Some(username) = &mqtt.username {
+        options.set_credentials(username, mqtt.password.as_deref().unwrap_or_default());
+    }
+    let (client, mut eventloop) = AsyncClient::new(options, REQUEST_CAPACITY);
+    // The client doesn't connect until we ask it for something. Wait until it has, so a broker
+    // that isn't there is an error here instead of a link that's lost right away.
+    loop {
+        if let Event::Incoming(Packet::ConnAck(_)) = eventloop.poll().await? {
+            break;
+        }
+    }
+    client
+        .subscribe(format!("{}/2/e/#", mqtt.root_topic), QoS::AtMostOnce)
+        .await?;
+
+    let (decoded_tx, decoded_listener) = mpsc::unbounded_channel();
+    let relay = tokio::spawn(relay(eventloop, decoded_tx, channels.clone(), my_id));
+
+    // Say who we are, now and every so often.
+    let node_info = Data {
+        portnum: PortNum::NodeinfoApp as i32,
+        payload: User {
+            id: num_id_to_hex(my_id),
+            long_name: long_name.to_string(),
+            short_name: mqtt.short_name.clone(),
+            ..User::default()
+        }
+        .encode_to_vec(),
+        ..Data::default()
+    };
+    let announcer = {
+        let client = client.clone();
+        let topic = topic(&mqtt.root_topic, &primary, my_id);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(NODE_INFO_INTERVAL);
+            loop {
+                interval.tick().await;
+                let envelope = wrap(
+                    &primary,
+                    my_id,
+                    utils::generate_rand_id(),
+                    BROADCAST_ID,
+                    &node_info,
+                );
+                if let Err(err) = client
+                    .publish(&topic, QoS::AtMostOnce, false, envelope.encode_to_vec())
+                    .await
+                {
+                    log::warn!("MQTT: couldn't announce ourselves: {err}");
+                    break;
+                }
+            }
+        })
+    };
+
+    Ok((
+        decoded_listener,
+        Link {
+            client,
+            relay,
+            announcer,
+            channels,
+            root_topic: mqtt.root_topic.clone(),
+            my_id,
+        },
+    ))
+}
+
+/// The topic gateways listen to for packets from us on a channel.
+fn topic(root_topic: &str, channel: &Channel, my_id: u32) -> String {
+    format!("{root_topic}/2/e/{}/{}", channel.name, num_id_to_hex(my_id))
+}
+
+/// Pass the packets gateways publish along to the server, once each, until the broker goes
+/// away.
+async fn relay(
+    mut eventloop: EventLoop,
+    decoded_tx: UnboundedSender<FromRadio>,
+    channels: Vec<Channel>,
+    my_id: u32,
+) {
+    let mut seen = Seen::default();
+    loop {
+        match eventloop.poll().await {
+            Ok(Event::Incoming(Packet::Publish(publish))) => {
+                let Some(from_radio) = unwrap(&channels, my_id, &publish.payload) else {
+                    continue;
+                };
+                if let Some(from_radio::PayloadVariant::Packet(packet)) =
+                    &from_radio.payload_variant
+                {
+                    if !seen.first_time(packet.from, packet.id) {
+                        continue;
+                    }
+                }
+                if decoded_tx.send(from_radio).is_err() {
+                    break;
+                }
+            }
+            Ok(_) => {}
+            Err(err) => {
+                log::warn!("MQTT: {err}");
+                break;
+            }
+        }
+    }
+}
+
+impl Link {
+    /// Publish a text message for a gateway to send, and return its packet ID.
+    pub async fn send_text(
+        &mut self,
+        to: u32,
+        channel: u32,
+        text: String,
+    ) -> Result<u32, Box<dyn Error>> {
+        let Some(channel) = usize::try_from(channel)
+            .ok()
+            .and_then(|index| self.channels.get(index))
+        else {
+            return Err(UnknownChannel(channel).into());
+        };
+        let id = utils::generate_rand_id();
+        let data = Data {
+            portnum: PortNum::TextMessageApp as i32,
+            payload: text.into_bytes(),
+            ..Data::default()
+        };
+        let envelope = wrap(channel, self.my_id, id, to, &data);
+        self.client
+            .publish(
+                topic(&self.root_topic, channel, self.my_id),
+                QoS::AtLeastOnce,
+                false,
+                envelope.encode_to_vec(),
+            )
+            .await?;
+        Ok(id)
+    }
+
+    /// Say goodbye to the broker and stop relaying.
+    pub async fn disconnect(self) {
+        if let Err(err) = self.client.disconnect().await {
+            log::debug!("MQTT: error while disconnecting: {err}");
+        }
+        self.announcer.abort();
+        self.relay.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MY_ID: u32 = 0x0000_0001;
+    const ALICE: u32 = 0x0000_a11c;
+
+    fn channel(name: &str, key: &str) -> Channel {
+        Channel::new(&MqttChannel {
+            name: name.to_string(),
+            key: key.to_string(),
+        })
+        .expect("channel")
+    }
+
+    fn text(text: &str) -> Data {
+        Data {
+            portnum: PortNum::TextMessageApp as i32,
+            payload: text.as_bytes().to_vec(),
+            ..Data::default()
+        }
+    }
+
+    /// Pretend Alice sent this, the way a gateway would publish it.
+    fn from_alice(channel: &Channel, id: u32, to: u32, data: &Data) -> Vec<u8> {
+        let mut envelope = wrap(channel, ALICE, id, to, data);
+        envelope.gateway_id = "!0000beef".to_string();
+        envelope.encode_to_vec()
+    }
+
+    fn packet(from_radio: FromRadio) -> MeshPacket {
+        match from_radio.payload_variant {
+            Some(from_radio::PayloadVariant::Packet(packet)) => packet,
+            other => panic!("not a packet: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn keys_and_hashes_match_the_firmware() {
+        let long_fast = channel("LongFast", "AQ==");
+        assert_eq!(long_fast.key, DEFAULT_KEY);
+        assert_eq!(long_fast.hash, 8);
+        assert!(channel("Open", "AA==").key.is_empty());
+        assert_eq!(channel("Other", "Ag==").key[15], 0x02);
+        assert!(Channel::new(&MqttChannel {
+            name: "Bad".to_string(),
+            key: "AAAA".to_string(),
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn packets_are_decrypted_onto_the_channel_number() {
+        let channels = vec![
+            channel("LongFast", "AQ=="),
+            channel("Secret", &STANDARD.encode([7u8; 32])),
+        ];
+        let payload = from_alice(&channels[1], 42, MY_ID, &text("ping"));
+
+        let packet = packet(unwrap(&channels, MY_ID, &payload).expect("packet"));
+        assert_eq!((packet.from, packet.to, packet.id), (ALICE, MY_ID, 42));
+        assert_eq!(packet.channel, 1);
+        assert_eq!(
+            packet.payload_variant,
+            Some(mesh_packet::PayloadVariant::Decoded(text("ping")))
+        );
+    }
+
+    #[test]
+    fn strangers_and_echoes_are_ignored() {
+        let channels = vec![channel("LongFast", "AQ==")];
+        // A channel we don't know the key for.
+        let other = from_alice(&channel("Private", "AQ=="), 1, MY_ID, &text("hi"));
+        assert!(unwrap(&channels, MY_ID, &other).is_none());
+        // A channel we know, but with a different key.
+        let wrong_key = from_alice(&channel("LongFast", "Ag=="), 2, MY_ID, &text("hi"));
+        assert!(unwrap(&channels, MY_ID, &wrong_key).is_none());
+        // Our own packet, coming back from the broker.
+        let echo = wrap(&channels[0], MY_ID, 3, ALICE, &text("pong")).encode_to_vec();
+        assert!(unwrap(&channels, MY_ID, &echo).is_none());
+        assert!(unwrap(&channels, MY_ID, b"not a protobuf").is_none());
+    }
+
+    #[test]
+    fn copies_from_other_gateways_are_seen_once() {
+        let mut seen = Seen::default();
+        assert!(seen.first_time(ALICE, 1));
+        assert!(!seen.first_time(ALICE, 1));
+        assert!(seen.first_time(MY_ID, 1));
+        for id in 2..=u32::try_from(SEEN_PACKETS).expect("fits") + 1 {
+            seen.first_time(ALICE, id);
+        }
+        // Long enough ago, it's forgotten.
+        assert!(seen.first_time(ALICE, 1));
+    }
+
+    #[tokio::test]
+    async fn missing_brokers_are_an_error() {
+        let mqtt = MqttConfig {
+            host: "127.0.0.1".to_string(),
+            port: 1,
+            ..MqttConfig::default()
+        };
+        assert!(connect(&mqtt, MY_ID, "Test BBS").await.is_err());
+    }
+}
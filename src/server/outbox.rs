@@ -0,0 +1,154 @@
+use super::acks::Page;
+use std::collections::{HashMap, VecDeque};
+
+/// Something waiting to go out over the radio.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outgoing {
+    /// A page to one node, and how many times it's been sent before.
+    Direct { page: Page, attempts: u32 },
+    /// A page to everyone on a channel.
+    Broadcast { channel: u32, text: String },
+}
+
+impl Outgoing {
+    pub fn text(&self) -> &str {
+        match self {
+            Outgoing::Direct { page, .. } => &page.text,
+            Outgoing::Broadcast { text, .. } => text,
+        }
+    }
+
+    /// Who gets a turn to send this: the destination node, or nobody in particular for
+    /// broadcasts, which all share one turn.
+    fn turn(&self) -> Option<u32> {
+        match self {
+            Outgoing::Direct { page, .. } => Some(page.destination),
+            Outgoing::Broadcast { .. } => None,
+        }
+    }
+
+    fn message_id(&self) -> Option<i32> {
+        match self {
+            Outgoing::Direct { page, .. } => page.message_id,
+            Outgoing::Broadcast { .. } => None,
+        }
+    }
+}
+
+/// Pages waiting to be sent, taking turns between destinations so one long reply doesn't make
+/// everyone else wait until it's done.
+#[derive(Debug, Default)]
+pub struct Outbox {
+    queues: HashMap<Option<u32>, VecDeque<Outgoing>>,
+    turns: VecDeque<Option<u32>>,
+}
+
+impl Outbox {
+    /// Add a page to the end of its destination's line.
+    pub fn push(&mut self, outgoing: Outgoing) {
+        let turn = outgoing.turn();
+        let queue = self.queues.entry(turn).or_default();
+        if queue.is_empty() {
+            self.turns.push_back(turn);
+        }
+        queue.push_back(outgoing);
+    }
+
+    /// Look at the page `pop` would take next.
+    pub fn peek(&self) -> Option<&Outgoing> {
+        self.queues.get(self.turns.front()?)?.front()
+    }
+
+    /// Take the next page from whichever destination's turn it is.
+    pub fn pop(&mut self) -> Option<Outgoing> {
+        let turn = self.turns.pop_front()?;
+        let queue = self.queues.get_mut(&turn)?;
+        let outgoing = queue.pop_front();
+        if queue.is_empty() {
+            self.queues.remove(&turn);
+        } else {
+            self.turns.push_back(turn);
+        }
+        outgoing
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.turns.is_empty()
+    }
+
+    /// Are any pages of this queued message still waiting to be sent?
+    pub fn has_message(&self, message_id: i32) -> bool {
+        self.queues
+            .values()
+            .flatten()
+            .any(|outgoing| outgoing.message_id() == Some(message_id))
+    }
+
+    /// Forget the rest of a queued message we've given up on sending.
+    pub fn drop_message(&mut self, message_id: i32) {
+        for queue in self.queues.values_mut() {
+            queue.retain(|outgoing| outgoing.message_id() != Some(message_id));
+        }
+        self.queues.retain(|_, queue| !queue.is_empty());
+        self.turns.retain(|turn| self.queues.contains_key(turn));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn direct(destination: u32, text: &str, message_id: Option<i32>) -> Outgoing {
+        Outgoing::Direct {
+            page: Page {
+                destination,
+                channel: 0,
+                text: text.to_string(),
+                message_id,
+            },
+            attempts: 0,
+        }
+    }
+
+    fn text_of(outgoing: Outgoing) -> String {
+        match outgoing {
+            Outgoing::Direct { page, .. } => page.text,
+            Outgoing::Broadcast { text, .. } => text,
+        }
+    }
+
+    #[test]
+    fn destinations_take_turns() {
+        let mut outbox = Outbox::default();
+        for text in ["a1", "a2", "a3"] {
+            outbox.push(direct(1, text, None));
+        }
+        outbox.push(direct(2, "b1", None));
+        outbox.push(Outgoing::Broadcast {
+            channel: 0,
+            text: "ad".to_string(),
+        });
+
+        assert_eq!(
+            outbox.peek().map(|o| text_of(o.clone())),
+            Some("a1".to_string())
+        );
+        let order: Vec<String> = std::iter::from_fn(|| outbox.pop()).map(text_of).collect();
+        assert_eq!(order, vec!["a1", "b1", "ad", "a2", "a3"]);
+        assert!(outbox.is_empty());
+    }
+
+    #[test]
+    fn abandoned_messages_are_dropped() {
+        let mut outbox = Outbox::default();
+        outbox.push(direct(1, "m1", Some(7)));
+        outbox.push(direct(1, "m2", Some(7)));
+        outbox.push(direct(2, "other", Some(8)));
+        assert!(outbox.has_message(7));
+
+        outbox.drop_message(7);
+        assert!(!outbox.has_message(7));
+        assert_eq!(text_of(outbox.pop().expect("page")), "other");
+        assert!(outbox.is_empty());
+    }
+}
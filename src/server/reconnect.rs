@@ -0,0 +1,68 @@
+use std::time::Duration;
+
+/// How long to wait before the first attempt to reconnect to the radio. Each failed attempt
+/// waits twice as long as the one before.
+const FIRST_DELAY: Duration = Duration::from_secs(1);
+/// Never wait longer than this between attempts.
+const MAX_DELAY: Duration = Duration::from_secs(300);
+/// A connection that stayed up this long was healthy, so losing it starts the delays over.
+const STABLE_AFTER: Duration = Duration::from_secs(60);
+
+/// How long to wait between attempts to reconnect to the radio.
+#[derive(Debug)]
+pub struct Backoff {
+    delay: Duration,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self { delay: FIRST_DELAY }
+    }
+}
+
+impl Backoff {
+    /// How long to wait before the next attempt. Each call waits twice as long as the last, up
+    /// to a limit.
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = self.delay;
+        self.delay = (self.delay * 2).min(MAX_DELAY);
+        delay
+    }
+
+    /// The link was up for this long before it dropped. If it was a good while, don't hold the
+    /// radio's earlier troubles against it.
+    pub fn connected_for(&mut self, uptime: Duration) {
+        if uptime >= STABLE_AFTER {
+            self.delay = FIRST_DELAY;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delays_double_up_to_a_limit() {
+        let mut backoff = Backoff::default();
+        assert_eq!(backoff.next_delay(), FIRST_DELAY);
+        assert_eq!(backoff.next_delay(), FIRST_DELAY * 2);
+        assert_eq!(backoff.next_delay(), FIRST_DELAY * 4);
+        for _ in 0..20 {
+            backoff.next_delay();
+        }
+        assert_eq!(backoff.next_delay(), MAX_DELAY);
+    }
+
+    #[test]
+    fn stable_connections_start_over() {
+        let mut backoff = Backoff::default();
+        backoff.next_delay();
+        backoff.next_delay();
+        // Dropping again right away keeps backing off.
+        backoff.connected_for(Duration::from_secs(1));
+        assert_eq!(backoff.next_delay(), FIRST_DELAY * 4);
+        backoff.connected_for(STABLE_AFTER);
+        assert_eq!(backoff.next_delay(), FIRST_DELAY);
+    }
+}
@@ -0,0 +1,143 @@
+use crate::db::{announcements, posts};
+use crate::{advertisement, BBSConfig};
+use chrono::{DateTime, Local, Timelike as _};
+use diesel::SqliteConnection;
+
+/// Get the announcements that are due to be broadcast, and the channel each one goes out on.
+/// They're remembered as sent, so call this only when you're going to send them.
+pub fn due(
+    conn: &mut SqliteConnection,
+    cfg: &BBSConfig,
+    now: DateTime<Local>,
+) -> Vec<(u32, Vec<String>)> {
+    let Some(schedule) = &cfg.schedule else {
+        return Vec::new();
+    };
+    // Anything that comes due during quiet hours waits until they're over. Only the latest one
+    // goes out then, not one for every interval we skipped.
+    if schedule.is_quiet(now.hour()) {
+        return Vec::new();
+    }
+    let now_us = now.timestamp_micros();
+    let mut out = Vec::new();
+    for announcement in &schedule.announcements {
+        let interval_us = i64::from(announcement.interval_minutes) * 60_000_000;
+        if announcements::last_sent(conn, &announcement.name)
+            .is_some_and(|last_sent_at_us| last_sent_at_us + interval_us > now_us)
+        {
+            continue;
+        }
+        announcements::sent(conn, &announcement.name, now_us);
+        out.push((
+            announcement.channel.unwrap_or(cfg.public_channel),
+            expand(conn, cfg, &announcement.text, now),
+        ));
+    }
+    out
+}
+
+/// Fill in the placeholders in an announcement's text.
+fn expand(
+    conn: &mut SqliteConnection,
+    cfg: &BBSConfig,
+    text: &str,
+    now: DateTime<Local>,
+) -> Vec<String> {
+    let mut text = text
+        .replace("{ad}", &advertisement(cfg).join("\n"))
+        .replace("{bbs_name}", &cfg.bbs_name);
+    if text.contains("{posts_today}") {
+        let midnight = now
+            .date_naive()
+            .and_hms_opt(0, 0, 0)
+            .and_then(|midnight| midnight.and_local_timezone(Local).earliest())
+            .unwrap_or(now);
+        let count = posts::count_public_after(conn, midnight.timestamp_micros());
+        text = text.replace("{posts_today}", &count.to_string());
+    }
+    text.lines().map(ToString::to_string).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{self, boards, users};
+    use crate::{AnnouncementConfig, ScheduleConfig};
+    use chrono::TimeZone as _;
+
+    fn test_config() -> BBSConfig {
+        BBSConfig {
+            public_channel: 3,
+            ad_text: "Come visit!".to_string(),
+            schedule: Some(ScheduleConfig {
+                quiet_start_hour: Some(22),
+                quiet_end_hour: Some(7),
+                announcements: vec![
+                    AnnouncementConfig {
+                        name: "ad".to_string(),
+                        text: "{ad}".to_string(),
+                        interval_minutes: 60,
+                        channel: None,
+                    },
+                    AnnouncementConfig {
+                        name: "news".to_string(),
+                        text: "New posts on {bbs_name} today: {posts_today}".to_string(),
+                        interval_minutes: 24 * 60,
+                        channel: Some(1),
+                    },
+                ],
+            }),
+            ..BBSConfig::for_tests()
+        }
+    }
+
+    fn at(hour: u32, minute: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(2026, 10, 17, hour, minute, 0)
+            .earliest()
+            .expect("valid local time")
+    }
+
+    #[test]
+    fn announcements_go_out_once_per_interval() {
+        let mut conn = db::test_connection();
+        let cfg = test_config();
+
+        let sent = due(&mut conn, &cfg, at(12, 0));
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[0].0, 3);
+        assert_eq!(sent[0].1[0], "Come visit!");
+        assert_eq!(
+            sent[1],
+            (1, vec!["New posts on Test BBS today: 0".to_string()])
+        );
+
+        assert!(due(&mut conn, &cfg, at(12, 30)).is_empty());
+        let sent = due(&mut conn, &cfg, at(13, 0));
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].0, 3);
+    }
+
+    #[test]
+    fn quiet_hours_hold_everything() {
+        let mut conn = db::test_connection();
+        let cfg = test_config();
+        assert!(due(&mut conn, &cfg, at(23, 0)).is_empty());
+        assert_eq!(due(&mut conn, &cfg, at(7, 0)).len(), 2);
+    }
+
+    #[test]
+    fn posts_today_counts_public_posts() {
+        let mut conn = db::test_connection();
+        let mut cfg = test_config();
+        let board = boards::add(&mut conn, "General", "General discussion").expect("board");
+        let (author, _) = users::record(&mut conn, "!70000041").expect("author");
+        posts::add(&mut conn, author.account_id(), board.id, "Hello").expect("post");
+        if let Some(schedule) = cfg.schedule.as_mut() {
+            schedule.quiet_start_hour = None;
+        }
+
+        let sent = due(&mut conn, &cfg, Local::now());
+        assert_eq!(sent[1].1, vec!["New posts on Test BBS today: 1"]);
+    }
+}
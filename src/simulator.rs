@@ -0,0 +1,221 @@
+//! A pretend radio that talks to the BBS over an in-memory stream, for exercising the whole
+//! server without any hardware. Tests play the part of the mesh: they send packets from as many
+//! virtual nodes as they like, and read back exactly what the BBS asked the radio to transmit.
+
+use crate::num_id_to_hex;
+use meshtastic::{
+    api::StreamHandle,
+    protobufs::{
+        from_radio, mesh_packet, routing, to_radio, Data, FromRadio, MapReport, MeshPacket,
+        PortNum, Routing, ToRadio, User,
+    },
+    Message,
+};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+
+/// Every frame on the stream starts with these two bytes, then the length of the protobuf that
+/// follows as a big-endian u16.
+const MAGIC: [u8; 2] = [0x94, 0xc3];
+/// How much the BBS and the simulator can write before waiting for the other side to read.
+const PIPE_BYTES: usize = 64 * 1024;
+/// How long to wait for the BBS to transmit something before deciding it isn't going to.
+const WAIT: Duration = Duration::from_secs(5);
+
+/// Our side of the link to the BBS.
+pub struct Simulator {
+    stream: DuplexStream,
+    /// Bytes we've read from the BBS that don't make up a whole frame yet.
+    buffer: Vec<u8>,
+    next_packet_id: u32,
+}
+
+/// Make a simulated radio, and the stream the BBS should use to talk to it.
+pub fn pair() -> (StreamHandle<DuplexStream>, Simulator) {
+    let (bbs, radio) = tokio::io::duplex(PIPE_BYTES);
+    (
+        StreamHandle::from_stream(bbs),
+        Simulator {
+            stream: radio,
+            buffer: Vec::new(),
+            next_packet_id: 1,
+        },
+    )
+}
+
+/// The text a packet carries, if it's a text message.
+pub fn text_of(packet: &MeshPacket) -> Option<String> {
+    match &packet.payload_variant {
+        Some(mesh_packet::PayloadVariant::Decoded(data))
+            if data.portnum == PortNum::TextMessageApp as i32 =>
+        {
+            String::from_utf8(data.payload.clone()).ok()
+        }
+        _ => None,
+    }
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn now_as_seconds() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs() as u32)
+}
+
+impl Simulator {
+    /// Send a text message from a node, either to the BBS's node ID or to everyone.
+    pub async fn text(&mut self, from: u32, to: u32, channel: u32, text: &str) {
+        self.packet(
+            from,
+            to,
+            channel,
+            Data {
+                portnum: PortNum::TextMessageApp as i32,
+                payload: text.as_bytes().to_vec(),
+                ..Data::default()
+            },
+        )
+        .await;
+    }
+
+    /// Announce a node's names to everyone, like radios do when they join the mesh.
+    pub async fn node_info(&mut self, from: u32, short_name: &str, long_name: &str) {
+        let user = User {
+            id: num_id_to_hex(from),
+            short_name: short_name.to_string(),
+            long_name: long_name.to_string(),
+            ..User::default()
+        };
+        self.broadcast(from, PortNum::NodeinfoApp, user.encode_to_vec())
+            .await;
+    }
+
+    /// Report a node's names to the map, like radios configured to do so do every so often.
+    pub async fn map_report(&mut self, from: u32, short_name: &str, long_name: &str) {
+        let report = MapReport {
+            short_name: short_name.to_string(),
+            long_name: long_name.to_string(),
+            ..MapReport::default()
+        };
+        self.broadcast(from, PortNum::MapReportApp, report.encode_to_vec())
+            .await;
+    }
+
+    /// Tell the BBS whether the packet it sent as `request_id` made it to `from`.
+    pub async fn routed(&mut self, from: u32, to: u32, request_id: u32, delivered: bool) {
+        let reason = if delivered {
+            routing::Error::None
+        } else {
+            routing::Error::MaxRetransmit
+        };
+        let routing = Routing {
+            variant: Some(routing::Variant::ErrorReason(reason as i32)),
+        };
+        self.packet(
+            from,
+            to,
+            0,
+            Data {
+                portnum: PortNum::RoutingApp as i32,
+                payload: routing.encode_to_vec(),
+                request_id,
+                ..Data::default()
+            },
+        )
+        .await;
+    }
+
+    async fn broadcast(&mut self, from: u32, portnum: PortNum, payload: Vec<u8>) {
+        self.packet(
+            from,
+            0xffff_ffff,
+            0,
+            Data {
+                portnum: portnum as i32,
+                payload,
+                ..Data::default()
+            },
+        )
+        .await;
+    }
+
+    async fn packet(&mut self, from: u32, to: u32, channel: u32, data: Data) {
+        let id = self.next_packet_id;
+        self.next_packet_id += 1;
+        self.send(FromRadio {
+            payload_variant: Some(from_radio::PayloadVariant::Packet(MeshPacket {
+                from,
+                to,
+                channel,
+                id,
+                rx_time: now_as_seconds(),
+                payload_variant: Some(mesh_packet::PayloadVariant::Decoded(data)),
+                ..MeshPacket::default()
+            })),
+            ..FromRadio::default()
+        })
+        .await;
+    }
+
+    /// Hand the BBS anything the radio might say.
+    ///
+    /// # Panics
+    ///
+    /// If the BBS has hung up, or the packet is too large to frame.
+    pub async fn send(&mut self, from_radio: FromRadio) {
+        let payload = from_radio.encode_to_vec();
+        let length = u16::try_from(payload.len()).expect("packets should fit in a frame");
+        let mut frame = MAGIC.to_vec();
+        frame.extend(length.to_be_bytes());
+        frame.extend(payload);
+        self.stream
+            .write_all(&frame)
+            .await
+            .expect("the BBS should still be listening");
+    }
+
+    /// The next frame the BBS wrote to the radio, or None if it hung up or went quiet.
+    pub async fn next_frame(&mut self) -> Option<ToRadio> {
+        loop {
+            if let Some(frame) = self.take_frame() {
+                return Some(frame);
+            }
+            let mut chunk = [0u8; 1024];
+            match tokio::time::timeout(WAIT, self.stream.read(&mut chunk)).await {
+                Ok(Ok(0) | Err(_)) | Err(_) => return None,
+                Ok(Ok(read)) => self.buffer.extend_from_slice(&chunk[..read]),
+            }
+        }
+    }
+
+    /// The next packet the BBS asked the radio to transmit, skipping the config requests and
+    /// heartbeats it sends along the way.
+    pub async fn next_packet(&mut self) -> Option<MeshPacket> {
+        loop {
+            if let to_radio::PayloadVariant::Packet(packet) =
+                self.next_frame().await?.payload_variant?
+            {
+                return Some(packet);
+            }
+        }
+    }
+
+    /// Pull a whole frame out of what we've read, if there is one. Bytes before the magic
+    /// number are thrown away, as the radio would.
+    fn take_frame(&mut self) -> Option<ToRadio> {
+        loop {
+            let start = self.buffer.windows(2).position(|bytes| bytes == MAGIC)?;
+            self.buffer.drain(..start);
+            let header = self.buffer.get(..4)?;
+            let length = usize::from(u16::from_be_bytes([header[2], header[3]]));
+            if self.buffer.len() < 4 + length {
+                return None;
+            }
+            let frame: Vec<u8> = self.buffer.drain(..4 + length).skip(4).collect();
+            match ToRadio::decode(&frame[..]) {
+                Ok(to_radio) => return Some(to_radio),
+                Err(err) => log::error!("The BBS wrote a frame we couldn't decode: {err}"),
+            }
+        }
+    }
+}
@@ -0,0 +1,188 @@
+//! Run the whole server against the simulated radio in src/simulator.rs. Build with
+//! `--features simulator`.
+
+use config::Map;
+use diesel::SqliteConnection;
+use diesel_migrations::MigrationHarness;
+use frozenbbs::{
+    db::{self, queued_messages, users},
+    num_id_to_hex, server,
+    simulator::{pair, text_of, Simulator},
+    BBSConfig, CommandConfig, MenuConfig, PublicConfig,
+};
+use meshtastic::protobufs::to_radio;
+
+const BBS: u32 = 0x0000_0001;
+const ALICE: u32 = 0x0000_a11c;
+const BOB: u32 = 0x0000_0b0b;
+const CAROL: u32 = 0x0000_ca01;
+const BROADCAST: u32 = 0xffff_ffff;
+
+/// A fresh in-memory database with every migration applied.
+fn test_connection() -> SqliteConnection {
+    let mut conn = db::establish_connection(&BBSConfig::for_tests());
+    conn.run_pending_migrations(db::MIGRATIONS)
+        .expect("should migrate the test database");
+    conn
+}
+
+fn test_config() -> BBSConfig {
+    let mut menus = Map::new();
+    menus.insert(
+        "General".to_string(),
+        MenuConfig {
+            help_suffix: "G".to_string(),
+            commands: vec![
+                CommandConfig::for_tests("PING", "ping", "always", "ping::ping"),
+                CommandConfig::for_tests(
+                    "DM user msg",
+                    "(?s)dm\\s*(\\S+)\\s+(.+?)\\s*",
+                    "always",
+                    "dm::send",
+                ),
+            ],
+        },
+    );
+    // Not all of the configuration's fields are public, so set what we need one at a time.
+    let mut cfg = BBSConfig::for_tests();
+    cfg.my_id = num_id_to_hex(BBS);
+    cfg.menus = menus;
+    cfg.public = Some(PublicConfig {
+        channels: vec![2],
+        ..PublicConfig::default()
+    });
+    cfg
+}
+
+/// Who the BBS sent a text packet to, on which channel, and what it said.
+async fn next_text(sim: &mut Simulator) -> (u32, u32, String) {
+    let packet = sim
+        .next_packet()
+        .await
+        .expect("the BBS should send a packet");
+    let text = text_of(&packet).expect("the packet should be text");
+    (packet.to, packet.channel, text)
+}
+
+#[tokio::test]
+async fn replies_go_back_to_the_sender_on_their_channel() {
+    let mut conn = test_connection();
+    let cfg = test_config();
+    let (stream, mut sim) = pair();
+
+    let script = async move {
+        // The BBS asks the radio for its config as soon as it connects.
+        let frame = sim.next_frame().await.expect("config request");
+        assert!(matches!(
+            frame.payload_variant,
+            Some(to_radio::PayloadVariant::WantConfigId(_))
+        ));
+
+        sim.text(ALICE, BBS, 1, "ping").await;
+        let (to, channel, welcome) = next_text(&mut sim).await;
+        assert_eq!((to, channel), (ALICE, 1));
+        assert!(welcome.contains("Test BBS"), "{welcome}");
+
+        sim.text(ALICE, BBS, 1, "ping").await;
+        let packet = sim.next_packet().await.expect("pong");
+        assert_eq!(packet.to, ALICE);
+        assert_eq!(packet.channel, 1);
+        assert!(packet.want_ack);
+        assert_eq!(text_of(&packet).as_deref(), Some("pong"));
+        drop(sim);
+    };
+    let (served, ()) = tokio::join!(server::serve_stream(&mut conn, &cfg, stream), script);
+    served.expect("the BBS should serve the simulator");
+}
+
+#[tokio::test]
+async fn public_commands_are_answered_to_everyone() {
+    let mut conn = test_connection();
+    let cfg = test_config();
+    let (stream, mut sim) = pair();
+
+    let script = async move {
+        // Chatter that isn't for the BBS goes unanswered, so the next thing it sends is the
+        // answer to the prefixed command.
+        sim.text(BOB, BROADCAST, 2, "ping").await;
+        sim.text(BOB, BROADCAST, 2, "!bbs ping").await;
+        assert_eq!(
+            next_text(&mut sim).await,
+            (BROADCAST, 2, "pong".to_string())
+        );
+        drop(sim);
+    };
+    let (served, ()) = tokio::join!(server::serve_stream(&mut conn, &cfg, stream), script);
+    served.expect("the BBS should serve the simulator");
+}
+
+#[tokio::test]
+async fn messages_wait_until_the_recipient_is_heard() {
+    let mut conn = test_connection();
+    let cfg = test_config();
+    let (stream, mut sim) = pair();
+
+    let script = async move {
+        sim.node_info(ALICE, "ALC", "Alice").await;
+        sim.map_report(CAROL, "CRL", "Carol").await;
+
+        // Get Bob past the new user welcome, then have him write to Alice.
+        sim.text(BOB, BBS, 0, "ping").await;
+        next_text(&mut sim).await;
+        sim.text(BOB, BBS, 0, "dm ALC hello there").await;
+        let (to, _, published) = next_text(&mut sim).await;
+        assert_eq!(to, BOB);
+        assert!(published.starts_with("Published at"), "{published}");
+
+        // Alice's radio checks in, so the BBS delivers the message, and it's marked sent
+        // once she acknowledges it.
+        sim.node_info(ALICE, "ALC", "Alice").await;
+        let packet = sim.next_packet().await.expect("the message");
+        assert_eq!(packet.to, ALICE);
+        let text = text_of(&packet).expect("text");
+        assert!(text.ends_with("hello there"), "{text}");
+        sim.routed(ALICE, BBS, packet.id, true).await;
+        // Give the BBS a moment to notice the acknowledgement before hanging up.
+        sim.text(BOB, BBS, 0, "ping").await;
+        assert_eq!(next_text(&mut sim).await.2, "pong");
+        drop(sim);
+    };
+    let (served, ()) = tokio::join!(server::serve_stream(&mut conn, &cfg, stream), script);
+    served.expect("the BBS should serve the simulator");
+
+    let alice = users::get(&mut conn, &num_id_to_hex(ALICE)).expect("alice");
+    assert!(queued_messages::get(&mut conn, &alice).is_empty());
+    let carol = users::get(&mut conn, &num_id_to_hex(CAROL)).expect("carol");
+    assert_eq!(carol.short_name(), "CRL");
+}
+
+#[tokio::test]
+async fn only_the_recipient_can_acknowledge_a_message() {
+    let mut conn = test_connection();
+    let cfg = test_config();
+    let (stream, mut sim) = pair();
+
+    let script = async move {
+        sim.node_info(ALICE, "ALC", "Alice").await;
+        sim.text(BOB, BBS, 0, "ping").await;
+        next_text(&mut sim).await;
+        sim.text(BOB, BBS, 0, "dm ALC hello there").await;
+        next_text(&mut sim).await;
+
+        sim.node_info(ALICE, "ALC", "Alice").await;
+        let packet = sim.next_packet().await.expect("the message");
+        assert_eq!(packet.to, ALICE);
+        // The BBS's own radio acknowledges the packet when it hears a neighbor relay it, and
+        // another node might claim to have it, but neither means Alice got it.
+        sim.routed(BBS, BBS, packet.id, true).await;
+        sim.routed(CAROL, BBS, packet.id, true).await;
+        sim.text(BOB, BBS, 0, "ping").await;
+        assert_eq!(next_text(&mut sim).await.2, "pong");
+        drop(sim);
+    };
+    let (served, ()) = tokio::join!(server::serve_stream(&mut conn, &cfg, stream), script);
+    served.expect("the BBS should serve the simulator");
+
+    let alice = users::get(&mut conn, &num_id_to_hex(ALICE)).expect("alice");
+    assert_eq!(queued_messages::get(&mut conn, &alice).len(), 1);
+}